@@ -0,0 +1,350 @@
+//! Compact binary form of URIs for caching.
+//!
+//! [`Uri::to_compact_bytes`] stores the output script of the address, the amount in satoshis and
+//! decoded parameters with length prefixes so that loading the URI with
+//! [`Uri::from_compact_bytes`] doesn't need to parse and percent-decode text. The format is
+//! private to this crate and may change between versions - it's only meant for caches which can
+//! be rebuilt from the textual URIs.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use bitcoin::address::{NetworkChecked, NetworkUnchecked, NetworkValidation};
+use crate::de::{DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::{Param, ParamInner, Uri};
+
+/// Version of the format, the first byte of the compact form.
+const VERSION: u8 = 1;
+
+const HAS_AMOUNT: u8 = 1;
+const HAS_LABEL: u8 = 1 << 1;
+const HAS_MESSAGE: u8 = 1 << 2;
+
+/// Networks distinguished by the address encodings, in the order they are tried.
+///
+/// Signet addresses are the same as testnet ones so they don't need separate value.
+const NETWORKS: [bitcoin::Network; 3] = [bitcoin::Network::Bitcoin, bitcoin::Network::Testnet, bitcoin::Network::Regtest];
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+/// Reads the compact form.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, MalformedError> {
+        let (first, rest) = self.bytes.split_first().ok_or(MalformedError(MalformedErrorInner::Truncated))?;
+        self.bytes = rest;
+        Ok(*first)
+    }
+
+    fn varint(&mut self) -> Result<u64, MalformedError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            // only the lowest bit of the tenth byte fits into u64
+            if shift == 63 && byte > 1 {
+                return Err(MalformedError(MalformedErrorInner::InvalidLength));
+            }
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MalformedError(MalformedErrorInner::InvalidLength))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], MalformedError> {
+        let len = self.varint()?;
+        if len > self.bytes.len() as u64 {
+            return Err(MalformedError(MalformedErrorInner::Truncated));
+        }
+        let (value, rest) = self.bytes.split_at(len as usize);
+        self.bytes = rest;
+        Ok(value)
+    }
+
+    fn param(&mut self) -> Result<Param<'static>, MalformedError> {
+        Ok(Param(ParamInner::UnencodedBytes(Cow::Owned(self.bytes()?.to_vec()))))
+    }
+}
+
+impl<NetVal: NetworkValidation, T> Uri<'_, NetVal, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Encodes the URI in the compact binary form.
+    ///
+    /// The label, the message and values of extras are stored decoded, keys of extras are
+    /// stored as displayed. Use [`Uri::from_compact_bytes`] to decode it.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        use alloc::string::ToString;
+        use crate::ser::SerializeValue;
+
+        let mut bytes = alloc::vec![VERSION];
+        let address = self.address.as_unchecked();
+        // the script alone doesn't determine the address, the network kind does the rest
+        let network = NETWORKS.iter().position(|network| address.is_valid_for_network(*network)).unwrap_or(0);
+        bytes.push(network as u8);
+        write_bytes(&mut bytes, address.assume_checked_ref().script_pubkey().as_bytes());
+
+        let mut flags = 0;
+        if self.amount.is_some() {
+            flags |= HAS_AMOUNT;
+        }
+        if self.label.is_some() {
+            flags |= HAS_LABEL;
+        }
+        if self.message.is_some() {
+            flags |= HAS_MESSAGE;
+        }
+        bytes.push(flags);
+        if let Some(amount) = self.amount {
+            write_varint(&mut bytes, amount.to_sat());
+        }
+        for param in self.label.iter().chain(&self.message) {
+            write_bytes(&mut bytes, &param.decoded_bytes());
+        }
+
+        let extras = self.extras.serialize_params().collect::<Vec<_>>();
+        write_varint(&mut bytes, extras.len() as u64);
+        for (key, value) in extras {
+            write_bytes(&mut bytes, key.to_string().as_bytes());
+            write_bytes(&mut bytes, &value.to_decoded_bytes());
+        }
+        bytes
+    }
+}
+
+impl<T: DeserializeParams<'static>> Uri<'static, NetworkUnchecked, T> {
+    /// Decodes the URI from the compact binary form produced by [`Uri::to_compact_bytes`].
+    ///
+    /// Extras are deserialized from the stored parameters the same way as when parsing, so
+    /// they get validated again. The network has to be checked as usual.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self, FromCompactError<T::Error>> {
+        let mut reader = Reader { bytes };
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(MalformedError(MalformedErrorInner::UnknownVersion(version)).into());
+        }
+        let network = reader.byte()?;
+        let network = *NETWORKS
+            .get(usize::from(network))
+            .ok_or(MalformedError(MalformedErrorInner::UnknownNetwork(network)))?;
+        let script = bitcoin::Script::from_bytes(reader.bytes()?);
+        let address: bitcoin::Address<NetworkChecked> =
+            bitcoin::Address::from_script(script, network).map_err(|_| MalformedError(MalformedErrorInner::InvalidScript))?;
+
+        let flags = reader.byte()?;
+        let amount = if flags & HAS_AMOUNT != 0 {
+            let amount = bitcoin::Amount::from_sat(reader.varint()?);
+            if amount > bitcoin::Amount::MAX_MONEY {
+                return Err(MalformedError(MalformedErrorInner::AmountAboveMaxMoney).into());
+            }
+            Some(amount)
+        } else {
+            None
+        };
+        let label = if flags & HAS_LABEL != 0 { Some(reader.param()?) } else { None };
+        let message = if flags & HAS_MESSAGE != 0 { Some(reader.param()?) } else { None };
+
+        let mut state = T::DeserializationState::default();
+        for _ in 0..reader.varint()? {
+            let key = core::str::from_utf8(reader.bytes()?).map_err(|_| MalformedError(MalformedErrorInner::InvalidKey))?;
+            let value = reader.param()?;
+            if state.deserialize_temp(key, value).map_err(FromCompactError::Extras)? == ParamKind::Unknown && key.starts_with("req-") {
+                return Err(MalformedError(MalformedErrorInner::UnknownRequiredParameter(key.into())).into());
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(MalformedError(MalformedErrorInner::TrailingBytes).into());
+        }
+        let extras = state.finalize().map_err(FromCompactError::Extras)?;
+
+        Ok(Uri {
+            address: address.into_unchecked(),
+            amount,
+            label,
+            message,
+            extras,
+            original: None,
+            had_encoded_params: false,
+        })
+    }
+}
+
+/// Error returned by [`Uri::from_compact_bytes`].
+#[derive(Debug, Clone)]
+pub enum FromCompactError<T> {
+    /// The bytes are not a valid compact form.
+    Malformed(MalformedError),
+    /// Extras failed to deserialize.
+    Extras(T),
+}
+
+impl<T> From<MalformedError> for FromCompactError<T> {
+    fn from(value: MalformedError) -> Self {
+        FromCompactError::Malformed(value)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for FromCompactError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromCompactError::Malformed(error) => fmt::Display::fmt(error, f),
+            FromCompactError::Extras(_) => write!(f, "failed to parse extra argument(s)"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<T: std::error::Error + 'static> std::error::Error for FromCompactError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FromCompactError::Malformed(error) => Some(error),
+            FromCompactError::Extras(error) => Some(error),
+        }
+    }
+}
+
+/// Error returned when the bytes are not a valid compact form.
+#[derive(Debug, Clone)]
+pub struct MalformedError(MalformedErrorInner);
+
+#[derive(Debug, Clone)]
+enum MalformedErrorInner {
+    Truncated,
+    InvalidLength,
+    UnknownVersion(u8),
+    UnknownNetwork(u8),
+    InvalidScript,
+    AmountAboveMaxMoney,
+    InvalidKey,
+    UnknownRequiredParameter(String),
+    TrailingBytes,
+}
+
+impl fmt::Display for MalformedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            MalformedErrorInner::Truncated => write!(f, "the compact URI is truncated"),
+            MalformedErrorInner::InvalidLength => write!(f, "the compact URI contains invalid length"),
+            MalformedErrorInner::UnknownVersion(version) => write!(f, "unknown version {} of the compact URI", version),
+            MalformedErrorInner::UnknownNetwork(network) => write!(f, "unknown network {} of the compact URI", network),
+            MalformedErrorInner::InvalidScript => write!(f, "the script of the compact URI is not an address"),
+            MalformedErrorInner::AmountAboveMaxMoney => write!(f, "the amount of the compact URI exceeds 21 million BTC"),
+            MalformedErrorInner::InvalidKey => write!(f, "a key of the compact URI is not valid UTF-8"),
+            MalformedErrorInner::UnknownRequiredParameter(key) => {
+                write!(f, "the compact URI contains unknown required parameter '{}'", key)
+            },
+            MalformedErrorInner::TrailingBytes => write!(f, "the compact URI contains trailing bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for MalformedError {}
+
+#[cfg(test)]
+mod tests {
+    use super::FromCompactError;
+    use crate::extras::{PayjoinExtras, UnknownParams};
+    use crate::Uri;
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn roundtrip() {
+        for input in [
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=20999999.9769&label=Luke-Jr&message=%FF%26",
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?foo=1&bar=x%3Dy&foo=2",
+            "bitcoin:tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx?amount=0.001",
+            "bitcoin:bcrt1q6rhpng9evdsfnn833a4f4vej0asu6dk5srld6x?label=regtest",
+        ] {
+            let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap();
+            let bytes = uri.to_compact_bytes();
+            let decoded = Uri::<'_, _, UnknownParams<'_>>::from_compact_bytes(&bytes).unwrap();
+            assert_eq!(decoded.assume_checked().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn extras_validated() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pjos=0";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        let decoded = Uri::<'_, _, PayjoinExtras>::from_compact_bytes(&uri.to_compact_bytes()).unwrap();
+        assert_eq!(decoded.extras, uri.extras);
+
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pjos=yes").unwrap();
+        match Uri::<'_, _, PayjoinExtras>::from_compact_bytes(&uri.to_compact_bytes()).unwrap_err() {
+            FromCompactError::Extras(error) => assert_eq!(error.parameter(), "pjos"),
+            FromCompactError::Malformed(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=x"
+            .parse::<Uri<'_, _>>()
+            .unwrap();
+        let bytes = uri.to_compact_bytes();
+        for len in 0..bytes.len() {
+            assert!(Uri::<'_, _>::from_compact_bytes(&bytes[..len]).is_err(), "{}", len);
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(Uri::<'_, _>::from_compact_bytes(&trailing).is_err());
+        let mut version = bytes;
+        version[0] = 2;
+        match Uri::<'_, _>::from_compact_bytes(&version).unwrap_err() {
+            FromCompactError::Malformed(error) => assert_eq!(error.to_string(), "unknown version 2 of the compact URI"),
+            FromCompactError::Extras(never) => match never {},
+        }
+    }
+
+    #[test]
+    fn malformed_amount() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1".parse::<Uri<'_, _>>().unwrap();
+        let bytes = uri.to_compact_bytes();
+        // version, network, script length, script and flags precede the amount
+        let prefix = &bytes[..3 + usize::from(bytes[2]) + 1];
+        let decode = |amount: &[u8]| {
+            let bytes = [prefix, amount, &[0]].concat();
+            match Uri::<'_, _>::from_compact_bytes(&bytes) {
+                Ok(uri) => Ok(uri.amount.unwrap()),
+                Err(FromCompactError::Malformed(error)) => Err(error.to_string()),
+                Err(FromCompactError::Extras(never)) => match never {},
+            }
+        };
+        let varint = |value: u64| {
+            let mut bytes = alloc::vec::Vec::new();
+            super::write_varint(&mut bytes, value);
+            bytes
+        };
+
+        assert_eq!(decode(&varint(bitcoin::Amount::MAX_MONEY.to_sat())), Ok(bitcoin::Amount::MAX_MONEY));
+        let above = Err("the amount of the compact URI exceeds 21 million BTC".to_string());
+        assert_eq!(decode(&varint(bitcoin::Amount::MAX_MONEY.to_sat() + 1)), above);
+        assert_eq!(decode(&varint(u64::MAX)), above);
+        let overflow = Err("the compact URI contains invalid length".to_string());
+        assert_eq!(decode(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02]), overflow);
+        assert_eq!(decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x81]), overflow);
+    }
+}
@@ -9,38 +9,679 @@
 use alloc::borrow::ToOwned;
 use alloc::borrow::Cow;
 use alloc::string::String;
+use alloc::vec::Vec;
 use core::convert::{TryFrom, TryInto};
 use bitcoin::amount::{Denomination, ParseAmountError};
 use bitcoin::address::ParseError as AddressError;
 use bitcoin::address::NetworkValidation;
 use core::fmt;
-use super::{Uri, Param};
+use core::marker::PhantomData;
+use super::{Uri, Param, NoExtras};
 use percent_encoding_rfc3986::PercentDecodeError;
 
+/// Options altering the behavior of parsing.
+///
+/// All options are off by default which results in BIP21-compliant parsing. The only limit
+/// enforced by default is [`DEFAULT_MAX_LENGTH`].
+/// Use [`Uri::parse_with_options`] to parse with non-default options.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    reject_unnecessary_encoding: bool,
+    allow_semicolon_separator: bool,
+    amount_in_sats: bool,
+    plus_as_space: bool,
+    allow_web_prefix: bool,
+    normalize_escape_case: bool,
+    assume_scheme: bool,
+    reject_trailing_separator: bool,
+    max_length: usize,
+    max_params: usize,
+    retain_original: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            reject_unnecessary_encoding: false,
+            allow_semicolon_separator: false,
+            amount_in_sats: false,
+            plus_as_space: false,
+            allow_web_prefix: false,
+            normalize_escape_case: false,
+            assume_scheme: false,
+            reject_trailing_separator: false,
+            max_length: DEFAULT_MAX_LENGTH,
+            max_params: usize::MAX,
+            retain_original: false,
+        }
+    }
+}
+
+/// The default maximum length of parsed URIs in bytes.
+///
+/// This is far above the capacity of QR codes and the lengths of real-world URIs but it bounds
+/// the work and memory spent on pathological inputs from untrusted sources.
+pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024;
+
+/// The string a URI was parsed from, the options used to parse it and the parsed fields.
+///
+/// The fields allow detecting modifications without parsing the input again, see
+/// [`Uri::unmodified_original`].
+#[derive(Debug, Clone)]
+pub(crate) struct Original<'a> {
+    pub(crate) input: &'a str,
+    pub(crate) options: ParseOptions,
+    pub(crate) fields: ParsedFields<'a>,
+}
+
+/// The fields of a URI as they were parsed.
+#[derive(Debug, Clone)]
+pub(crate) struct ParsedFields<'a> {
+    pub(crate) address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+    pub(crate) amount: Option<bitcoin::Amount>,
+    pub(crate) label: Option<Param<'a>>,
+    pub(crate) message: Option<Param<'a>>,
+    /// Keys and values of the parameters recognized by extras, in the order of the input.
+    pub(crate) extras: Vec<(&'a str, Param<'a>)>,
+}
+
+impl<'a> Original<'a> {
+    /// Returns the input with escapes in upper case if requested by
+    /// [`ParseOptions::normalize_escape_case`].
+    pub(crate) fn normalized_input(&self) -> Cow<'a, str> {
+        if !self.options.normalize_escape_case || !has_lowercase_escape(self.input) {
+            return Cow::Borrowed(self.input);
+        }
+        let mut normalized = String::with_capacity(self.input.len());
+        let mut rest = self.input;
+        while let Some(pos) = rest.find('%') {
+            normalized.push_str(&rest[..=pos]);
+            rest = &rest[(pos + 1)..];
+            let digits = rest.bytes().take(2).take_while(u8::is_ascii_hexdigit).count();
+            if digits == 2 {
+                normalized.push_str(&rest[..2].to_ascii_uppercase());
+                rest = &rest[2..];
+            }
+        }
+        normalized.push_str(rest);
+        Cow::Owned(normalized)
+    }
+}
+
+impl ParseOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates options accepting all known non-standard forms of URIs.
+    ///
+    /// This enables [`allow_web_prefix`](Self::allow_web_prefix),
+    /// [`normalize_escape_case`](Self::normalize_escape_case),
+    /// [`assume_scheme`](Self::assume_scheme),
+    /// [`allow_semicolon_separator`](Self::allow_semicolon_separator) and
+    /// [`plus_as_space`](Self::plus_as_space). [`amount_in_sats`](Self::amount_in_sats) is not
+    /// enabled since it changes the meaning of standard URIs rather than accepting more of them.
+    /// The limits stay at their defaults.
+    pub fn lenient() -> Self {
+        ParseOptions {
+            allow_web_prefix: true,
+            normalize_escape_case: true,
+            assume_scheme: true,
+            allow_semicolon_separator: true,
+            plus_as_space: true,
+            ..Default::default()
+        }
+    }
+
+    /// Accepts the `web+` prefix of the scheme (`web+bitcoin:`).
+    ///
+    /// **Non-standard**: browsers only allow registering protocol handlers for schemes prefixed
+    /// with `web+` so URIs passed by them may look like `web+bitcoin:address`. The prefix is
+    /// case-insensitive and it's stripped before parsing. Positions in errors and warnings are
+    /// still relative to the whole input. URIs parsed from prefixed input don't retain
+    /// [`Uri::original`] since it isn't a `bitcoin:` URI.
+    pub fn allow_web_prefix(mut self, value: bool) -> Self {
+        self.allow_web_prefix = value;
+        self
+    }
+
+    /// Treats lowercase hex digits in percent-encoding (`%2f`) as their uppercase form (`%2F`).
+    ///
+    /// Both are decoded the same way and the serializer always emits uppercase as recommended by
+    /// RFC 3986, so this only affects the checks of the retained input. [`Uri::original`] still
+    /// returns the input as given but [`Uri::is_canonical`] and [`Uri::canonicalization_diff`]
+    /// compare its copy with escapes in upper case, so they only ignore the case of escapes.
+    pub fn normalize_escape_case(mut self, value: bool) -> Self {
+        self.normalize_escape_case = value;
+        self
+    }
+
+    /// Treats input without a scheme as if it was prefixed with `bitcoin:`.
+    ///
+    /// **Non-standard**: clipboard contents sometimes contain only the part after the scheme,
+    /// e.g. `address?amount=1`. Input is considered scheme-less if there's no `:` before the
+    /// query, so other schemes are still rejected. Positions in errors and warnings are relative
+    /// to the input as given. URIs parsed this way don't retain [`Uri::original`] since the input
+    /// isn't a valid URI.
+    pub fn assume_scheme(mut self, value: bool) -> Self {
+        self.assume_scheme = value;
+        self
+    }
+
+    /// Rejects a trailing `&` at the end of the query, e.g. `bitcoin:address?amount=1&`.
+    ///
+    /// A single trailing separator is ignored by default since such URIs appear in the wild and
+    /// the meaning is clear. Two or more trailing separators are always rejected.
+    pub fn reject_trailing_separator(mut self, value: bool) -> Self {
+        self.reject_trailing_separator = value;
+        self
+    }
+
+    /// Sets the maximum length of the parsed URI in bytes, [`DEFAULT_MAX_LENGTH`] by default.
+    ///
+    /// Longer inputs are rejected before doing any other work. Decoding is linear-time and the
+    /// memory allocated while parsing is proportional to the input so this bounds both.
+    pub fn max_length(mut self, value: usize) -> Self {
+        self.max_length = value;
+        self
+    }
+
+    /// Sets the maximum number of parameters in the query, unlimited by default.
+    ///
+    /// All parameters count, including `amount`, `label` and `message`. This bounds the work
+    /// of extras and the memory of collections such as [`UnknownParams`](crate::extras::UnknownParams)
+    /// more tightly than [`max_length`](Self::max_length) alone, e.g. on servers parsing untrusted
+    /// input.
+    pub fn max_params(mut self, value: usize) -> Self {
+        self.max_params = value;
+        self
+    }
+
+    /// Retains the input so that [`Uri::original`] returns it, disabled by default.
+    ///
+    /// The URI then keeps the options and a copy of the parsed fields too, so that it can tell
+    /// whether the input still describes it. This is needed by [`Uri::is_canonical`],
+    /// [`Uri::canonicalization_diff`] and [`AmountForm::Faithful`](crate::ser::AmountForm::Faithful)
+    /// to inspect the input rather than the output of `Display`. Only borrowing parses retain
+    /// the input.
+    pub fn retain_original(mut self, value: bool) -> Self {
+        self.retain_original = value;
+        self
+    }
+
+    /// Rejects keys and values containing percent-encoded `unreserved` characters.
+    ///
+    /// RFC 3986 `unreserved` characters (`ALPHA / DIGIT / "-" / "." / "_" / "~"`) never need to
+    /// be encoded so URIs containing e.g. `%41` instead of `A` are not in minimal form. This
+    /// can be used to detect obfuscated or non-canonical URIs. The returned error reports the
+    /// position of the offending escape via [`UriError::position`].
+    pub fn reject_unnecessary_encoding(mut self, value: bool) -> Self {
+        self.reject_unnecessary_encoding = value;
+        self
+    }
+
+    /// Accepts `;` as parameter separator in addition to `&`.
+    ///
+    /// **Non-standard**: BIP21 mandates `&` but some legacy producers used `;` following old HTML
+    /// form conventions. Note that with this option enabled `;` can not appear in values
+    /// unencoded.
+    pub fn allow_semicolon_separator(mut self, value: bool) -> Self {
+        self.allow_semicolon_separator = value;
+        self
+    }
+
+    /// Interprets the amount as an integer number of satoshis instead of BTC.
+    ///
+    /// **Non-standard**: BIP21 mandates the amount in BTC but some misbehaving producers emit
+    /// e.g. `amount=1000` meaning satoshis. Only enable this for URIs from such producers. In
+    /// this mode amounts containing a decimal point are rejected.
+    pub fn amount_in_sats(mut self, value: bool) -> Self {
+        self.amount_in_sats = value;
+        self
+    }
+
+    /// Decodes `+` in values as space.
+    ///
+    /// **Non-standard**: RFC 3986 doesn't give `+` any special meaning but some producers encode
+    /// spaces as `+` following `application/x-www-form-urlencoded`. Encoded plus (`%2B`) is
+    /// still decoded as `+` and spaces are displayed as `%20` so re-serialized URIs are
+    /// standard. Note that values containing `+` are copied when decoding.
+    pub fn plus_as_space(mut self, value: bool) -> Self {
+        self.plus_as_space = value;
+        self
+    }
+}
+
+/// Decodes the value of a parameter taking `options` into account.
+fn decode_param<'a>(value: &'a str, options: &ParseOptions) -> Result<Param<'a>, PercentDecodeError> {
+    if options.plus_as_space && value.contains('+') {
+        Ok(Param::decode(&value.replace('+', "%20"))?.decode_into_owned())
+    } else {
+        Param::decode(value)
+    }
+}
+
+/// Returns the offset of the percent-escape cut off by the end of `value`, if any.
+///
+/// This is `%` or `%` followed by a single hex digit at the very end of the value.
+fn find_truncated_escape(value: &str) -> Option<usize> {
+    let bytes = value.as_bytes();
+    match bytes {
+        [.., b'%'] => Some(bytes.len() - 1),
+        [.., b'%', digit] if digit.is_ascii_hexdigit() => Some(bytes.len() - 2),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `value` contains a percent-encoded byte with lowercase hex digit.
+pub(crate) fn has_lowercase_escape(value: &str) -> bool {
+    value
+        .as_bytes()
+        .windows(3)
+        .any(|window| window[0] == b'%' && window[1..].iter().all(u8::is_ascii_hexdigit) && window[1..].iter().any(u8::is_ascii_lowercase))
+}
+
+/// Returns the offset of the first whitespace if the scheme of `input` is `bitcoin` corrupted
+/// by whitespace, e.g. `bit coin:` or `bitcoin :`.
+fn find_whitespace_in_scheme(input: &str) -> Option<usize> {
+    let (scheme, _) = input.split_once(':')?;
+    let offset = scheme.find(char::is_whitespace)?;
+    let mut letters = scheme.chars().filter(|c| !c.is_whitespace());
+    let is_bitcoin = "bitcoin"
+        .chars()
+        .all(|expected| matches!(letters.next(), Some(c) if c.eq_ignore_ascii_case(&expected)));
+    if is_bitcoin && letters.next().is_none() {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+/// Checks that `value` is an amount in BTC returning the violation and its offset.
+///
+/// Returns `value` without the zeros beyond the 8th decimal on success, these are rejected by
+/// [`bitcoin::Amount::from_str_in`].
+///
+/// BIP21 only says the amount is a decimal number of BTC and its ABNF allows even `.` alone.
+/// The accepted grammar is stricter: `1*8DIGIT [ "." 1*DIGIT ]` with at most 8 significant
+/// decimal digits. More integer digits would be above the supply anyway and more decimal digits
+/// can't be represented in satoshis. Trailing zeros beyond the 8th decimal are accepted since
+/// they don't change the value.
+///
+/// Common mistakes get their own errors: signs (`+1`), hexadecimal (`0x10`), scientific
+/// notation (`1e3`) and decimal comma (`1,5`) used in some locales.
+fn check_amount_grammar(value: &str) -> Result<&str, (AmountGrammarError, usize)> {
+    if value.is_empty() {
+        return Err((AmountGrammarError::Empty, 0));
+    }
+    if value.starts_with(['+', '-']) {
+        return Err((AmountGrammarError::SignNotAllowed, 0));
+    }
+    if value.starts_with("0x") || value.starts_with("0X") {
+        return Err((AmountGrammarError::HexadecimalNotation, 1));
+    }
+    if let Some(offset) = value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        let error = match value[offset..].chars().next().expect("offset is a char boundary") {
+            'e' | 'E' if offset > 0 => AmountGrammarError::ScientificNotation,
+            ',' if offset > 0 => AmountGrammarError::DecimalComma,
+            c => AmountGrammarError::InvalidCharacter(c),
+        };
+        return Err((error, offset));
+    }
+    let (integer, fraction) = match value.find('.') {
+        Some(pos) => (&value[..pos], Some(&value[(pos + 1)..])),
+        None => (value, None),
+    };
+    if integer.is_empty() {
+        return Err((AmountGrammarError::LeadingDecimalPoint, 0));
+    }
+    if integer.len() > MAX_AMOUNT_INTEGER_DIGITS {
+        return Err((AmountGrammarError::TooManyIntegerDigits, MAX_AMOUNT_INTEGER_DIGITS));
+    }
+    if let Some(fraction) = fraction {
+        let fraction_offset = integer.len() + 1;
+        if fraction.is_empty() {
+            return Err((AmountGrammarError::TrailingDecimalPoint, integer.len()));
+        }
+        if let Some(pos) = fraction.find('.') {
+            return Err((AmountGrammarError::InvalidCharacter('.'), fraction_offset + pos));
+        }
+        // zeros beyond the last representable decimal don't change the value
+        if let Some(pos) = fraction.get(MAX_AMOUNT_DECIMALS..).and_then(|excess| excess.find(|c| c != '0')) {
+            return Err((AmountGrammarError::TooManyDecimals, fraction_offset + MAX_AMOUNT_DECIMALS + pos));
+        }
+        return Ok(&value[..(fraction_offset + fraction.len().min(MAX_AMOUNT_DECIMALS))]);
+    }
+    Ok(value)
+}
+
+/// Returns the position of the first percent-escape encoding an `unreserved` character.
+pub(crate) fn find_unnecessary_encoding(value: &str) -> Option<usize> {
+    let bytes = value.as_bytes();
+    bytes.iter().enumerate().find_map(|(i, byte)| {
+        let hex = bytes.get((i + 1)..(i + 3)).filter(|_| *byte == b'%')?;
+        // invalid escapes are reported by the decoder
+        let decoded = u8::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()?;
+        if decoded.is_ascii_alphanumeric() || b"-._~".contains(&decoded) {
+            Some(i)
+        } else {
+            None
+        }
+    })
+}
+
 impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T> {
+    /// Parses the URI using non-default `options`.
+    ///
+    /// This is zero-copy, just like `TryFrom<&str>`.
+    pub fn parse_with_options(string: &'a str, options: &ParseOptions) -> Result<Self, Error<T::Error>> {
+        Self::deserialize_raw(string, options, None, None)
+    }
+
+    /// Parses the URI collecting non-fatal issues.
+    ///
+    /// The URI is parsed the same way as with `TryFrom<&str>` but recoverable oddities are
+    /// reported as [`Warning`]s so that wallets can surface them as soft alerts. Warnings found
+    /// before the parsing failed are returned even if it fails.
+    pub fn parse_with_warnings(string: &'a str) -> (Result<Self, Error<T::Error>>, Vec<Warning>) {
+        let mut warnings = Vec::new();
+        let result = Self::deserialize_raw(string, &Default::default(), Some(&mut warnings), None);
+        (result, warnings)
+    }
+
+    /// Parses the URI dropping malformed parameters instead of failing.
+    ///
+    /// This is a best-effort parse for display purposes ("just show me something"). Parameters
+    /// that would fail BIP21 parsing - e.g. invalid amount, invalid percent-encoding or missing
+    /// `=` - are dropped and each dropped parameter is recorded as [`Fixup`]. Invalid scheme or
+    /// address, unknown `req-` parameters as well as errors of extras still fail the parsing,
+    /// since showing such URI would misrepresent what it requests.
+    ///
+    /// **Warning**: since required parameters may be dropped, the returned URI must **not** be
+    /// used for making payments!
+    pub fn try_from_uri_str_lossy(string: &'a str) -> Result<(Self, Vec<Fixup>), Error<T::Error>> {
+        let mut fixups = Vec::new();
+        let uri = Self::deserialize_raw(string, &Default::default(), None, Some(&mut fixups))?;
+        Ok((uri, fixups))
+    }
+
+    /// Implements deserialization using the default state of extras.
+    pub(crate) fn deserialize_raw(
+        input: &'a str,
+        options: &ParseOptions,
+        warnings: Option<&mut Vec<Warning>>,
+        fixups: Option<&mut Vec<Fixup>>,
+    ) -> Result<Self, Error<T::Error>> {
+        Self::deserialize_with_state(input, options, warnings, fixups, T::DeserializationState::default(), parse_address)
+    }
+
+    /// Parses the URI using `parser` to parse the address.
+    ///
+    /// This allows supporting address formats newer than the linked version of [`bitcoin`] or
+    /// experimental ones. The parser receives the address part of the URI, which is only
+    /// checked not to be empty and not to contain `&`, `=` or control characters, and must
+    /// return the address it represents. Everything else is parsed the same way as with
+    /// `TryFrom<&str>`. Note that displaying the URI writes the returned address, not the input.
+    /// For the same reason the URI doesn't retain [`Uri::original`].
+    pub fn parse_with_address_parser<F, E>(input: &'a str, parser: F) -> Result<Self, CustomAddressError<E, T::Error>>
+    where
+        F: FnOnce(&str) -> Result<bitcoin::Address<bitcoin::address::NetworkUnchecked>, E>,
+    {
+        Self::parse_with_options_and_address_parser(input, &Default::default(), parser)
+    }
+
+    /// Parses the URI using non-default `options` and `parser` to parse the address.
+    ///
+    /// This combines [`parse_with_options`](Self::parse_with_options) and
+    /// [`parse_with_address_parser`](Self::parse_with_address_parser).
+    pub fn parse_with_options_and_address_parser<F, E>(
+        input: &'a str,
+        options: &ParseOptions,
+        parser: F,
+    ) -> Result<Self, CustomAddressError<E, T::Error>>
+    where
+        F: FnOnce(&str) -> Result<bitcoin::Address<bitcoin::address::NetworkUnchecked>, E>,
+    {
+        let mut address_error = None;
+        let parse_address = |address: &'a str| {
+            parser(address).map_err(|error| {
+                address_error = Some(error);
+                // placeholder, replaced by the error of the parser below
+                Error::Uri(UriError(UriErrorInner::MissingAddress))
+            })
+        };
+        let state = T::DeserializationState::default();
+        let result = Self::deserialize_with_state(input, options, None, None, state, parse_address);
+        match (result, address_error) {
+            (_, Some(error)) => Err(CustomAddressError::Address(error)),
+            (Ok(uri), None) => Ok(Uri { original: None, ..uri }),
+            (Err(error), None) => Err(CustomAddressError::Uri(error)),
+        }
+    }
+}
+
+/// Parses the address using [`bitcoin`], the default parser of addresses.
+fn parse_address<E>(address: &str) -> Result<bitcoin::Address<bitcoin::address::NetworkUnchecked>, Error<E>> {
+    address.parse().map_err(Error::uri)
+}
+
+/// Error returned by [`Uri::parse_with_address_parser`].
+#[derive(Debug, Clone)]
+pub enum CustomAddressError<A, T> {
+    /// The address was rejected by the custom parser.
+    Address(A),
+    /// The rest of the URI is invalid.
+    Uri(Error<T>),
+}
+
+impl<A: fmt::Display, T: fmt::Display> fmt::Display for CustomAddressError<A, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // unlike `Error` this includes the message of the source so that the common
+        // `.map_err(|error| error.to_string())` doesn't lose the reason
+        match self {
+            CustomAddressError::Address(error) => write!(f, "the address was rejected by the address parser: {}", error),
+            CustomAddressError::Uri(Error::Uri(error)) => write!(f, "invalid BIP21 URI: {}", error),
+            CustomAddressError::Uri(Error::Extras(error)) => write!(f, "failed to parse extra argument(s): {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<A: std::error::Error + 'static, T: fmt::Display + std::error::Error + 'static> std::error::Error for CustomAddressError<A, T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CustomAddressError::Address(error) => Some(error),
+            CustomAddressError::Uri(error) => Some(error),
+        }
+    }
+}
+
+impl<'a> Uri<'a, bitcoin::address::NetworkUnchecked, NoExtras> {
+    /// Parses the URI passing extra parameters to `callback`.
+    ///
+    /// This is a lightweight alternative to implementing [`DeserializeParams`] for ad-hoc
+    /// integrations, e.g. when only a single custom parameter is needed. The callback receives
+    /// the key and the value of each parameter other than `amount`, `label` and `message` and
+    /// decides whether it's known. It can store the values in a caller-owned structure. Unknown
+    /// `req-` parameters are rejected as usual and errors returned by the callback are
+    /// returned as [`Error::Extras`].
+    pub fn parse_with<F, E>(string: &'a str, callback: F) -> Result<Self, Error<E>>
+    where
+        F: FnMut(&'a str, Param<'a>) -> Result<ParamKind, E>,
+    {
+        let state = ParamCallbackState {
+            callback,
+            _error: PhantomData,
+        };
+        let uri = Uri::deserialize_with_state(string, &Default::default(), None, None, state, parse_address)?;
+        Ok(Uri {
+            address: uri.address,
+            amount: uri.amount,
+            label: uri.label,
+            message: uri.message,
+            extras: NoExtras,
+            original: uri.original,
+            had_encoded_params: uri.had_encoded_params,
+        })
+    }
+}
+
+/// Extras produced by [`ParamCallbackState`].
+struct ParamCallbackExtras<E>(PhantomData<fn() -> E>);
+
+impl<E> DeserializationError for ParamCallbackExtras<E> {
+    type Error = E;
+}
+
+/// State forwarding parameters to a callback.
+///
+/// This is not a [`DeserializationState`] since there's no default callback and the callback
+/// needs keys borrowed from the input.
+struct ParamCallbackState<F, E> {
+    callback: F,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<'a, F: FnMut(&'a str, Param<'a>) -> Result<ParamKind, E>, E> ParamSink<'a> for ParamCallbackState<F, E> {
+    type Value = ParamCallbackExtras<E>;
+
+    fn normalize_key<'k>(&mut self, key: &'k str) -> &'k str {
+        key
+    }
+
+    fn deserialize(&mut self, key: &'a str, value: Param<'a>) -> Result<ParamKind, E> {
+        (self.callback)(key, value)
+    }
+
+    fn finalize(self) -> Result<ParamCallbackExtras<E>, E> {
+        Ok(ParamCallbackExtras(PhantomData))
+    }
+}
+
+/// Receiver of the extra parameters while parsing.
+///
+/// This is the part of [`DeserializationState`] used by the parser, it's also implemented by
+/// the state of [`Uri::parse_with`].
+trait ParamSink<'a> {
+    type Value: DeserializationError;
+
+    fn normalize_key<'k>(&mut self, key: &'k str) -> &'k str;
+
+    fn deserialize(&mut self, key: &'a str, value: Param<'a>) -> Result<ParamKind, <Self::Value as DeserializationError>::Error>;
+
+    fn finalize(self) -> Result<Self::Value, <Self::Value as DeserializationError>::Error>;
+}
+
+impl<'a, S: DeserializationState<'a>> ParamSink<'a> for S {
+    type Value = S::Value;
+
+    fn normalize_key<'k>(&mut self, key: &'k str) -> &'k str {
+        DeserializationState::normalize_key(self, key)
+    }
+
+    fn deserialize(&mut self, key: &'a str, value: Param<'a>) -> Result<ParamKind, <Self::Value as DeserializationError>::Error> {
+        self.deserialize_borrowed(key, value)
+    }
+
+    fn finalize(self) -> Result<Self::Value, <Self::Value as DeserializationError>::Error> {
+        DeserializationState::finalize(self)
+    }
+}
+
+impl<'a, T: DeserializationError> Uri<'a, bitcoin::address::NetworkUnchecked, T> {
     /// Implements deserialization.
-    fn deserialize_raw(string: &'a str) -> Result<Self, Error<T::Error>> {
+    ///
+    /// Warnings are only collected if `warnings` is `Some`. If `fixups` is `Some` invalid
+    /// parameters are dropped and recorded instead of failing.
+    fn deserialize_with_state<S, A>(
+        input: &'a str,
+        options: &ParseOptions,
+        mut warnings: Option<&mut Vec<Warning>>,
+        mut fixups: Option<&mut Vec<Fixup>>,
+        mut deserializer: S,
+        parse_address: A,
+    ) -> Result<Self, Error<T::Error>>
+    where
+        S: ParamSink<'a, Value = T>,
+        A: FnOnce(&'a str) -> Result<bitcoin::Address<bitcoin::address::NetworkUnchecked>, Error<T::Error>>,
+    {
         const SCHEME: &str = "bitcoin:";
-        if string.len() < SCHEME.len() {
-            return Err(Error::Uri(UriError(UriErrorInner::TooShort)));
+        const WEB_PREFIX: &str = "web+";
+        let mut string = input;
+        let mut warn = |warning| {
+            if let Some(warnings) = warnings.as_mut() {
+                warnings.push(warning);
+            }
+        };
+        let position = |part: &str| part.as_ptr() as usize - input.as_ptr() as usize;
+        if string.len() > options.max_length {
+            return Err(Error::Uri(UriError(UriErrorInner::TooLong {
+                length: string.len(),
+                max: options.max_length,
+            })));
         }
-
-        if !string.get(..SCHEME.len()).map_or(false, |s| s.eq_ignore_ascii_case(SCHEME)) {
-            return Err(Error::Uri(UriError(UriErrorInner::InvalidScheme)));
+        let web_prefix_stripped =
+            options.allow_web_prefix && matches!(string.get(..WEB_PREFIX.len()), Some(prefix) if prefix.eq_ignore_ascii_case(WEB_PREFIX));
+        if web_prefix_stripped {
+            string = &string[WEB_PREFIX.len()..];
         }
+        // addresses never contain `:` so its absence before the query means there's no scheme
+        let scheme_assumed = options.assume_scheme && !string.split('?').next().unwrap_or(string).contains(':');
+        if !scheme_assumed {
+            if string.len() < SCHEME.len() {
+                return Err(Error::Uri(UriError(UriErrorInner::TooShort)));
+            }
+
+            match string.get(..SCHEME.len()) {
+                Some(SCHEME) => (),
+                Some(scheme) if scheme.eq_ignore_ascii_case(SCHEME) => warn(Warning::NonLowercaseScheme),
+                _ => {
+                    if let Some(offset) = find_whitespace_in_scheme(string) {
+                        return Err(Error::Uri(UriError(UriErrorInner::WhitespaceInScheme {
+                            position: position(string) + offset,
+                        })));
+                    }
+                    return Err(Error::Uri(UriError(UriErrorInner::InvalidScheme)));
+                },
+            }
 
-        let string = &string[SCHEME.len()..];
+            string = &string[SCHEME.len()..];
+        }
 
         let (address, params) = match string.find('?') {
             Some(pos) => (&string[..pos], Some(&string[(pos + 1)..])),
             None => (string, None),
         };
 
-        let address = address.parse().map_err(Error::uri)?;
-        let mut deserializer = T::DeserializationState::default();
+        // BIP21 makes the address mandatory, addressless URIs are not representable by `Uri` but
+        // they are reported separately from malformed ones
+        if address.is_empty() {
+            let addressless_param = params
+                .map(|params| params.split('#').next().unwrap_or(params))
+                .into_iter()
+                .flat_map(|params| params.split('&'))
+                .map(|param| param.split('=').next().unwrap_or(param))
+                .find(|key| ADDRESSLESS_PARAMS.contains(key));
+            return match addressless_param {
+                Some(key) => Err(Error::Uri(UriError(UriErrorInner::Addressless(key.to_owned())))),
+                None => Err(Error::Uri(UriError(UriErrorInner::MissingAddress))),
+            };
+        }
+        // reject garbage early so that it doesn't produce confusing errors from the address parser
+        if let Some(offset) = address.bytes().position(|byte| matches!(byte, b'&' | b'=') || byte.is_ascii_control()) {
+            return Err(Error::Uri(UriError(UriErrorInner::InvalidAddressCharacter {
+                byte: address.as_bytes()[offset],
+                position: position(address) + offset,
+            })));
+        }
+        let address = parse_address(address)?;
         let mut amount = None;
         let mut label = None;
         let mut message = None;
+        let mut had_encoded_params = false;
+        let mut parsed_extras = Vec::new();
         if let Some(params) = params {
             // [RFC 3986 § 3.4](https://www.rfc-editor.org/rfc/rfc3986#section-3.4):
             //
@@ -52,36 +693,158 @@ impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T
                 None => params,
             };
 
-            for param in params.split('&') {
-                let pos = param
-                    .find('=')
-                    .ok_or_else(|| Error::Uri(UriError(UriErrorInner::MissingEquals(param.to_owned()))))?;
-                let key = &param[..pos];
-                let value = &param[(pos + 1)..];
-                match key {
-                    "amount" => {
-                        let parsed_amount = bitcoin::Amount::from_str_in(value, Denomination::Bitcoin).map_err(Error::uri)?;
-                        amount = Some(parsed_amount);
-                    },
-                    "label" => {
-                        let label_decoder = Param::decode(value).map_err(Error::percent_decode_static("label"))?;
-                        label = Some(label_decoder);
-                    },
-                    "message" => {
-                        let message_decoder = Param::decode(value).map_err(Error::percent_decode_static("message"))?;
-                        message = Some(message_decoder);
-                    },
-                    extra_key => {
-                        let decoder = Param::decode(value).map_err(Error::percent_decode(key))?;
-                        let is_known = deserializer.deserialize_borrowed(extra_key, decoder).map_err(Error::Extras)?;
-                        if is_known == ParamKind::Unknown && extra_key.starts_with("req-") {
-                            return Err(Error::Uri(UriError(UriErrorInner::UnknownRequiredParameter(extra_key.to_owned()))));
+            let allow_semicolon = options.allow_semicolon_separator;
+            let params = match params.strip_suffix('&') {
+                Some(_) if options.reject_trailing_separator => {
+                    return Err(Error::Uri(UriError(UriErrorInner::TrailingSeparator {
+                        position: position(params) + params.len() - 1,
+                    })));
+                },
+                Some(stripped) => {
+                    warn(Warning::TrailingSeparator {
+                        position: position(params) + stripped.len(),
+                    });
+                    stripped
+                },
+                None => params,
+            };
+            // `bitcoin:address?` has an empty query which contains no parameters
+            let params = Some(params).filter(|params| !params.is_empty());
+            let split_params = || {
+                params
+                    .into_iter()
+                    .flat_map(|params| params.split(move |c| c == '&' || (allow_semicolon && c == ';')))
+            };
+            for (index, param) in split_params().enumerate() {
+                if index == options.max_params {
+                    return Err(Error::Uri(UriError(UriErrorInner::TooManyParams {
+                        count: split_params().count(),
+                        limit: options.max_params,
+                    })));
+                }
+                let mut deserialize_param = || -> Result<(), Error<T::Error>> {
+                    let pos = param
+                        .find('=')
+                        .ok_or_else(|| Error::Uri(UriError(UriErrorInner::MissingEquals(param.to_owned()))))?;
+                    let key = &param[..pos];
+                    let value = &param[(pos + 1)..];
+                    // keys are not decoded so `%6Cabel` is an unknown key rather than `label`
+                    for part in [key, value] {
+                        if let Some(offset) = find_unnecessary_encoding(part) {
+                            let position = position(part) + offset;
+                            if options.reject_unnecessary_encoding {
+                                return Err(Error::Uri(UriError(UriErrorInner::UnnecessaryEncoding {
+                                    parameter: key.to_owned(),
+                                    position,
+                                })));
+                            }
+                            warn(Warning::UnnecessaryEncoding { position });
+                        }
+                    }
+                    if key != "amount" {
+                        if let Some(offset) = find_truncated_escape(value) {
+                            return Err(Error::Uri(UriError(UriErrorInner::TruncatedPercentEscape {
+                                parameter: key.to_owned(),
+                                position: position(value) + offset,
+                            })));
                         }
-                    },
+                    }
+                    if value.len() > LONG_VALUE_LENGTH {
+                        warn(Warning::LongValue {
+                            position: position(value),
+                            length: value.len(),
+                        });
+                    }
+                    match key {
+                        "amount" => {
+                            let (value, denomination) = if options.amount_in_sats {
+                                if value.starts_with(['+', '-']) {
+                                    return Err(Error::Uri(UriError(UriErrorInner::AmountGrammar {
+                                        error: AmountGrammarError::SignNotAllowed,
+                                        position: position(value),
+                                    })));
+                                }
+                                if let Some(offset) = value.find('.') {
+                                    return Err(Error::Uri(UriError(UriErrorInner::DecimalPointInSats {
+                                        position: position(value) + offset,
+                                    })));
+                                }
+                                (value, Denomination::Satoshi)
+                            } else {
+                                match check_amount_grammar(value) {
+                                    Ok(value) => (value, Denomination::Bitcoin),
+                                    Err((error, offset)) => {
+                                        return Err(Error::Uri(UriError(UriErrorInner::AmountGrammar {
+                                            error,
+                                            position: position(value) + offset,
+                                        })))
+                                    },
+                                }
+                            };
+                            let parsed_amount = bitcoin::Amount::from_str_in(value, denomination).map_err(Error::uri)?;
+                            if parsed_amount > bitcoin::Amount::MAX_MONEY {
+                                return Err(Error::Uri(UriError(UriErrorInner::AmountAboveMaxMoney { position: position(value) })));
+                            }
+                            amount = Some(parsed_amount);
+                        },
+                        "label" => {
+                            let label_decoder = decode_param(value, options).map_err(Error::percent_decode_static("label"))?;
+                            label = Some(label_decoder);
+                        },
+                        "message" => {
+                            let message_decoder = decode_param(value, options).map_err(Error::percent_decode_static("message"))?;
+                            message = Some(message_decoder);
+                        },
+                        extra_key => {
+                            let decoder = decode_param(value, options).map_err(Error::percent_decode(key))?;
+                            if extra_key == ADDRESS_TYPE_HINT_KEY {
+                                let address_type = address.assume_checked_ref().address_type();
+                                if address_type_hint_matches(&decoder.decoded_bytes(), address_type) == Some(false) {
+                                    warn(Warning::AddressTypeHintMismatch { position: position(value) });
+                                }
+                            }
+                            let parsed_value = Some(&decoder).filter(|_| options.retain_original).cloned();
+                            let normalized_key = deserializer.normalize_key(extra_key);
+                            let is_known = deserializer.deserialize(normalized_key, decoder).map_err(Error::Extras)?;
+                            if let (ParamKind::Known, Some(value)) = (is_known, parsed_value) {
+                                parsed_extras.push((extra_key, value));
+                            }
+                            if is_known == ParamKind::Unknown {
+                                if extra_key.starts_with("req-") {
+                                    return Err(Error::Uri(UriError(UriErrorInner::UnknownRequiredParameter(extra_key.to_owned()))));
+                                }
+                                warn(Warning::UnknownParameter { position: position(key) });
+                            }
+                        },
+                    }
+                    had_encoded_params |= value.contains('%');
+                    Ok(())
+                };
+                match (deserialize_param(), fixups.as_mut()) {
+                    (Err(Error::Uri(error)), Some(fixups)) if !matches!(error.0, UriErrorInner::UnknownRequiredParameter(_)) => fixups.push(Fixup {
+                        position: position(param),
+                        error,
+                    }),
+                    (result, _) => result?,
                 }
             }
         }
         let extras = deserializer.finalize().map_err(Error::Extras)?;
+        let original = if options.retain_original && !scheme_assumed && !web_prefix_stripped {
+            Some(Original {
+                input,
+                options: options.clone(),
+                fields: ParsedFields {
+                    address: address.clone(),
+                    amount,
+                    label: label.clone(),
+                    message: message.clone(),
+                    extras: parsed_extras,
+                },
+            })
+        } else {
+            None
+        };
 
         Ok(Uri {
             address,
@@ -89,6 +852,8 @@ impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T
             label,
             message,
             extras,
+            original,
+            had_encoded_params,
         })
     }
 }
@@ -97,15 +862,256 @@ impl<NetVal: NetworkValidation, T> Uri<'_, NetVal, T> {
     /// Makes the lifetime `'static` by converting all fields to owned.
     ///
     /// Note that this does **not** affect `extras`!
-    fn into_static(self) -> Uri<'static, NetVal, T> {
+    pub(crate) fn into_static(self) -> Uri<'static, NetVal, T> {
         Uri {
             address: self.address,
             amount: self.amount,
             label: self.label.map(|label| label.decode_into_owned()),
             message: self.message.map(|message| message.decode_into_owned()),
             extras: self.extras,
+            original: None,
+            had_encoded_params: self.had_encoded_params,
+        }
+    }
+}
+
+/// The maximum number of digits before the decimal point of the amount in BTC.
+///
+/// The supply is below 100 000 000 BTC so longer amounts are always invalid.
+pub const MAX_AMOUNT_INTEGER_DIGITS: usize = 8;
+
+/// The maximum number of digits after the decimal point of the amount in BTC.
+pub const MAX_AMOUNT_DECIMALS: usize = 8;
+
+/// Values longer than this many bytes (as encoded in the URI) are reported by
+/// [`Warning::LongValue`].
+pub const LONG_VALUE_LENGTH: usize = 1024;
+
+/// Key of the non-standard parameter hinting at the type of the address, e.g. `addrtype=bech32`.
+///
+/// The parameter is treated as any other unknown parameter so [`UnknownParams`] preserves it.
+/// If its value is a recognized type that doesn't match the address
+/// [`Warning::AddressTypeHintMismatch`] is reported. Recognized values (case-insensitive) are
+/// `bc` (any segwit address), `bech32` (segwit v0), `bech32m` or `taproot` and the names of
+/// [`bitcoin::AddressType`] such as `p2wpkh`.
+///
+/// [`UnknownParams`]: crate::extras::UnknownParams
+pub const ADDRESS_TYPE_HINT_KEY: &str = "addrtype";
+
+/// Returns whether the hinted type matches the type of the address or `None` if the hint is
+/// not recognized.
+fn address_type_hint_matches(hint: &[u8], address_type: Option<bitcoin::AddressType>) -> Option<bool> {
+    use bitcoin::AddressType;
+
+    let hint = core::str::from_utf8(hint).ok()?.to_ascii_lowercase();
+    let matches = match &*hint {
+        "bc" => matches!(address_type, Some(AddressType::P2wpkh | AddressType::P2wsh | AddressType::P2tr)),
+        "bech32" => matches!(address_type, Some(AddressType::P2wpkh | AddressType::P2wsh)),
+        "bech32m" | "taproot" | "p2tr" => address_type == Some(AddressType::P2tr),
+        "p2pkh" => address_type == Some(AddressType::P2pkh),
+        "p2sh" => address_type == Some(AddressType::P2sh),
+        "p2wpkh" => address_type == Some(AddressType::P2wpkh),
+        "p2wsh" => address_type == Some(AddressType::P2wsh),
+        _ => return None,
+    };
+    Some(matches)
+}
+
+/// Parameters that can replace the address in URIs such as `bitcoin:?pj=...`.
+const ADDRESSLESS_PARAMS: [&str; 3] = ["pj", "req-pj", "lightning"];
+
+/// Non-fatal issue found by [`Uri::parse_with_warnings`].
+///
+/// Positions are byte offsets into the parsed string.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Warning {
+    /// The scheme is not in lowercase.
+    ///
+    /// The scheme is case-insensitive but the canonical form is lowercase. The position is
+    /// always 0.
+    NonLowercaseScheme,
+    /// A key or value contains percent-encoded `unreserved` character at `position`.
+    UnnecessaryEncoding {
+        /// The position of the `%` character.
+        position: usize,
+    },
+    /// An unknown optional parameter starting at `position` was ignored.
+    UnknownParameter {
+        /// The position of the key.
+        position: usize,
+    },
+    /// A value starting at `position` is longer than [`LONG_VALUE_LENGTH`].
+    LongValue {
+        /// The position of the value.
+        position: usize,
+        /// The length of the value in bytes.
+        length: usize,
+    },
+    /// The query ends with `&` at `position` which was ignored.
+    TrailingSeparator {
+        /// The position of the `&` character.
+        position: usize,
+    },
+    /// The value of [`ADDRESS_TYPE_HINT_KEY`] parameter starting at `position` doesn't match the
+    /// type of the address.
+    AddressTypeHintMismatch {
+        /// The position of the value.
+        position: usize,
+    },
+}
+
+impl Warning {
+    /// Returns the position in the input this warning relates to.
+    pub fn position(&self) -> usize {
+        match self {
+            Warning::NonLowercaseScheme => 0,
+            Warning::UnnecessaryEncoding { position } => *position,
+            Warning::UnknownParameter { position } => *position,
+            Warning::LongValue { position, .. } => *position,
+            Warning::TrailingSeparator { position } => *position,
+            Warning::AddressTypeHintMismatch { position } => *position,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::NonLowercaseScheme => write!(f, "the scheme is not in lowercase"),
+            Warning::UnnecessaryEncoding { position } => write!(f, "needless percent-encoding at {}", position),
+            Warning::UnknownParameter { position } => write!(f, "unknown parameter at {} was ignored", position),
+            Warning::LongValue { position, length } => write!(f, "the value at {} is {} bytes long", position, length),
+            Warning::TrailingSeparator { position } => write!(f, "trailing separator at {} was ignored", position),
+            Warning::AddressTypeHintMismatch { position } => write!(f, "the address type hint at {} doesn't match the address", position),
+        }
+    }
+}
+
+/// Parameter dropped by [`Uri::try_from_uri_str_lossy`].
+#[derive(Debug, Clone)]
+pub struct Fixup {
+    position: usize,
+    error: UriError,
+}
+
+impl Fixup {
+    /// Returns the byte position of the dropped parameter in the input.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the error that would be returned by strict parsing.
+    pub fn error(&self) -> &UriError {
+        &self.error
+    }
+}
+
+impl fmt::Display for Fixup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dropped parameter at {}: {}", self.position, self.error)
+    }
+}
+
+/// Parses URIs from newline-delimited `input`.
+///
+/// Each line is trimmed and blank lines are skipped. Every returned item carries its own result
+/// so one invalid line doesn't abort processing of the others.
+pub fn parse_lines<'a, T: DeserializeParams<'a>>(
+    input: &'a str,
+) -> impl Iterator<Item = Result<Uri<'a, bitcoin::address::NetworkUnchecked, T>, Error<T::Error>>> {
+    input.lines().map(str::trim).filter(|line| !line.is_empty()).map(Uri::try_from)
+}
+
+/// Parses the URI and returns its [canonical form](Uri::to_canonical_string).
+///
+/// Unknown parameters are preserved, so the result carries the same information as the input
+/// only formatted canonically. The network of the address is not checked - the caller has to
+/// do it when the output is used for paying.
+pub fn normalize(input: &str) -> Result<String, Error<core::convert::Infallible>> {
+    let uri = Uri::<'_, bitcoin::address::NetworkUnchecked, crate::extras::UnknownParams<'_>>::try_from(input)?;
+    Ok(uri.assume_checked().to_canonical_string())
+}
+
+/// Repairs common corruptions of URIs passed through mobile deep-linking.
+///
+/// **Non-standard**: some operating systems and apps mangle links before handing them over, so
+/// this undoes it before parsing:
+///
+/// * HTML-escaped separators `&amp;` are replaced with `&`.
+/// * Double percent-encoding is collapsed once - `%25` followed by two hex digits becomes `%`,
+///   e.g. `%2520` becomes `%20`.
+///
+/// Note that this changes the meaning of values that legitimately contain these sequences so it
+/// should only be applied to input known to come from deep links. Returns the input unchanged
+/// (borrowed) if there's nothing to repair.
+pub fn unmangle_deep_link(input: &str) -> Cow<'_, str> {
+    const DOUBLE_ENCODED_PERCENT: &str = "%25";
+
+    let is_double_encoded = |pos: usize| {
+        input.as_bytes()[pos..].starts_with(DOUBLE_ENCODED_PERCENT.as_bytes())
+            && matches!(input.as_bytes().get((pos + 3)..(pos + 5)), Some(hex) if hex.iter().all(u8::is_ascii_hexdigit))
+    };
+    if !input.contains("&amp;") && !input.match_indices(DOUBLE_ENCODED_PERCENT).any(|(pos, _)| is_double_encoded(pos)) {
+        return Cow::Borrowed(input);
+    }
+    let mut result = String::with_capacity(input.len());
+    let mut rest = 0;
+    while let Some(offset) = input[rest..].find(['&', '%']) {
+        let pos = rest + offset;
+        result.push_str(&input[rest..pos]);
+        if input[pos..].starts_with("&amp;") {
+            result.push('&');
+            rest = pos + "&amp;".len();
+        } else if is_double_encoded(pos) {
+            result.push('%');
+            rest = pos + DOUBLE_ENCODED_PERCENT.len();
+        } else {
+            result.push_str(&input[pos..(pos + 1)]);
+            rest = pos + 1;
         }
     }
+    result.push_str(&input[rest..]);
+    Cow::Owned(result)
+}
+
+/// Zero-width characters removed by [`strip_invisible`].
+///
+/// These are zero width space, zero width non-joiner, zero width joiner, word joiner and zero
+/// width no-break space (BOM).
+const ZERO_WIDTH_CHARS: [char; 5] = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Removes the byte order mark and zero-width characters corrupting copy-pasted URIs.
+///
+/// **Non-standard**: clipboard data sometimes carries a leading UTF-8 BOM or zero-width
+/// characters inserted by messaging apps and rich text editors. They are removed from the
+/// scheme, the address and the keys of parameters where they are never valid. Values are left
+/// alone since unencoded UTF-8 values are accepted and joiners are part of some text, e.g.
+/// emoji sequences such as `👨\u{200D}👩`. The fragment is left alone too. Parsing stays strict
+/// unless this is applied explicitly. Returns the input borrowed if there's nothing to remove
+/// other than a leading BOM.
+pub fn strip_invisible(input: &str) -> Cow<'_, str> {
+    let input = input.strip_prefix('\u{FEFF}').unwrap_or(input);
+    let keep = || {
+        let mut in_query = false;
+        let mut in_value = false;
+        let mut in_fragment = false;
+        move |c: char| {
+            match c {
+                '#' => in_fragment = true,
+                '?' => in_query = true,
+                '=' if in_query => in_value = true,
+                '&' => in_value = false,
+                _ => (),
+            }
+            in_value || in_fragment || !ZERO_WIDTH_CHARS.contains(&c)
+        }
+    };
+    if input.chars().all(keep()) {
+        return Cow::Borrowed(input);
+    }
+    let mut keep = keep();
+    Cow::Owned(input.chars().filter(|c| keep(*c)).collect())
 }
 
 /// Indicates whether a parameter with this name is known.
@@ -127,6 +1133,12 @@ pub enum ParamKind {
 /// Defines error type of deserialization.
 ///
 /// This is a separate trait to ensure the error is same for all lifetimes.
+///
+/// Errors returned by [`DeserializationState`] are passed to the caller unchanged as
+/// [`Error::Extras`] so the parser adds no context to them. To make debugging possible the
+/// errors should carry the key and the (decoded) value of the parameter that failed to
+/// deserialize. The extras in [`crate::extras`] follow this by providing `parameter()` and
+/// `value()` accessors.
 pub trait DeserializationError {
     /// The error returned when deserialization fails.
     type Error;
@@ -142,6 +1154,18 @@ pub trait DeserializationState<'de>: Default {
     /// Required parameters include the `req-` prefix.
     fn is_param_known(&self, key: &str) -> bool;
 
+    /// Maps the key of a parameter to its current name.
+    ///
+    /// This is called for each extra parameter before `deserialize_*` so that legacy keys can be
+    /// handled by the same code as their current names. Implementations may remember which form
+    /// was used to be able to re-serialize the same form. The check for unknown `req-` parameters
+    /// uses the key from the URI, not the returned one.
+    ///
+    /// The default implementation returns `key` unchanged.
+    fn normalize_key<'k>(&mut self, key: &'k str) -> &'k str {
+        key
+    }
+
     /// Deserializes a temporary.
     ///
     /// This can not borrow the key nor value, so has to clone them or throw away.
@@ -229,6 +1253,26 @@ impl<T: fmt::Display + std::error::Error + 'static> std::error::Error for Error<
     }
 }
 
+/// Converts the error to [`std::io::ErrorKind::InvalidData`] keeping it as the inner error.
+///
+/// This allows using `?` when parsing URIs in functions returning `std::io::Result`.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<T: std::error::Error + Send + Sync + 'static> From<Error<T>> for std::io::Error {
+    fn from(error: Error<T>) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Converts the error to [`std::io::ErrorKind::InvalidData`] keeping it as the inner error.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl From<UriError> for std::io::Error {
+    fn from(error: UriError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
 /// Error returned when parsing non-extras parts of URI.
 #[derive(Debug, Clone)]
 pub struct UriError(UriErrorInner);
@@ -237,15 +1281,165 @@ pub struct UriError(UriErrorInner);
 #[cfg_attr(not(feature = "std"), allow(dead_code))]
 enum UriErrorInner {
     TooShort,
+    TooLong {
+        length: usize,
+        max: usize,
+    },
+    TooManyParams {
+        count: usize,
+        limit: usize,
+    },
     InvalidScheme,
+    WhitespaceInScheme {
+        position: usize,
+    },
+    MissingAddress,
+    Addressless(String),
+    InvalidAddressCharacter {
+        byte: u8,
+        position: usize,
+    },
     Address(AddressError),
     Amount(ParseAmountError),
+    DecimalPointInSats {
+        position: usize,
+    },
+    AmountGrammar {
+        error: AmountGrammarError,
+        position: usize,
+    },
+    AmountAboveMaxMoney {
+        position: usize,
+    },
     UnknownRequiredParameter(String),
     PercentDecode {
         parameter: Cow<'static, str>,
         error: PercentDecodeError,
     },
     MissingEquals(String),
+    UnnecessaryEncoding {
+        parameter: String,
+        position: usize,
+    },
+    TruncatedPercentEscape {
+        parameter: String,
+        position: usize,
+    },
+    TrailingSeparator {
+        position: usize,
+    },
+}
+
+/// Violation of the grammar of the amount in BTC.
+#[derive(Debug, Clone)]
+enum AmountGrammarError {
+    Empty,
+    SignNotAllowed,
+    HexadecimalNotation,
+    ScientificNotation,
+    DecimalComma,
+    InvalidCharacter(char),
+    LeadingDecimalPoint,
+    TrailingDecimalPoint,
+    TooManyIntegerDigits,
+    TooManyDecimals,
+}
+
+impl fmt::Display for AmountGrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmountGrammarError::Empty => write!(f, "the amount is empty"),
+            AmountGrammarError::SignNotAllowed => write!(f, "the amount must not have a sign"),
+            AmountGrammarError::HexadecimalNotation => write!(f, "the amount must not be hexadecimal"),
+            AmountGrammarError::ScientificNotation => write!(f, "the amount must not use scientific notation"),
+            AmountGrammarError::DecimalComma => write!(f, "the amount uses decimal comma instead of decimal point"),
+            AmountGrammarError::InvalidCharacter(c) => write!(f, "the amount contains invalid character '{}'", c.escape_debug()),
+            AmountGrammarError::LeadingDecimalPoint => write!(f, "the amount is missing digits before the decimal point"),
+            AmountGrammarError::TrailingDecimalPoint => write!(f, "the amount is missing digits after the decimal point"),
+            AmountGrammarError::TooManyIntegerDigits => {
+                write!(
+                    f,
+                    "the amount has more than {} digits before the decimal point",
+                    MAX_AMOUNT_INTEGER_DIGITS
+                )
+            },
+            AmountGrammarError::TooManyDecimals => write!(f, "the amount has more than {} decimal places", MAX_AMOUNT_DECIMALS),
+        }
+    }
+}
+
+impl UriError {
+    pub(crate) fn unknown_required_parameter(key: &str) -> Self {
+        UriError(UriErrorInner::UnknownRequiredParameter(key.to_owned()))
+    }
+
+    /// Returns `true` if the URI intentionally has no address.
+    ///
+    /// URIs such as `bitcoin:?pj=...` omit the address because another parameter (`pj`, `req-pj`
+    /// or `lightning`) describes how to pay. These can not be represented by [`Uri`] but
+    /// applications may want to handle them differently from URIs that are missing the address
+    /// by mistake, such as `bitcoin:?amount=1`, for which this returns `false`.
+    pub fn is_addressless(&self) -> bool {
+        matches!(self.0, UriErrorInner::Addressless(_))
+    }
+
+    /// Returns `true` if a value ends with an incomplete percent-escape, such as `%` or `%A`.
+    ///
+    /// This typically means that the input got truncated, e.g. by a partial QR code scan.
+    /// [`position`](Self::position) returns the position of the `%` character.
+    pub fn is_truncated_percent_escape(&self) -> bool {
+        matches!(self.0, UriErrorInner::TruncatedPercentEscape { .. })
+    }
+
+    /// Returns `true` if the scheme is `bitcoin` with whitespace inside or around it.
+    ///
+    /// Inputs such as `bitcoin :address` or `bit coin:address` are usually damaged by copying
+    /// or OCR. They are rejected like any other invalid scheme, this allows telling the user
+    /// what is wrong. [`position`](Self::position) returns the position of the first whitespace.
+    pub fn is_whitespace_in_scheme(&self) -> bool {
+        matches!(self.0, UriErrorInner::WhitespaceInScheme { .. })
+    }
+
+    /// Returns `true` if the amount starts with `+` or `-`.
+    ///
+    /// BIP21 amounts are unsigned so such values are rejected instead of ignoring the sign.
+    pub fn is_amount_sign_not_allowed(&self) -> bool {
+        matches!(
+            self.0,
+            UriErrorInner::AmountGrammar {
+                error: AmountGrammarError::SignNotAllowed,
+                ..
+            }
+        )
+    }
+
+    /// Returns `true` if the amount exceeds [`bitcoin::Amount::MAX_MONEY`] (21 million BTC).
+    ///
+    /// Such amounts fit the grammar, e.g. `amount=99999999`, but no payment can ever have them.
+    pub fn is_amount_above_max_money(&self) -> bool {
+        matches!(self.0, UriErrorInner::AmountAboveMaxMoney { .. })
+    }
+
+    /// Returns `true` if the query has more parameters than allowed by
+    /// [`ParseOptions::max_params`].
+    pub fn is_too_many_params(&self) -> bool {
+        matches!(self.0, UriErrorInner::TooManyParams { .. })
+    }
+
+    /// Returns the byte position within the input at which the error was detected, if known.
+    pub fn position(&self) -> Option<usize> {
+        match &self.0 {
+            UriErrorInner::UnnecessaryEncoding { position, .. } => Some(*position),
+            UriErrorInner::TruncatedPercentEscape { position, .. } => Some(*position),
+            UriErrorInner::TrailingSeparator { position } => Some(*position),
+            UriErrorInner::WhitespaceInScheme { position } => Some(*position),
+            UriErrorInner::InvalidAddressCharacter { position, .. } => Some(*position),
+            UriErrorInner::DecimalPointInSats { position } => Some(*position),
+            UriErrorInner::AmountGrammar { position, .. } => Some(*position),
+            UriErrorInner::AmountAboveMaxMoney { position } => Some(*position),
+            _ => None,
+        }
+    }
 }
 
 impl From<AddressError> for UriErrorInner {
@@ -264,15 +1458,39 @@ impl fmt::Display for UriError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.0 {
             UriErrorInner::TooShort => write!(f, "the URI is too short"),
+            UriErrorInner::TooLong { length, max } => write!(f, "the URI is {} bytes long, the maximum is {}", length, max),
+            UriErrorInner::TooManyParams { count, limit } => write!(f, "the URI has {} parameters, the maximum is {}", count, limit),
             UriErrorInner::InvalidScheme => write!(f, "the URI has invalid scheme"),
+            UriErrorInner::WhitespaceInScheme { position } => write!(f, "the scheme contains whitespace at {}", position),
+            UriErrorInner::MissingAddress => write!(f, "the URI is missing address"),
+            UriErrorInner::Addressless(parameter) => {
+                write!(
+                    f,
+                    "the URI has no address, addressless URIs with parameter '{}' are not supported",
+                    parameter
+                )
+            },
+            UriErrorInner::InvalidAddressCharacter { byte, position } => {
+                write!(f, "the address contains invalid character 0x{:02x} at {}", byte, position)
+            },
             UriErrorInner::Address(_) => write!(f, "the address is invalid"),
             UriErrorInner::Amount(_) => write!(f, "the amount is invalid"),
+            UriErrorInner::DecimalPointInSats { position } => write!(f, "the amount in satoshis contains decimal point at {}", position),
+            UriErrorInner::AmountGrammar { error, position } => write!(f, "{} at {}", error, position),
+            UriErrorInner::AmountAboveMaxMoney { position } => write!(f, "the amount exceeds 21 million BTC at {}", position),
             UriErrorInner::UnknownRequiredParameter(parameter) => write!(f, "the URI contains unknown required parameter '{}'", parameter),
             #[cfg(feature = "std")]
             UriErrorInner::PercentDecode { parameter, error: _ } => write!(f, "can not percent-decode parameter {}", parameter),
             #[cfg(not(feature = "std"))]
             UriErrorInner::PercentDecode { parameter, error } => write!(f, "can not percent-decode parameter {}: {}", parameter, error),
             UriErrorInner::MissingEquals(parameter) => write!(f, "the parameter '{}' is missing a value", parameter),
+            UriErrorInner::UnnecessaryEncoding { parameter, position } => {
+                write!(f, "the parameter '{}' has needless percent-encoding at {}", parameter, position)
+            },
+            UriErrorInner::TruncatedPercentEscape { parameter, position } => {
+                write!(f, "the parameter '{}' ends with truncated percent-escape at {}", parameter, position)
+            },
+            UriErrorInner::TrailingSeparator { position } => write!(f, "the query has trailing separator at {}", position),
         }
     }
 }
@@ -283,12 +1501,24 @@ impl std::error::Error for UriError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self.0 {
             UriErrorInner::TooShort => None,
+            UriErrorInner::TooLong { .. } => None,
+            UriErrorInner::TooManyParams { .. } => None,
             UriErrorInner::InvalidScheme => None,
+            UriErrorInner::WhitespaceInScheme { .. } => None,
+            UriErrorInner::MissingAddress => None,
+            UriErrorInner::Addressless(_) => None,
+            UriErrorInner::InvalidAddressCharacter { .. } => None,
             UriErrorInner::Address(error) => Some(error),
             UriErrorInner::Amount(error) => Some(error),
+            UriErrorInner::DecimalPointInSats { .. } => None,
+            UriErrorInner::AmountGrammar { .. } => None,
+            UriErrorInner::AmountAboveMaxMoney { .. } => None,
             UriErrorInner::UnknownRequiredParameter(_) => None,
             UriErrorInner::PercentDecode { parameter: _, error } => Some(error),
             UriErrorInner::MissingEquals(_) => None,
+            UriErrorInner::UnnecessaryEncoding { .. } => None,
+            UriErrorInner::TruncatedPercentEscape { .. } => None,
+            UriErrorInner::TrailingSeparator { .. } => None,
         }
     }
 }
@@ -298,7 +1528,7 @@ impl<T: for<'de> DeserializeParams<'de>> core::str::FromStr for Uri<'_, bitcoin:
     type Err = Error<T::Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Uri::deserialize_raw(s).map(Uri::into_static)
+        Uri::deserialize_raw(s, &Default::default(), None, None).map(Uri::into_static)
     }
 }
 
@@ -306,7 +1536,7 @@ impl<'a, T: DeserializeParams<'a>> TryFrom<&'a str> for Uri<'a, bitcoin::address
     type Error = Error<T::Error>;
 
     fn try_from(s: &'a str) -> Result<Self, Self::Error> {
-        Self::deserialize_raw(s)
+        Self::deserialize_raw(s, &Default::default(), None, None)
     }
 }
 
@@ -331,6 +1561,107 @@ impl<'a, T: for<'de> DeserializeParams<'de>> TryFrom<Cow<'a, str>> for Uri<'a, b
     }
 }
 
+#[cfg(feature = "base64")]
+#[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
+impl<T: for<'de> DeserializeParams<'de>> Uri<'static, bitcoin::address::NetworkUnchecked, T> {
+    /// Decodes the URI encoded using [`Uri::to_base64url`].
+    ///
+    /// The input must be unpadded base64url encoding of the URI.
+    pub fn from_base64url(input: &str) -> Result<Self, Base64UrlError<T::Error>> {
+        use base64::Engine;
+
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(input)
+            .map_err(Base64UrlError::Base64)?;
+        let string = String::from_utf8(bytes).map_err(|error| Base64UrlError::Utf8(error.utf8_error()))?;
+        string.parse().map_err(Base64UrlError::Uri)
+    }
+}
+
+/// Error returned by [`Uri::from_base64url`].
+#[cfg(feature = "base64")]
+#[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
+#[derive(Debug, Clone)]
+pub enum Base64UrlError<T> {
+    /// The input is not valid unpadded base64url.
+    Base64(base64::DecodeError),
+    /// The decoded bytes are not valid UTF-8.
+    Utf8(core::str::Utf8Error),
+    /// The decoded string is not a valid URI.
+    Uri(Error<T>),
+}
+
+#[cfg(feature = "base64")]
+impl<T> fmt::Display for Base64UrlError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Base64UrlError::Base64(_) => write!(f, "the input is not valid base64url"),
+            Base64UrlError::Utf8(_) => write!(f, "the decoded URI is not valid UTF-8"),
+            Base64UrlError::Uri(_) => write!(f, "the decoded URI is invalid"),
+        }
+    }
+}
+
+#[cfg(all(feature = "base64", feature = "std"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "base64", feature = "std"))))]
+impl<T: fmt::Display + std::error::Error + 'static> std::error::Error for Base64UrlError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Base64UrlError::Base64(error) => Some(error),
+            Base64UrlError::Utf8(error) => Some(error),
+            Base64UrlError::Uri(error) => Some(error),
+        }
+    }
+}
+
+impl<'a> Uri<'a, bitcoin::address::NetworkChecked, NoExtras> {
+    /// Parses the URI and checks that its address is valid for `network`.
+    ///
+    /// This combines `TryFrom<&str>` and [`require_network`](Uri::require_network) into a single
+    /// call, which is the typical entry point of wallets knowing their network up front. Unlike
+    /// `require_network` the network mismatch is reported separately from parsing errors.
+    pub fn parse_for_network(input: &'a str, network: bitcoin::Network) -> Result<Self, ParseForNetworkError> {
+        let uri = Uri::<'a, bitcoin::address::NetworkUnchecked, NoExtras>::try_from(input).map_err(|error| match error {
+            Error::Uri(error) => ParseForNetworkError::Parse(error),
+            Error::Extras(never) => match never {},
+        })?;
+        if !uri.address.is_valid_for_network(network) {
+            return Err(ParseForNetworkError::WrongNetwork(crate::ser::NetworkMismatchError::new(network)));
+        }
+        Ok(uri.assume_checked())
+    }
+}
+
+/// Error returned by [`Uri::parse_for_network`].
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum ParseForNetworkError {
+    /// The URI is invalid.
+    Parse(UriError),
+    /// The URI is valid but its address belongs to another network.
+    WrongNetwork(crate::ser::NetworkMismatchError),
+}
+
+impl fmt::Display for ParseForNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseForNetworkError::Parse(_) => write!(f, "invalid BIP21 URI"),
+            ParseForNetworkError::WrongNetwork(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for ParseForNetworkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseForNetworkError::Parse(error) => Some(error),
+            ParseForNetworkError::WrongNetwork(_) => None,
+        }
+    }
+}
+
 impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T> {
     /// Checks whether network of this address is as required.
     ///
@@ -343,6 +1674,8 @@ impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T
             label: self.label,
             message: self.message,
             extras: self.extras,
+            original: self.original,
+            had_encoded_params: self.had_encoded_params,
         })
     }
 
@@ -354,6 +1687,8 @@ impl<'a, T: DeserializeParams<'a>> Uri<'a, bitcoin::address::NetworkUnchecked, T
             label: self.label,
             message: self.message,
             extras: self.extras,
+            original: self.original,
+            had_encoded_params: self.had_encoded_params,
         }
     }
 }
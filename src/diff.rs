@@ -0,0 +1,258 @@
+//! Structured differences between URIs.
+//!
+//! [`Uri::diff`] lists what changed between two URIs, e.g. between a payment request and the
+//! URI received from a payjoin counterparty, so that the changes can be logged or rejected.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bitcoin::address::NetworkChecked;
+use core::fmt;
+use crate::ser::SerializeParams;
+use crate::Uri;
+
+/// A single difference between two URIs.
+///
+/// Values are decoded, so differently percent-encoded values are not reported as changed.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Change {
+    /// The address is different.
+    Address {
+        /// The address of the first URI.
+        old: bitcoin::Address,
+        /// The address of the second URI.
+        new: bitcoin::Address,
+    },
+    /// The amount was added, removed or changed.
+    Amount {
+        /// The amount of the first URI.
+        old: Option<bitcoin::Amount>,
+        /// The amount of the second URI.
+        new: Option<bitcoin::Amount>,
+    },
+    /// A parameter is only present in the second URI.
+    ParamAdded {
+        /// The key of the parameter.
+        key: String,
+        /// The decoded value.
+        value: Vec<u8>,
+    },
+    /// A parameter is only present in the first URI.
+    ParamRemoved {
+        /// The key of the parameter.
+        key: String,
+        /// The decoded value.
+        value: Vec<u8>,
+    },
+    /// A parameter has different values.
+    ParamChanged {
+        /// The key of the parameter.
+        key: String,
+        /// The decoded value in the first URI.
+        old: Vec<u8>,
+        /// The decoded value in the second URI.
+        new: Vec<u8>,
+    },
+}
+
+/// Displays the change for logs, values that are not valid UTF-8 are displayed lossily.
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lossy = String::from_utf8_lossy;
+        match self {
+            Change::Address { old, new } => write!(f, "address changed from {} to {}", old, new),
+            Change::Amount { old: None, new: Some(new) } => write!(f, "amount {} added", new),
+            Change::Amount { old: Some(old), new: None } => write!(f, "amount {} removed", old),
+            Change::Amount {
+                old: Some(old),
+                new: Some(new),
+            } => write!(f, "amount changed from {} to {}", old, new),
+            Change::Amount { old: None, new: None } => write!(f, "amount unchanged"),
+            Change::ParamAdded { key, value } => write!(f, "parameter {}={} added", key, lossy(value)),
+            Change::ParamRemoved { key, value } => write!(f, "parameter {}={} removed", key, lossy(value)),
+            Change::ParamChanged { key, old, new } => write!(f, "parameter {} changed from {} to {}", key, lossy(old), lossy(new)),
+        }
+    }
+}
+
+/// Differences between two URIs returned by [`Uri::diff`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UriDiff {
+    changes: Vec<Change>,
+}
+
+impl UriDiff {
+    /// Returns the changes in order: address, amount and then parameters.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns `true` if the URIs are semantically equal.
+    ///
+    /// For URIs with the same extras this agrees with `==`.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl IntoIterator for UriDiff {
+    type Item = Change;
+    type IntoIter = alloc::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<T> Uri<'_, NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Returns the differences between `self` and `other`.
+    ///
+    /// The parameters (including `label` and `message`) are compared by their decoded values in
+    /// the order of appearance of their keys: first those present in `self`, then those only
+    /// present in `other`. Values of repeated keys are sorted before being matched, so a repeated
+    /// parameter being added shows up as [`Change::ParamAdded`]. Like with `==`, the order of the
+    /// parameters doesn't matter. The URIs may have different extras, e.g. to compare a URI parsed with
+    /// [`UnknownParams`](crate::extras::UnknownParams) against a typed one.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn diff<U>(&self, other: &Uri<'_, NetworkChecked, U>) -> UriDiff
+    where
+        for<'a> &'a U: SerializeParams,
+    {
+        let mut changes = Vec::new();
+        if self.address != other.address {
+            changes.push(Change::Address {
+                old: self.address.clone(),
+                new: other.address.clone(),
+            });
+        }
+        if self.amount != other.amount {
+            changes.push(Change::Amount {
+                old: self.amount,
+                new: other.amount,
+            });
+        }
+        let params = self.decoded_params();
+        let other_params = other.decoded_params();
+        let values = |params: &[(String, alloc::borrow::Cow<'_, [u8]>)], key: &str| {
+            let mut values = params
+                .iter()
+                .filter(|(param_key, _)| param_key == key)
+                .map(|(_, value)| value.to_vec())
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values
+        };
+        let mut keys = Vec::<&str>::new();
+        for (key, _) in params.iter().chain(&other_params) {
+            if key != "amount" && !keys.contains(&key.as_str()) {
+                keys.push(key);
+            }
+        }
+        for key in keys {
+            let old = values(&params, key);
+            let new = values(&other_params, key);
+            for i in 0..old.len().max(new.len()) {
+                let change = match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) if old == new => continue,
+                    (Some(old), Some(new)) => Change::ParamChanged {
+                        key: key.to_string(),
+                        old: old.clone(),
+                        new: new.clone(),
+                    },
+                    (Some(old), None) => Change::ParamRemoved {
+                        key: key.to_string(),
+                        value: old.clone(),
+                    },
+                    (None, Some(new)) => Change::ParamAdded {
+                        key: key.to_string(),
+                        value: new.clone(),
+                    },
+                    (None, None) => unreachable!("the index is below the maximum length"),
+                };
+                changes.push(change);
+            }
+        }
+        UriDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Change;
+    use crate::extras::UnknownParams;
+    use crate::Uri;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use core::convert::TryFrom;
+
+    fn parse(input: &'static str) -> Uri<'static, bitcoin::address::NetworkChecked, UnknownParams<'static>> {
+        Uri::try_from(input).unwrap().assume_checked()
+    }
+
+    #[test]
+    fn diff() {
+        let requested = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Shop&pj=https://example.com/pj&pjos=0");
+        let received = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2&pj=https://evil.example/pj&pjos=0&message=Thanks");
+        let diff = requested.diff(&received);
+        assert_eq!(
+            diff.changes(),
+            [
+                Change::Amount {
+                    old: Some(bitcoin::Amount::ONE_BTC),
+                    new: Some(bitcoin::Amount::from_sat(200_000_000)),
+                },
+                Change::ParamRemoved {
+                    key: "label".to_string(),
+                    value: b"Shop".to_vec(),
+                },
+                Change::ParamChanged {
+                    key: "pj".to_string(),
+                    old: b"https://example.com/pj".to_vec(),
+                    new: b"https://evil.example/pj".to_vec(),
+                },
+                Change::ParamAdded {
+                    key: "message".to_string(),
+                    value: b"Thanks".to_vec(),
+                },
+            ]
+        );
+        let messages = diff.into_iter().map(|change| change.to_string()).collect::<Vec<_>>();
+        assert_eq!(messages[0], "amount changed from 1 BTC to 2 BTC");
+        assert_eq!(messages[1], "parameter label=Shop removed");
+    }
+
+    #[test]
+    fn diff_ignores_formatting() {
+        let a = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1.0&label=a%2Db&foo=1&bar=2");
+        let b = parse("BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?bar=2&label=a-b&amount=1&foo=1");
+        assert!(a.diff(&b).is_empty());
+        assert_eq!(a, b);
+
+        let repeated = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1&foo=2");
+        let reordered = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=2&foo=1");
+        assert!(repeated.diff(&reordered).is_empty());
+        assert_eq!(repeated, reordered);
+
+        let c = parse("bitcoin:1BoatSLRHtKNngkdXEeobR76b53LETtpyT?foo=1&foo=2");
+        let changes = a.diff(&c).changes().to_vec();
+        assert!(matches!(changes[0], Change::Address { .. }));
+        assert_eq!(
+            changes[changes.len() - 2],
+            Change::ParamAdded {
+                key: "foo".to_string(),
+                value: b"2".to_vec(),
+            }
+        );
+        assert_eq!(
+            changes[changes.len() - 1],
+            Change::ParamRemoved {
+                key: "bar".to_string(),
+                value: b"2".to_vec(),
+            }
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Ready-made extras implementing common BIP21 extensions.
+//!
+//! The types in this module can be used as the `Extras` type parameter of [`Uri`](crate::Uri).
+//! They only recognize, validate and preserve their parameters. Fetching the endpoints is left to
+//! the application - the crate never performs any networking.
+//!
+//! Errors of all these types carry the key and the value of the parameter that failed to parse.
+
+pub mod asset;
+pub mod bip72;
+pub mod callback;
+pub mod combined;
+pub mod expiry;
+pub mod fee;
+pub mod lazy;
+pub mod lightning;
+pub mod payjoin;
+pub mod unknown;
+
+pub use asset::AssetExtras;
+pub use bip72::Bip72Extras;
+pub use callback::CallbackExtras;
+pub use combined::CombinedExtras;
+pub use expiry::ExpiryExtras;
+pub use fee::FeeExtras;
+pub use lazy::LazyExtras;
+pub use lightning::LightningExtras;
+pub use payjoin::PayjoinExtras;
+pub use unknown::UnknownParams;
+
+use alloc::string::String;
+use core::fmt;
+use crate::Param;
+
+/// Scheme, host and port of an absolute URL such as `https://user@example.com:8443/path`.
+///
+/// The hand-written validation of URL parameters is built on this so that it's the same
+/// whether the `url` feature is enabled or not.
+struct UrlParts<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<&'a str>,
+}
+
+impl<'a> UrlParts<'a> {
+    /// Splits the URL, returns `None` if there's no `://` after the scheme.
+    fn split(url: &'a str) -> Option<Self> {
+        let (scheme, rest) = url.split_once("://")?;
+        let authority = rest.find(['/', '?', '#']).map_or(rest, |end| &rest[..end]);
+        let host = authority.rfind('@').map_or(authority, |pos| &authority[(pos + 1)..]);
+        // IPv6 literals contain colons so the port is only after the closing bracket
+        let port_start = if host.starts_with('[') {
+            host.find(']').and_then(|end| host[end..].find(':').map(|pos| end + pos))
+        } else {
+            host.rfind(':')
+        };
+        let (host, port) = match port_start {
+            Some(pos) => (&host[..pos], Some(&host[(pos + 1)..])),
+            None => (host, None),
+        };
+        Some(UrlParts { scheme, host, port })
+    }
+
+    /// Returns `true` if the port is missing, empty or a decimal number up to 65535.
+    fn has_valid_port(&self) -> bool {
+        match self.port {
+            Some(port) => port.bytes().all(|byte| byte.is_ascii_digit()) && (port.is_empty() || port.parse::<u16>().is_ok()),
+            None => true,
+        }
+    }
+}
+
+/// Decodes the value as UTF-8 returning the error together with lossily decoded value.
+fn decode_utf8(value: Param<'_>) -> Result<String, (core::str::Utf8Error, String)> {
+    String::from_utf8(value.decoded_bytes().into_owned())
+        .map_err(|error| (error.utf8_error(), String::from_utf8_lossy(error.as_bytes()).into_owned()))
+}
+
+/// Parses the decoded value of a boolean parameter.
+///
+/// Only `1` (`true`) and `0` (`false`) are accepted - the form used by BIP78 `pjos` and many other
+/// flags. Anything else, including empty value, is an error so that typos in security-relevant
+/// flags don't silently fall back to a default. Use [`parse_bool_param_lenient`] for producers
+/// writing `true`/`false`.
+pub fn parse_bool_param(value: &[u8]) -> Result<bool, InvalidBoolError> {
+    match value {
+        b"1" => Ok(true),
+        b"0" => Ok(false),
+        _ => Err(InvalidBoolError { lenient: false }),
+    }
+}
+
+/// Parses the decoded value of a boolean parameter accepting also `true` and `false`.
+///
+/// **Non-standard**: in addition to the forms accepted by [`parse_bool_param`] this accepts
+/// `true` and `false` in any letter case.
+pub fn parse_bool_param_lenient(value: &[u8]) -> Result<bool, InvalidBoolError> {
+    if value.eq_ignore_ascii_case(b"true") {
+        Ok(true)
+    } else if value.eq_ignore_ascii_case(b"false") {
+        Ok(false)
+    } else {
+        parse_bool_param(value).map_err(|_| InvalidBoolError { lenient: true })
+    }
+}
+
+/// Error returned when the value of a boolean parameter is invalid.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidBoolError {
+    lenient: bool,
+}
+
+impl fmt::Display for InvalidBoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.lenient {
+            write!(f, "the value is not a boolean, expected 0, 1, true or false")
+        } else {
+            write!(f, "the value is not a boolean, expected 0 or 1")
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for InvalidBoolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_bool_param, parse_bool_param_lenient};
+    use alloc::string::ToString;
+
+    #[test]
+    fn bool_params() {
+        assert_eq!(parse_bool_param(b"1"), Ok(true));
+        assert_eq!(parse_bool_param(b"0"), Ok(false));
+        for invalid in [&b""[..], b"01", b"2", b" 1", b"true", b"yes"] {
+            assert!(parse_bool_param(invalid).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(
+            parse_bool_param(b"x").unwrap_err().to_string(),
+            "the value is not a boolean, expected 0 or 1"
+        );
+
+        assert_eq!(parse_bool_param_lenient(b"1"), Ok(true));
+        assert_eq!(parse_bool_param_lenient(b"TRUE"), Ok(true));
+        assert_eq!(parse_bool_param_lenient(b"False"), Ok(false));
+        for invalid in [&b""[..], b"t", b"truee", b"on"] {
+            assert!(parse_bool_param_lenient(invalid).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(
+            parse_bool_param_lenient(b"x").unwrap_err().to_string(),
+            "the value is not a boolean, expected 0, 1, true or false"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Asset identifier of colored-coin style payment requests.
+//!
+//! Some ecosystems issuing assets on top of bitcoin attach the identifier of the requested asset
+//! in the `assetid` parameter. It's not standardized by a BIP so the identifier is treated as an
+//! opaque string and preserved exactly.
+
+use alloc::string::String;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// Asset parameter of the URI.
+///
+/// URIs with other parameters can still be preserved losslessly by
+/// [`UnknownParams`](super::UnknownParams), this type only recognizes the asset.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct AssetExtras {
+    /// The identifier of the requested asset (`assetid` parameter).
+    ///
+    /// This is the decoded value, it gets percent-encoded when displaying the URI.
+    pub asset_id: Option<String>,
+}
+
+impl AssetExtras {
+    /// Creates asset extras with given identifier.
+    pub fn new(asset_id: String) -> Self {
+        AssetExtras { asset_id: Some(asset_id) }
+    }
+
+    /// Returns the identifier of the requested asset, if any.
+    pub fn asset_id(&self) -> Option<&str> {
+        self.asset_id.as_deref()
+    }
+}
+
+impl crate::IntoOwnedExtras for AssetExtras {
+    type Owned = AssetExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for AssetExtras {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(AssetExtras {
+            asset_id: crate::merge_option("assetid", self.asset_id, &other.asset_id, PartialEq::eq)?,
+        })
+    }
+}
+
+impl DeserializeParams<'_> for AssetExtras {
+    type DeserializationState = AssetState;
+}
+
+impl DeserializationError for AssetExtras {
+    type Error = AssetError;
+}
+
+/// Deserialization state of [`AssetExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct AssetState {
+    asset_id: Option<String>,
+}
+
+impl DeserializationState<'_> for AssetState {
+    type Value = AssetExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        key == "assetid"
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, AssetError> {
+        if key != "assetid" {
+            return Ok(ParamKind::Unknown);
+        }
+        let asset_id = super::decode_utf8(value).map_err(|(error, value)| AssetError {
+            value,
+            kind: AssetErrorKind::Utf8(error),
+        })?;
+        // silently picking one of the assets could make the wallet pay with a wrong one
+        if self.asset_id.is_some() {
+            return Err(AssetError {
+                value: asset_id,
+                kind: AssetErrorKind::Duplicate,
+            });
+        }
+        self.asset_id = Some(asset_id);
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<AssetExtras, AssetError> {
+        Ok(AssetExtras { asset_id: self.asset_id })
+    }
+}
+
+impl<'a> SerializeParams for &'a AssetExtras {
+    type Key = &'static str;
+    type Value = &'a str;
+    type Iterator = core::option::IntoIter<(&'static str, &'a str)>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        self.asset_id.as_deref().map(|asset_id| ("assetid", asset_id)).into_iter()
+    }
+}
+
+/// Error returned when the `assetid` parameter is invalid.
+#[derive(Debug, Clone)]
+pub struct AssetError {
+    value: String,
+    kind: AssetErrorKind,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+enum AssetErrorKind {
+    Utf8(core::str::Utf8Error),
+    Duplicate,
+}
+
+impl AssetError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        "assetid"
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`. For repeated parameters this is the
+    /// value of the second occurrence.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns `true` if the URI contains more than one `assetid` parameter.
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self.kind, AssetErrorKind::Duplicate)
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            AssetErrorKind::Utf8(_) => write!(f, "the assetid parameter is not valid UTF-8"),
+            AssetErrorKind::Duplicate => write!(f, "the URI contains more than one assetid parameter"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            AssetErrorKind::Utf8(error) => Some(error),
+            AssetErrorKind::Duplicate => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssetExtras;
+    use crate::extras::UnknownParams;
+    use crate::Uri;
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn asset_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&assetid=La8Ks%2FXyZ%3D%3D";
+        let uri = input.parse::<Uri<'_, _, AssetExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.asset_id(), Some("La8Ks/XyZ=="));
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&assetid=La8Ks/XyZ%3D%3D"
+        );
+        let reparsed = uri.to_string().parse::<Uri<'_, _, AssetExtras>>().unwrap();
+        assert_eq!(reparsed.extras, uri.extras);
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _, AssetExtras>>().unwrap();
+        assert_eq!(uri.extras.asset_id(), None);
+    }
+
+    #[test]
+    fn preserved_by_unknown_params() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?assetid=La8Ks%2FXyZ%3D%3D&assetamount=5";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.extras.get("assetid").unwrap().decoded_bytes(), &b"La8Ks/XyZ=="[..]);
+        let reparsed = uri.to_string().parse::<Uri<'_, _, AssetExtras>>().unwrap();
+        assert_eq!(reparsed.extras.asset_id(), Some("La8Ks/XyZ=="));
+    }
+
+    #[test]
+    fn invalid_asset() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?assetid=a&assetid=b";
+        let error = match input.parse::<Uri<'_, _, AssetExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => error,
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        };
+        assert!(error.is_duplicate());
+        assert_eq!(error.value(), "b");
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?assetid=%FF";
+        let error = match input.parse::<Uri<'_, _, AssetExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => error,
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        };
+        assert!(!error.is_duplicate());
+        assert_eq!(error.parameter(), "assetid");
+        assert_eq!(error.value(), "\u{FFFD}");
+    }
+}
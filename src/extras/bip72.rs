@@ -0,0 +1,220 @@
+//! Payment request parameter as defined in [BIP72](https://github.com/bitcoin/bips/blob/master/bip-0072.mediawiki).
+//!
+//! Note that BIP70 payment requests are deprecated, this exists to not break legacy flows.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// BIP72 parameters of the URI.
+///
+/// Recognizes the `r` parameter containing the URL of the payment request and the MIME type
+/// hints some legacy wallets put next to it (see [`MIME_HINT_KEYS`]).
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct Bip72Extras {
+    /// The URL of the payment request (`r` parameter).
+    ///
+    /// This is the decoded value, it gets percent-encoded when displaying the URI.
+    pub payment_request: Option<String>,
+
+    /// MIME type hints (e.g. `type=application/bitcoin-paymentrequest`) in order of appearance.
+    ///
+    /// The pairs contain the key and the decoded value. They are preserved as-is so that
+    /// displaying the URI doesn't break legacy flows.
+    pub mime_hints: Vec<(String, String)>,
+}
+
+/// Keys of parameters recognized as MIME type hints of the payment request.
+pub const MIME_HINT_KEYS: [&str; 2] = ["mime", "type"];
+
+impl Bip72Extras {
+    /// Returns the first MIME type hint, if any.
+    pub fn mime_type(&self) -> Option<&str> {
+        self.mime_hints.first().map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the payment request parsed as [`url::Url`] or `None` if there's no `r` parameter.
+    ///
+    /// The returned value can be directly handed to an HTTP client.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn payment_request_url(&self) -> Option<Result<url::Url, url::ParseError>> {
+        self.payment_request.as_deref().map(url::Url::parse)
+    }
+}
+
+impl crate::IntoOwnedExtras for Bip72Extras {
+    type Owned = Bip72Extras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for Bip72Extras {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(Bip72Extras {
+            payment_request: crate::merge_option("r", self.payment_request, &other.payment_request, PartialEq::eq)?,
+            mime_hints: match (self.mime_hints.is_empty(), other.mime_hints.is_empty()) {
+                (false, false) if self.mime_hints != other.mime_hints => return Err(crate::MergeConflict::new(&*other.mime_hints[0].0)),
+                (true, _) => other.mime_hints.clone(),
+                (false, _) => self.mime_hints,
+            },
+        })
+    }
+}
+
+impl DeserializeParams<'_> for Bip72Extras {
+    type DeserializationState = Bip72State;
+}
+
+impl DeserializationError for Bip72Extras {
+    type Error = Bip72Error;
+}
+
+/// Deserialization state of [`Bip72Extras`].
+#[derive(Debug, Default, Clone)]
+pub struct Bip72State {
+    payment_request: Option<String>,
+    mime_hints: Vec<(String, String)>,
+}
+
+impl DeserializationState<'_> for Bip72State {
+    type Value = Bip72Extras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        key == "r" || MIME_HINT_KEYS.contains(&key)
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, Bip72Error> {
+        match key {
+            "r" => {
+                self.payment_request = Some(super::decode_utf8(value).map_err(Bip72Error::utf8("r"))?);
+                Ok(ParamKind::Known)
+            },
+            _ => match MIME_HINT_KEYS.iter().find(|hint_key| **hint_key == key) {
+                Some(parameter) => {
+                    let value = super::decode_utf8(value).map_err(Bip72Error::utf8(parameter))?;
+                    self.mime_hints.push((key.into(), value));
+                    Ok(ParamKind::Known)
+                },
+                None => Ok(ParamKind::Unknown),
+            },
+        }
+    }
+
+    fn finalize(self) -> Result<Bip72Extras, Bip72Error> {
+        Ok(Bip72Extras {
+            payment_request: self.payment_request,
+            mime_hints: self.mime_hints,
+        })
+    }
+}
+
+type MimeHintsIter<'a> = core::iter::Map<core::slice::Iter<'a, (String, String)>, fn(&'a (String, String)) -> (&'a str, &'a str)>;
+
+impl<'a> SerializeParams for &'a Bip72Extras {
+    type Key = &'a str;
+    type Value = &'a str;
+    type Iterator = core::iter::Chain<core::option::IntoIter<(&'a str, &'a str)>, MimeHintsIter<'a>>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        let mime_hints: MimeHintsIter<'a> = self.mime_hints.iter().map(|(key, value)| (key, value));
+        self.payment_request
+            .as_deref()
+            .map(|payment_request| ("r", payment_request))
+            .into_iter()
+            .chain(mime_hints)
+    }
+}
+
+/// Error returned when BIP72 parameters are invalid.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub struct Bip72Error {
+    parameter: &'static str,
+    value: String,
+    error: core::str::Utf8Error,
+}
+
+impl Bip72Error {
+    fn utf8(parameter: &'static str) -> impl FnOnce((core::str::Utf8Error, String)) -> Self {
+        move |(error, value)| Bip72Error { parameter, value, error }
+    }
+
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        self.parameter
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for Bip72Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the parameter '{}' is not valid UTF-8", self.parameter)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for Bip72Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bip72Extras;
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    #[test]
+    fn r_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&r=https://merchant.com/pay.php?h%3D2a8628fc2fbe";
+        let uri = input.parse::<Uri<'_, _, Bip72Extras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.payment_request.as_deref(), Some("https://merchant.com/pay.php?h=2a8628fc2fbe"));
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn mime_hints_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?r=https://merchant.com/pay&type=application/bitcoin-paymentrequest&mime=x%26y";
+        let uri = input.parse::<Uri<'_, _, Bip72Extras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.mime_type(), Some("application/bitcoin-paymentrequest"));
+        assert_eq!(uri.extras.mime_hints[1], ("mime".into(), "x&y".into()));
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?type=a%FF";
+        match input.parse::<Uri<'_, _, Bip72Extras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => {
+                assert_eq!(error.parameter(), "type");
+                assert_eq!(error.value(), "a\u{FFFD}");
+                assert_eq!(error.to_string(), "the parameter 'type' is not valid UTF-8");
+            },
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn payment_request_url() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?r=https://merchant.com/pay.php?h%3D2a8628fc2fbe";
+        let uri = input.parse::<Uri<'_, _, Bip72Extras>>().unwrap();
+        let url = uri.extras.payment_request_url().unwrap().unwrap();
+        assert_eq!(url.query(), Some("h=2a8628fc2fbe"));
+    }
+}
@@ -0,0 +1,333 @@
+//! Callback URL of point-of-sale payment requests.
+//!
+//! Point-of-sale systems sometimes attach a URL in the `callback` or `return` parameter that the
+//! wallet should notify (or open) once the payment is made. It's not standardized by a BIP so
+//! only the shape of the URL is checked: it must be an absolute `http(s)` URL with a host and a
+//! valid port. The check is the same whether the `url` feature is enabled or not.
+
+use alloc::string::String;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// Callback parameter of the URI.
+///
+/// Recognizes the `callback` parameter and its `return` alias. The key is preserved so that
+/// displaying the URI doesn't change it.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct CallbackExtras {
+    /// The callback URL (`callback` or `return` parameter).
+    ///
+    /// This is the decoded value, it gets percent-encoded when displaying the URI.
+    pub url: Option<String>,
+
+    /// The key the URL appeared under.
+    pub key: CallbackKey,
+}
+
+/// The key of the callback parameter.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CallbackKey {
+    /// The `callback` key.
+    #[default]
+    Callback,
+    /// The `return` key.
+    Return,
+}
+
+impl CallbackKey {
+    /// Returns the key as it appears in the URI.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CallbackKey::Callback => "callback",
+            CallbackKey::Return => "return",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "callback" => Some(CallbackKey::Callback),
+            "return" => Some(CallbackKey::Return),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CallbackKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl CallbackExtras {
+    /// Creates callback extras with given URL using the `callback` key.
+    ///
+    /// The URL is not validated, parsing the displayed URI fails if it's invalid.
+    pub fn new(url: String) -> Self {
+        CallbackExtras {
+            url: Some(url),
+            key: CallbackKey::Callback,
+        }
+    }
+
+    /// Returns the callback URL, if any.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the callback URL parsed as [`url::Url`] or `None` if there's no callback.
+    ///
+    /// Parsing only checks the shape of the URL, so this can still fail, e.g. for hosts with
+    /// characters forbidden by the URL standard or for manually constructed extras.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn callback_url(&self) -> Option<Result<url::Url, url::ParseError>> {
+        self.url.as_deref().map(url::Url::parse)
+    }
+}
+
+impl crate::IntoOwnedExtras for CallbackExtras {
+    type Owned = CallbackExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for CallbackExtras {
+    /// Merges the URLs, the key is taken from the URI providing the URL.
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        let key = if self.url.is_none() && other.url.is_some() {
+            other.key
+        } else {
+            self.key
+        };
+        Ok(CallbackExtras {
+            url: crate::merge_option(key.as_str(), self.url, &other.url, PartialEq::eq)?,
+            key,
+        })
+    }
+}
+
+impl DeserializeParams<'_> for CallbackExtras {
+    type DeserializationState = CallbackState;
+}
+
+impl DeserializationError for CallbackExtras {
+    type Error = CallbackError;
+}
+
+/// Deserialization state of [`CallbackExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct CallbackState {
+    url: Option<String>,
+    key: CallbackKey,
+}
+
+impl DeserializationState<'_> for CallbackState {
+    type Value = CallbackExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        CallbackKey::from_key(key).is_some()
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, CallbackError> {
+        let key = match CallbackKey::from_key(key) {
+            Some(key) => key,
+            None => return Ok(ParamKind::Unknown),
+        };
+        let url = super::decode_utf8(value).map_err(|(error, value)| CallbackError {
+            key,
+            value,
+            kind: CallbackErrorKind::Utf8(error),
+        })?;
+        // notifying a different URL than the merchant intended could leak the payment
+        if self.url.is_some() {
+            return Err(CallbackError {
+                key,
+                value: url,
+                kind: CallbackErrorKind::Duplicate,
+            });
+        }
+        if let Err(kind) = validate_url(&url) {
+            return Err(CallbackError { key, value: url, kind });
+        }
+        self.url = Some(url);
+        self.key = key;
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<CallbackExtras, CallbackError> {
+        Ok(CallbackExtras {
+            url: self.url,
+            key: self.key,
+        })
+    }
+}
+
+/// Checks that the URL is an absolute `http(s)` URL with a non-empty host and a valid port.
+fn validate_url(url: &str) -> Result<(), CallbackErrorKind> {
+    if url.chars().any(|c| c.is_control() || c == ' ') {
+        return Err(CallbackErrorKind::InvalidUrl);
+    }
+    let parts = super::UrlParts::split(url).ok_or(CallbackErrorKind::InvalidUrl)?;
+    if !parts.scheme.eq_ignore_ascii_case("http") && !parts.scheme.eq_ignore_ascii_case("https") {
+        return Err(CallbackErrorKind::InvalidUrl);
+    }
+    if parts.host.is_empty() || !parts.has_valid_port() {
+        return Err(CallbackErrorKind::InvalidUrl);
+    }
+    Ok(())
+}
+
+impl<'a> SerializeParams for &'a CallbackExtras {
+    type Key = &'static str;
+    type Value = &'a str;
+    type Iterator = core::option::IntoIter<(&'static str, &'a str)>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        self.url.as_deref().map(|url| (self.key.as_str(), url)).into_iter()
+    }
+}
+
+/// Error returned when the callback parameter is invalid.
+#[derive(Debug, Clone)]
+pub struct CallbackError {
+    key: CallbackKey,
+    value: String,
+    kind: CallbackErrorKind,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+enum CallbackErrorKind {
+    Utf8(core::str::Utf8Error),
+    Duplicate,
+    InvalidUrl,
+}
+
+impl CallbackError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        self.key.as_str()
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`. For repeated parameters this is the
+    /// value of the second occurrence.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns `true` if the URI contains more than one callback parameter.
+    ///
+    /// This includes the case when both `callback` and `return` are present.
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self.kind, CallbackErrorKind::Duplicate)
+    }
+
+    /// Returns `true` if the value is not an absolute `http(s)` URL with a host and a valid port.
+    pub fn is_invalid_url(&self) -> bool {
+        matches!(self.kind, CallbackErrorKind::InvalidUrl)
+    }
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CallbackErrorKind::Utf8(_) => write!(f, "the {} parameter is not valid UTF-8", self.key),
+            CallbackErrorKind::Duplicate => write!(f, "the URI contains more than one callback parameter"),
+            CallbackErrorKind::InvalidUrl => write!(f, "the {} parameter is not an http(s) URL", self.key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for CallbackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            CallbackErrorKind::Utf8(error) => Some(error),
+            CallbackErrorKind::Duplicate | CallbackErrorKind::InvalidUrl => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CallbackExtras, CallbackKey};
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    fn parse_error(input: &'static str) -> super::CallbackError {
+        match input.parse::<Uri<'_, _, CallbackExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => error,
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn callback_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&callback=https://shop.example/paid?order%3D42";
+        let uri = input.parse::<Uri<'_, _, CallbackExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.url(), Some("https://shop.example/paid?order=42"));
+        assert_eq!(uri.extras.key, CallbackKey::Callback);
+        assert_eq!(uri.to_string(), input);
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?return=http://user@10.0.0.1:8080/";
+        let uri = input.parse::<Uri<'_, _, CallbackExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.key, CallbackKey::Return);
+        assert_eq!(uri.to_string(), input);
+
+        for input in [
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://shop.example:65535/",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://[::1]:8080/",
+        ] {
+            assert!(input.parse::<Uri<'_, _, CallbackExtras>>().is_ok(), "{}", input);
+        }
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _, CallbackExtras>>().unwrap();
+        assert_eq!(uri.extras, CallbackExtras::default());
+    }
+
+    #[test]
+    fn invalid_callback() {
+        for input in [
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=shop.example/paid",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=ftp://shop.example/",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?return=https:///paid",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?return=https://shop%20example/",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://shop.example:99999/",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://shop.example:80a/",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://:8080/",
+        ] {
+            let error = parse_error(input);
+            assert!(error.is_invalid_url(), "{}", input);
+        }
+        let error = parse_error("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=ftp://shop.example/");
+        assert_eq!(error.parameter(), "callback");
+        assert_eq!(error.value(), "ftp://shop.example/");
+        assert_eq!(error.to_string(), "the callback parameter is not an http(s) URL");
+    }
+
+    #[test]
+    fn duplicate_callback() {
+        let error = parse_error("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://a.example&return=https://b.example");
+        assert!(error.is_duplicate());
+        assert_eq!(error.parameter(), "return");
+        assert_eq!(error.value(), "https://b.example");
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn callback_url() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?callback=https://shop.example/paid?order%3D42";
+        let uri = input.parse::<Uri<'_, _, CallbackExtras>>().unwrap();
+        let url = uri.extras.callback_url().unwrap().unwrap();
+        assert_eq!(url.host_str(), Some("shop.example"));
+        assert_eq!(url.query(), Some("order=42"));
+    }
+}
@@ -0,0 +1,287 @@
+//! Composition of extras.
+//!
+//! [`CombinedExtras`] allows parsing URIs carrying parameters of several extensions at once,
+//! e.g. payjoin and Lightning. Each parameter is offered to the first extras and if they don't
+//! recognize it, to the second ones. More extras can be combined by nesting, putting
+//! [`UnknownParams`](super::UnknownParams) last collects the parameters nobody recognized:
+//!
+//! `CombinedExtras<PayjoinExtras, CombinedExtras<LightningExtras, UnknownParams<'a>>>`
+
+use alloc::borrow::Cow;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::{SerializeParams, SerializeValue};
+use crate::Param;
+
+/// Two extras parsed from the same URI.
+///
+/// Parameters recognized by `first` are not offered to `second`. Displaying the URI writes the
+/// parameters of `first` followed by those of `second`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct CombinedExtras<A, B> {
+    /// The extras having precedence.
+    pub first: A,
+    /// The extras receiving the parameters not recognized by `first`.
+    pub second: B,
+}
+
+impl<A, B> CombinedExtras<A, B> {
+    /// Combines the extras.
+    pub fn new(first: A, second: B) -> Self {
+        CombinedExtras { first, second }
+    }
+}
+
+impl<A: crate::IntoOwnedExtras, B: crate::IntoOwnedExtras> crate::IntoOwnedExtras for CombinedExtras<A, B> {
+    type Owned = CombinedExtras<A::Owned, B::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        CombinedExtras {
+            first: self.first.into_owned(),
+            second: self.second.into_owned(),
+        }
+    }
+}
+
+impl<A: crate::MergeExtras, B: crate::MergeExtras> crate::MergeExtras for CombinedExtras<A, B> {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(CombinedExtras {
+            first: self.first.merge(&other.first)?,
+            second: self.second.merge(&other.second)?,
+        })
+    }
+}
+
+impl<'de, A: DeserializeParams<'de>, B: DeserializeParams<'de>> DeserializeParams<'de> for CombinedExtras<A, B> {
+    type DeserializationState = CombinedState<A::DeserializationState, B::DeserializationState>;
+}
+
+impl<A: DeserializationError, B: DeserializationError> DeserializationError for CombinedExtras<A, B> {
+    type Error = CombinedError<A::Error, B::Error>;
+}
+
+/// Deserialization state of [`CombinedExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct CombinedState<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<'de, A: DeserializationState<'de>, B: DeserializationState<'de>> DeserializationState<'de> for CombinedState<A, B> {
+    type Value = CombinedExtras<A::Value, B::Value>;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        self.first.is_param_known(key) || self.second.is_param_known(key)
+    }
+
+    fn deserialize_temp(
+        &mut self,
+        key: &str,
+        value: Param<'_>,
+    ) -> Result<ParamKind, CombinedError<<A::Value as DeserializationError>::Error, <B::Value as DeserializationError>::Error>> {
+        let first_key = self.first.normalize_key(key);
+        if self.first.deserialize_temp(first_key, value.clone()).map_err(CombinedError::First)? == ParamKind::Known {
+            return Ok(ParamKind::Known);
+        }
+        let second_key = self.second.normalize_key(key);
+        self.second.deserialize_temp(second_key, value).map_err(CombinedError::Second)
+    }
+
+    fn deserialize_borrowed(
+        &mut self,
+        key: &'de str,
+        value: Param<'de>,
+    ) -> Result<ParamKind, CombinedError<<A::Value as DeserializationError>::Error, <B::Value as DeserializationError>::Error>> {
+        let first_key = self.first.normalize_key(key);
+        if self.first.deserialize_borrowed(first_key, value.clone()).map_err(CombinedError::First)? == ParamKind::Known {
+            return Ok(ParamKind::Known);
+        }
+        let second_key = self.second.normalize_key(key);
+        self.second.deserialize_borrowed(second_key, value).map_err(CombinedError::Second)
+    }
+
+    fn finalize(self) -> Result<Self::Value, CombinedError<<A::Value as DeserializationError>::Error, <B::Value as DeserializationError>::Error>> {
+        Ok(CombinedExtras {
+            first: self.first.finalize().map_err(CombinedError::First)?,
+            second: self.second.finalize().map_err(CombinedError::Second)?,
+        })
+    }
+}
+
+/// Extras that can be serialized by reference.
+///
+/// This is implemented for every type whose references implement [`SerializeParams`]. It only
+/// exists to bound the combined extras, bounding them with `&'a A: SerializeParams` sends the
+/// compiler into infinite recursion when the type of extras is inferred.
+pub trait SerializeParamsRef<'a> {
+    /// The serializable reference.
+    type Ref: SerializeParams;
+
+    /// Returns the serializable reference.
+    fn as_serialize_params(&'a self) -> Self::Ref;
+}
+
+impl<'a, T: 'a> SerializeParamsRef<'a> for T
+where
+    &'a T: SerializeParams,
+{
+    type Ref = &'a T;
+
+    fn as_serialize_params(&'a self) -> Self::Ref {
+        self
+    }
+}
+
+impl<'a, A: SerializeParamsRef<'a>, B: SerializeParamsRef<'a>> SerializeParams for &'a CombinedExtras<A, B> {
+    type Key = CombinedKey<<A::Ref as SerializeParams>::Key, <B::Ref as SerializeParams>::Key>;
+    type Value = CombinedValue<<A::Ref as SerializeParams>::Value, <B::Ref as SerializeParams>::Value>;
+    type Iterator = CombinedParams<<A::Ref as SerializeParams>::Iterator, <B::Ref as SerializeParams>::Iterator>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        CombinedParams {
+            first: self.first.as_serialize_params().serialize_params(),
+            second: self.second.as_serialize_params().serialize_params(),
+        }
+    }
+}
+
+/// Key of a parameter of [`CombinedExtras`], coming from either of the extras.
+#[derive(Debug, Clone)]
+pub enum CombinedKey<A, B> {
+    /// The key of the first extras.
+    First(A),
+    /// The key of the second extras.
+    Second(B),
+}
+
+impl<A: fmt::Display, B: fmt::Display> fmt::Display for CombinedKey<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombinedKey::First(key) => fmt::Display::fmt(key, f),
+            CombinedKey::Second(key) => fmt::Display::fmt(key, f),
+        }
+    }
+}
+
+/// Value of a parameter of [`CombinedExtras`], coming from either of the extras.
+///
+/// Unlike [`CombinedKey`] this doesn't implement `Display` so that values which are [`Param`]s
+/// keep their bytes.
+#[derive(Debug, Clone)]
+pub enum CombinedValue<A, B> {
+    /// The value of the first extras.
+    First(A),
+    /// The value of the second extras.
+    Second(B),
+}
+
+impl<A: SerializeValue, B: SerializeValue> SerializeValue for CombinedValue<A, B> {
+    fn fmt_encoded(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombinedValue::First(value) => value.fmt_encoded(f),
+            CombinedValue::Second(value) => value.fmt_encoded(f),
+        }
+    }
+
+    fn to_decoded_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            CombinedValue::First(value) => value.to_decoded_bytes(),
+            CombinedValue::Second(value) => value.to_decoded_bytes(),
+        }
+    }
+}
+
+/// Iterator over parameters of [`CombinedExtras`].
+#[derive(Debug, Clone)]
+pub struct CombinedParams<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<KA, VA, KB, VB, A: Iterator<Item = (KA, VA)>, B: Iterator<Item = (KB, VB)>> Iterator for CombinedParams<A, B> {
+    type Item = (CombinedKey<KA, KB>, CombinedValue<VA, VB>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.first.next() {
+            Some((key, value)) => Some((CombinedKey::First(key), CombinedValue::First(value))),
+            None => self
+                .second
+                .next()
+                .map(|(key, value)| (CombinedKey::Second(key), CombinedValue::Second(value))),
+        }
+    }
+}
+
+/// Error returned when either of the combined extras fails to deserialize.
+///
+/// The error is transparent - it displays and reports the source of the inner error.
+#[derive(Debug, Clone)]
+pub enum CombinedError<A, B> {
+    /// The first extras failed.
+    First(A),
+    /// The second extras failed.
+    Second(B),
+}
+
+impl<A: fmt::Display, B: fmt::Display> fmt::Display for CombinedError<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CombinedError::First(error) => fmt::Display::fmt(error, f),
+            CombinedError::Second(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl<A: std::error::Error, B: std::error::Error> std::error::Error for CombinedError<A, B> {
+    // the error is transparent, `Display` already shows the inner error
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CombinedError::First(error) => error.source(),
+            CombinedError::Second(error) => error.source(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CombinedExtras;
+    use crate::extras::{LightningExtras, PayjoinExtras, UnknownParams};
+    use crate::Uri;
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    type Unified<'a> = CombinedExtras<PayjoinExtras, CombinedExtras<LightningExtras, UnknownParams<'a>>>;
+
+    #[test]
+    fn payjoin_and_lightning() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj&lightning=lnbc1invoice&foo=bar";
+        let uri = Uri::<'_, _, Unified<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.extras.first.endpoint.as_deref(), Some("https://example.com/pj"));
+        assert_eq!(uri.extras.second.first.invoice.as_deref(), Some("lnbc1invoice"));
+        let unknown = &uri.extras.second.second;
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown.get("foo").unwrap().decoded_bytes(), &b"bar"[..]);
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn errors_of_both() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pjos=2";
+        match Uri::<'_, _, Unified<'_>>::try_from(input).unwrap_err() {
+            crate::de::Error::Extras(super::CombinedError::First(error)) => assert_eq!(error.parameter(), "pjos"),
+            crate::de::Error::Extras(error) => panic!("unexpected error: {}", error),
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?lightning=%FF";
+        match Uri::<'_, _, Unified<'_>>::try_from(input).unwrap_err() {
+            crate::de::Error::Extras(super::CombinedError::Second(super::CombinedError::First(error))) => {
+                assert_eq!(error.to_string(), "the lightning parameter is not valid UTF-8")
+            },
+            crate::de::Error::Extras(error) => panic!("unexpected error: {}", error),
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+        assert!(Uri::<'_, _, Unified<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Expiry parameters of time-limited payment requests.
+//!
+//! These are not standardized by a BIP but are used by some wallets (e.g. Electrum) and
+//! point-of-sale systems:
+//!
+//! * `time` - unix timestamp (seconds) of the creation of the request
+//! * `exp` - number of seconds after `time` when the request expires
+
+use alloc::string::String;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// Expiry parameters of the URI.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct ExpiryExtras {
+    /// Unix timestamp of the creation of the request (`time` parameter).
+    pub time: Option<u64>,
+
+    /// Number of seconds after `time` when the request expires (`exp` parameter).
+    pub exp: Option<u64>,
+}
+
+impl ExpiryExtras {
+    /// Returns the unix timestamp (seconds) when the request expires.
+    ///
+    /// Returns `None` if either `time` or `exp` is missing or if the sum overflows.
+    pub fn expiry(&self) -> Option<u64> {
+        self.time?.checked_add(self.exp?)
+    }
+
+    /// Returns `true` if the request expired at `now` (unix timestamp in seconds).
+    ///
+    /// Returns `None` if the expiry is unknown.
+    pub fn is_expired(&self, now: u64) -> Option<bool> {
+        self.expiry().map(|expiry| now >= expiry)
+    }
+}
+
+impl crate::IntoOwnedExtras for ExpiryExtras {
+    type Owned = ExpiryExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for ExpiryExtras {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(ExpiryExtras {
+            time: crate::merge_option("time", self.time, &other.time, PartialEq::eq)?,
+            exp: crate::merge_option("exp", self.exp, &other.exp, PartialEq::eq)?,
+        })
+    }
+}
+
+impl DeserializeParams<'_> for ExpiryExtras {
+    type DeserializationState = ExpiryState;
+}
+
+impl DeserializationError for ExpiryExtras {
+    type Error = ExpiryError;
+}
+
+/// Deserialization state of [`ExpiryExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct ExpiryState {
+    time: Option<u64>,
+    exp: Option<u64>,
+}
+
+/// Parses unsigned decimal number without sign.
+fn parse_seconds(parameter: &'static str, value: Param<'_>) -> Result<u64, ExpiryError> {
+    let value = value.decoded_bytes();
+    let seconds = if !value.is_empty() && value.iter().all(u8::is_ascii_digit) {
+        core::str::from_utf8(&value).ok().and_then(|digits| digits.parse().ok())
+    } else {
+        None
+    };
+    seconds.ok_or_else(|| ExpiryError {
+        parameter,
+        value: String::from_utf8_lossy(&value).into_owned(),
+    })
+}
+
+impl DeserializationState<'_> for ExpiryState {
+    type Value = ExpiryExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        matches!(key, "time" | "exp")
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, ExpiryError> {
+        match key {
+            "time" => self.time = Some(parse_seconds("time", value)?),
+            "exp" => self.exp = Some(parse_seconds("exp", value)?),
+            _ => return Ok(ParamKind::Unknown),
+        }
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<ExpiryExtras, ExpiryError> {
+        Ok(ExpiryExtras {
+            time: self.time,
+            exp: self.exp,
+        })
+    }
+}
+
+type ParamIter = core::option::IntoIter<(&'static str, u64)>;
+
+impl SerializeParams for &ExpiryExtras {
+    type Key = &'static str;
+    type Value = u64;
+    type Iterator = core::iter::Chain<ParamIter, ParamIter>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        let time = self.time.map(|time| ("time", time));
+        let exp = self.exp.map(|exp| ("exp", exp));
+        time.into_iter().chain(exp)
+    }
+}
+
+/// Error returned when a timestamp or duration is not a valid number of seconds.
+#[derive(Debug, Clone)]
+pub struct ExpiryError {
+    parameter: &'static str,
+    value: String,
+}
+
+impl ExpiryError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        self.parameter
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for ExpiryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid value '{}' of {} parameter, expected number of seconds",
+            self.value, self.parameter
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for ExpiryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::ExpiryExtras;
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    #[test]
+    fn expiry() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&time=1700000000&exp=3600";
+        let uri = input.parse::<Uri<'_, _, ExpiryExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.expiry(), Some(1_700_003_600));
+        assert_eq!(uri.extras.is_expired(1_700_003_599), Some(false));
+        assert_eq!(uri.extras.is_expired(1_700_003_600), Some(true));
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn unknown_expiry() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?exp=3600";
+        let uri = input.parse::<Uri<'_, _, ExpiryExtras>>().unwrap();
+        assert_eq!(uri.extras.expiry(), None);
+        assert_eq!(uri.extras.is_expired(u64::MAX), None);
+    }
+
+    #[test]
+    fn malformed() {
+        for value in ["", "+1", "-1", "1.5", "0x10", "18446744073709551616", "%31a"] {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?time={}", value);
+            assert!(input.parse::<Uri<'_, _, ExpiryExtras>>().is_err(), "{}", value);
+        }
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?time=%31";
+        assert_eq!(input.parse::<Uri<'_, _, ExpiryExtras>>().unwrap().extras.time, Some(1));
+    }
+}
@@ -0,0 +1,239 @@
+//! Fee suggestions of payment requests.
+//!
+//! These are not standardized by a BIP but some merchants and wallets attach them so that the
+//! sender confirms the payment in time. Exactly these parameters are supported:
+//!
+//! * `feerate` - suggested fee rate in satoshis per virtual byte as unsigned integer
+//! * `rbf` - whether the transaction should signal replace-by-fee, `0` or `1`
+//!
+//! The suggestions are advisory, the sender decides what fee to pay.
+//!
+//! Other parameters are preserved in [`FeeExtras::other`] so displaying the URI doesn't drop
+//! them. Since this collects all parameters, it has to be the last extras of
+//! [`CombinedExtras`](super::CombinedExtras).
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use bitcoin::FeeRate;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// Fee parameters of the URI.
+///
+/// Parameters other than the fee ones are kept as they are, see the
+/// [module documentation](crate::extras::fee).
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct FeeExtras {
+    /// Suggested fee rate (`feerate` parameter).
+    ///
+    /// It's displayed in whole satoshis per virtual byte, rounded up.
+    pub fee_rate: Option<FeeRate>,
+
+    /// Whether the transaction should signal replace-by-fee (`rbf` parameter).
+    pub rbf: Option<bool>,
+
+    /// Other parameters in order of appearance.
+    ///
+    /// The pairs contain the key and the decoded value. They are displayed after the fee
+    /// parameters. Unknown `req-` parameters are still rejected when parsing.
+    pub other: Vec<(String, Param<'static>)>,
+}
+
+impl<NetVal: bitcoin::address::NetworkValidation> crate::Uri<'_, NetVal, FeeExtras> {
+    /// Returns the suggested fee rate.
+    pub fn fee_rate(&self) -> Option<FeeRate> {
+        self.extras.fee_rate
+    }
+
+    /// Returns whether the transaction should signal replace-by-fee.
+    pub fn rbf(&self) -> Option<bool> {
+        self.extras.rbf
+    }
+}
+
+impl crate::IntoOwnedExtras for FeeExtras {
+    type Owned = FeeExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for FeeExtras {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(FeeExtras {
+            fee_rate: crate::merge_option("feerate", self.fee_rate, &other.fee_rate, PartialEq::eq)?,
+            rbf: crate::merge_option("rbf", self.rbf, &other.rbf, PartialEq::eq)?,
+            other: match (self.other.is_empty(), other.other.is_empty()) {
+                (false, false) if self.other != other.other => return Err(crate::MergeConflict::new(&*other.other[0].0)),
+                (true, _) => other.other.clone(),
+                (false, _) => self.other,
+            },
+        })
+    }
+}
+
+impl DeserializeParams<'_> for FeeExtras {
+    type DeserializationState = FeeState;
+}
+
+impl DeserializationError for FeeExtras {
+    type Error = FeeError;
+}
+
+/// Deserialization state of [`FeeExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct FeeState {
+    fee_rate: Option<FeeRate>,
+    rbf: Option<bool>,
+    other: Vec<(String, Param<'static>)>,
+}
+
+impl DeserializationState<'_> for FeeState {
+    type Value = FeeExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        !key.starts_with("req-")
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, FeeError> {
+        let parameter = match key {
+            "feerate" => "feerate",
+            "rbf" => "rbf",
+            _ if self.is_param_known(key) => {
+                self.other.push((key.into(), value.decode_into_owned()));
+                return Ok(ParamKind::Known);
+            },
+            _ => return Ok(ParamKind::Unknown),
+        };
+        let value = value.decoded_bytes();
+        let error = || FeeError {
+            parameter,
+            value: String::from_utf8_lossy(&value).into_owned(),
+        };
+        if parameter == "rbf" {
+            self.rbf = Some(super::parse_bool_param(&value).map_err(|_| error())?);
+        } else {
+            if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+                return Err(error());
+            }
+            let sat_per_vb = core::str::from_utf8(&value)
+                .ok()
+                .and_then(|digits| digits.parse().ok())
+                .ok_or_else(error)?;
+            self.fee_rate = Some(FeeRate::from_sat_per_vb(sat_per_vb).ok_or_else(error)?);
+        }
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<FeeExtras, FeeError> {
+        Ok(FeeExtras {
+            fee_rate: self.fee_rate,
+            rbf: self.rbf,
+            other: self.other,
+        })
+    }
+}
+
+type ParamIter<'a> = core::option::IntoIter<(&'a str, Param<'a>)>;
+type OtherIter<'a> = core::iter::Map<core::slice::Iter<'a, (String, Param<'static>)>, fn(&'a (String, Param<'static>)) -> (&'a str, Param<'a>)>;
+
+/// Values of other parameters are serialized as bytes so even those that are not valid UTF-8
+/// roundtrip.
+impl<'a> SerializeParams for &'a FeeExtras {
+    type Key = &'a str;
+    type Value = Param<'a>;
+    type Iterator = core::iter::Chain<core::iter::Chain<ParamIter<'a>, ParamIter<'a>>, OtherIter<'a>>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        let fee_rate = self
+            .fee_rate
+            .map(|fee_rate| ("feerate", Param::from(fee_rate.to_sat_per_vb_ceil().to_string())));
+        let rbf = self.rbf.map(|rbf| ("rbf", Param::from(u64::from(rbf).to_string())));
+        let other: OtherIter<'a> = self.other.iter().map(|(key, value)| (key, value.reborrow()));
+        fee_rate.into_iter().chain(rbf).chain(other)
+    }
+}
+
+/// Error returned when a fee parameter has invalid value.
+#[derive(Debug, Clone)]
+pub struct FeeError {
+    parameter: &'static str,
+    value: String,
+}
+
+impl FeeError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        self.parameter
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let expected = if self.parameter == "rbf" { "0 or 1" } else { "whole number of sat/vB" };
+        write!(f, "invalid value '{}' of {} parameter, expected {}", self.value, self.parameter, expected)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for FeeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::FeeExtras;
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    #[test]
+    fn fee_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&feerate=12&rbf=1&foo=bar";
+        let uri = input.parse::<Uri<'_, _, FeeExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.fee_rate(), bitcoin::FeeRate::from_sat_per_vb(12));
+        assert_eq!(uri.rbf(), Some(true));
+        assert_eq!(uri.extras.other, [("foo".into(), "bar".into())]);
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn other_params_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=a%26b&rbf=0&bin=%FF&foo=2";
+        let uri = input.parse::<Uri<'_, _, FeeExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.rbf(), Some(false));
+        assert_eq!(uri.extras.other.len(), 3);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?rbf=0&foo=a%26b&bin=%FF&foo=2");
+        let reparsed = uri.to_string().parse::<Uri<'_, _, FeeExtras>>().unwrap().assume_checked();
+        assert_eq!(reparsed, uri);
+
+        assert!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1"
+            .parse::<Uri<'_, _, FeeExtras>>()
+            .is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        for param in ["feerate=", "feerate=1.5", "feerate=-1", "feerate=18446744073709551615", "rbf=yes", "rbf="] {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?{}", param);
+            assert!(input.parse::<Uri<'_, _, FeeExtras>>().is_err(), "{}", param);
+        }
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?rbf=2";
+        match input.parse::<Uri<'_, _, FeeExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => {
+                assert_eq!(error.parameter(), "rbf");
+                assert_eq!(error.to_string(), "invalid value '2' of rbf parameter, expected 0 or 1");
+            },
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Extras validated on first access.
+//!
+//! [`LazyExtras`] only checks which parameters belong to the wrapped extras when the URI is
+//! parsed and keeps their raw values. Decoding and validating them into the typed form is
+//! deferred until [`LazyExtras::get`] is called, so URIs whose extras are never read don't pay
+//! for it. Consequently invalid values of extras don't fail the parsing but surface as errors
+//! returned by `get`. Unknown `req-` parameters are still rejected during parsing since that
+//! only needs the keys.
+
+use core::marker::PhantomData;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+use super::UnknownParams;
+
+/// Extras of type `T` validated on first access.
+///
+/// Displaying the URI writes the raw parameters as they were parsed.
+#[derive(Debug, Clone)]
+pub struct LazyExtras<'a, T> {
+    params: UnknownParams<'a>,
+    parsed: Option<T>,
+}
+
+impl<'a, T> LazyExtras<'a, T> {
+    /// Returns the raw parameters belonging to the extras, in order of appearance.
+    pub fn raw(&self) -> &UnknownParams<'a> {
+        &self.params
+    }
+
+    /// Returns `true` if the extras were already validated by [`get`](Self::get).
+    pub fn is_validated(&self) -> bool {
+        self.parsed.is_some()
+    }
+}
+
+impl<'a, T: DeserializeParams<'a>> LazyExtras<'a, T> {
+    /// Returns the extras, validating the raw parameters on the first call.
+    ///
+    /// The validated extras are cached so later calls are cheap. Failed validation is not
+    /// cached, calling this again returns the same error.
+    pub fn get(&mut self) -> Result<&T, crate::de::Error<T::Error>> {
+        if self.parsed.is_none() {
+            self.parsed = Some(self.params.parse_as()?);
+        }
+        Ok(self.parsed.as_ref().expect("parsed above"))
+    }
+
+    /// Returns the extras, validating them unless they were validated already.
+    pub fn into_inner(self) -> Result<T, crate::de::Error<T::Error>> {
+        match self.parsed {
+            Some(parsed) => Ok(parsed),
+            None => self.params.parse_as(),
+        }
+    }
+}
+
+impl<T: crate::IntoOwnedExtras> crate::IntoOwnedExtras for LazyExtras<'_, T> {
+    type Owned = LazyExtras<'static, T::Owned>;
+
+    fn into_owned(self) -> Self::Owned {
+        LazyExtras {
+            params: self.params.into_owned(),
+            parsed: self.parsed.map(crate::IntoOwnedExtras::into_owned),
+        }
+    }
+}
+
+impl<'a, T: DeserializeParams<'a>> DeserializeParams<'a> for LazyExtras<'a, T> {
+    type DeserializationState = LazyState<'a, T>;
+}
+
+/// Validation errors surface in [`LazyExtras::get`] so parsing never fails because of extras.
+impl<T> DeserializationError for LazyExtras<'_, T> {
+    type Error = core::convert::Infallible;
+}
+
+/// Deserialization state of [`LazyExtras`].
+///
+/// The state of `T` is only used to tell which parameters are known.
+pub struct LazyState<'a, T: DeserializeParams<'a>> {
+    known: T::DeserializationState,
+    params: UnknownParams<'a>,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, T: DeserializeParams<'a>> Default for LazyState<'a, T> {
+    fn default() -> Self {
+        LazyState {
+            known: Default::default(),
+            params: UnknownParams::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: DeserializeParams<'a>> LazyState<'a, T> {
+    fn is_known(&mut self, key: &str) -> bool {
+        let normalized_key = self.known.normalize_key(key);
+        self.known.is_param_known(normalized_key)
+    }
+}
+
+impl<'a, T: DeserializeParams<'a>> DeserializationState<'a> for LazyState<'a, T> {
+    type Value = LazyExtras<'a, T>;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        self.known.is_param_known(key)
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, core::convert::Infallible> {
+        if !self.is_known(key) {
+            return Ok(ParamKind::Unknown);
+        }
+        self.params.push(alloc::string::String::from(key), value.decode_into_owned());
+        Ok(ParamKind::Known)
+    }
+
+    fn deserialize_borrowed(&mut self, key: &'a str, value: Param<'a>) -> Result<ParamKind, core::convert::Infallible> {
+        if !self.is_known(key) {
+            return Ok(ParamKind::Unknown);
+        }
+        self.params.push(key, value);
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<LazyExtras<'a, T>, core::convert::Infallible> {
+        Ok(LazyExtras {
+            params: self.params,
+            parsed: None,
+        })
+    }
+}
+
+impl<'a, 'b, T> SerializeParams for &'a LazyExtras<'b, T> {
+    type Key = <&'a UnknownParams<'b> as SerializeParams>::Key;
+    type Value = <&'a UnknownParams<'b> as SerializeParams>::Value;
+    type Iterator = <&'a UnknownParams<'b> as SerializeParams>::Iterator;
+
+    fn serialize_params(self) -> Self::Iterator {
+        self.params.serialize_params()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LazyExtras;
+    use crate::extras::PayjoinExtras;
+    use crate::Uri;
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn validates_on_access() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj&pjos=0&foo=bar";
+        let mut uri = Uri::<'_, _, LazyExtras<'_, PayjoinExtras>>::try_from(input).unwrap();
+        assert!(!uri.extras.is_validated());
+        assert_eq!(uri.extras.raw().len(), 2);
+        let payjoin = uri.extras.get().unwrap();
+        assert_eq!(payjoin.endpoint.as_deref(), Some("https://example.com/pj"));
+        assert!(payjoin.disable_output_substitution);
+        assert!(uri.extras.is_validated());
+        assert_eq!(
+            uri.assume_checked().to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj&pjos=0"
+        );
+    }
+
+    #[test]
+    fn errors_at_access() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com/pj&pjos=2";
+        assert!(Uri::<'_, _, PayjoinExtras>::try_from(input).is_err());
+        let mut uri = Uri::<'_, _, LazyExtras<'_, PayjoinExtras>>::try_from(input).unwrap();
+        match uri.extras.get().unwrap_err() {
+            crate::de::Error::Extras(error) => assert_eq!(error.parameter(), "pjos"),
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+        assert!(!uri.extras.is_validated());
+        assert!(uri.extras.into_inner().is_err());
+
+        // unknown required parameters are still rejected when parsing
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1";
+        assert!(Uri::<'_, _, LazyExtras<'_, PayjoinExtras>>::try_from(input).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Lightning parameter of unified URIs.
+//!
+//! Unified URIs carry a BOLT11 invoice in the `lightning` parameter so that wallets supporting
+//! Lightning can pay the invoice while the others fall back to the on-chain address.
+
+use alloc::string::String;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+
+/// Lightning parameter of the URI.
+///
+/// The invoice is not validated, use a Lightning library to parse it.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct LightningExtras {
+    /// The BOLT11 invoice (`lightning` parameter).
+    pub invoice: Option<String>,
+}
+
+impl LightningExtras {
+    /// Creates lightning extras with given invoice.
+    pub fn new(invoice: String) -> Self {
+        LightningExtras { invoice: Some(invoice) }
+    }
+}
+
+/// Payment rails offered by a unified URI.
+///
+/// Returned by [`Uri::payment_rails`](crate::Uri::payment_rails) and
+/// [`RawUri::payment_rails`](crate::RawUri::payment_rails).
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PaymentRails {
+    /// Only an on-chain address.
+    OnChain,
+    /// Only a Lightning invoice, the URI has no address.
+    Lightning,
+    /// Both an on-chain address and a Lightning invoice.
+    Both,
+}
+
+impl<NetVal: bitcoin::address::NetworkValidation> crate::Uri<'_, NetVal, LightningExtras> {
+    /// Returns the payment rails offered by the URI.
+    ///
+    /// [`Uri`](crate::Uri) always has an address so this is either [`PaymentRails::OnChain`]
+    /// or [`PaymentRails::Both`]. Lightning-only URIs such as `bitcoin:?lightning=...` fail to
+    /// parse (see [`UriError::is_addressless`](crate::de::UriError::is_addressless)), use
+    /// [`RawUri::payment_rails`](crate::RawUri::payment_rails) to classify them.
+    pub fn payment_rails(&self) -> PaymentRails {
+        match self.extras.invoice {
+            Some(_) => PaymentRails::Both,
+            None => PaymentRails::OnChain,
+        }
+    }
+
+    /// Returns `true` if the URI doesn't offer a Lightning invoice.
+    pub fn is_onchain_only(&self) -> bool {
+        self.payment_rails() == PaymentRails::OnChain
+    }
+
+    /// Returns `true` if the URI offers only a Lightning invoice.
+    ///
+    /// This is always `false` since [`Uri`](crate::Uri) requires an address, it's provided for
+    /// symmetry with [`RawUri::payment_rails`](crate::RawUri::payment_rails).
+    pub fn is_lightning_only(&self) -> bool {
+        self.payment_rails() == PaymentRails::Lightning
+    }
+}
+
+impl crate::RawUri<'_> {
+    /// Returns the payment rails offered by the URI without validating it.
+    ///
+    /// A non-empty address counts as on-chain and a `lightning` (or `req-lightning`) parameter
+    /// with non-empty value counts as Lightning. Returns `None` if the URI offers neither.
+    pub fn payment_rails(&self) -> Option<PaymentRails> {
+        let has_address = !self.address().is_empty();
+        let has_lightning = self
+            .params()
+            .iter()
+            .any(|(key, value)| matches!(*key, "lightning" | "req-lightning") && matches!(value, Some(value) if !value.is_empty()));
+        match (has_address, has_lightning) {
+            (true, false) => Some(PaymentRails::OnChain),
+            (false, true) => Some(PaymentRails::Lightning),
+            (true, true) => Some(PaymentRails::Both),
+            (false, false) => None,
+        }
+    }
+}
+
+#[cfg(feature = "lightning")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lightning")))]
+impl crate::Uri<'static, bitcoin::address::NetworkChecked, LightningExtras> {
+    /// Constructs unified URI from the on-chain fallback of a BOLT11 invoice.
+    ///
+    /// The URI contains the first fallback address of the invoice, the amount of the invoice
+    /// and the invoice itself in the `lightning` parameter. Amounts with fractions of satoshis
+    /// are rounded up so that the on-chain payment is never less than the invoice.
+    pub fn from_bolt11_fallback(invoice: &lightning_invoice::Bolt11Invoice) -> Result<Self, Bolt11FallbackError> {
+        use alloc::string::ToString;
+
+        let address = invoice
+            .fallback_addresses()
+            .into_iter()
+            .next()
+            .ok_or(Bolt11FallbackError::MissingFallback)?;
+        let uri = crate::Uri::with_extras(address, LightningExtras::new(invoice.to_string()));
+        match invoice.amount_milli_satoshis() {
+            Some(msat) => {
+                let sat = msat / 1000 + u64::from(msat % 1000 != 0);
+                uri.with_amount(bitcoin::Amount::from_sat(sat))
+                    .map_err(Bolt11FallbackError::AmountOutOfRange)
+            },
+            None => Ok(uri),
+        }
+    }
+}
+
+/// Error returned by [`Uri::from_bolt11_fallback`](crate::Uri::from_bolt11_fallback).
+#[cfg(feature = "lightning")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lightning")))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Bolt11FallbackError {
+    /// The invoice has no on-chain fallback address.
+    MissingFallback,
+    /// The amount of the invoice exceeds the supply of bitcoin.
+    AmountOutOfRange(crate::AmountOutOfRangeError),
+}
+
+#[cfg(feature = "lightning")]
+impl fmt::Display for Bolt11FallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Bolt11FallbackError::MissingFallback => write!(f, "the invoice has no on-chain fallback address"),
+            Bolt11FallbackError::AmountOutOfRange(_) => write!(f, "the amount of the invoice is out of range"),
+        }
+    }
+}
+
+#[cfg(all(feature = "lightning", feature = "std"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "lightning", feature = "std"))))]
+impl std::error::Error for Bolt11FallbackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Bolt11FallbackError::MissingFallback => None,
+            Bolt11FallbackError::AmountOutOfRange(error) => Some(error),
+        }
+    }
+}
+
+impl crate::IntoOwnedExtras for LightningExtras {
+    type Owned = LightningExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for LightningExtras {
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        Ok(LightningExtras {
+            invoice: crate::merge_option("lightning", self.invoice, &other.invoice, PartialEq::eq)?,
+        })
+    }
+}
+
+impl DeserializeParams<'_> for LightningExtras {
+    type DeserializationState = LightningState;
+}
+
+impl DeserializationError for LightningExtras {
+    type Error = LightningError;
+}
+
+/// Deserialization state of [`LightningExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct LightningState {
+    invoice: Option<String>,
+}
+
+impl DeserializationState<'_> for LightningState {
+    type Value = LightningExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        key == "lightning"
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, LightningError> {
+        if key != "lightning" {
+            return Ok(ParamKind::Unknown);
+        }
+        let invoice = super::decode_utf8(value).map_err(|(error, value)| LightningError { value, error })?;
+        self.invoice = Some(invoice);
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<LightningExtras, LightningError> {
+        Ok(LightningExtras { invoice: self.invoice })
+    }
+}
+
+impl<'a> SerializeParams for &'a LightningExtras {
+    type Key = &'static str;
+    type Value = &'a str;
+    type Iterator = core::option::IntoIter<(&'static str, &'a str)>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        self.invoice.as_deref().map(|invoice| ("lightning", invoice)).into_iter()
+    }
+}
+
+/// Error returned when the `lightning` parameter is not valid UTF-8.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub struct LightningError {
+    value: String,
+    error: core::str::Utf8Error,
+}
+
+impl LightningError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        "lightning"
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for LightningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the lightning parameter is not valid UTF-8")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for LightningError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LightningExtras;
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    #[test]
+    fn lightning_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&lightning=lnbc1invoice";
+        let uri = input.parse::<Uri<'_, _, LightningExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.invoice.as_deref(), Some("lnbc1invoice"));
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn payment_rails() {
+        use super::PaymentRails;
+        use crate::RawUri;
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?lightning=lnbc1invoice"
+            .parse::<Uri<'_, _, LightningExtras>>()
+            .unwrap();
+        assert_eq!(uri.payment_rails(), PaymentRails::Both);
+        assert!(!uri.is_onchain_only());
+        assert!(!uri.is_lightning_only());
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1"
+            .parse::<Uri<'_, _, LightningExtras>>()
+            .unwrap();
+        assert_eq!(uri.payment_rails(), PaymentRails::OnChain);
+        assert!(uri.is_onchain_only());
+
+        let rails = |input: &str| RawUri::parse(input).unwrap().payment_rails();
+        assert_eq!(rails("bitcoin:?lightning=lnbc1invoice"), Some(PaymentRails::Lightning));
+        assert_eq!(
+            rails("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-lightning=lnbc1invoice"),
+            Some(PaymentRails::Both)
+        );
+        assert_eq!(rails("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?lightning="), Some(PaymentRails::OnChain));
+        assert_eq!(rails("bitcoin:?amount=1"), None);
+    }
+
+    #[cfg(feature = "lightning")]
+    #[test]
+    fn from_bolt11_fallback() {
+        use super::Bolt11FallbackError;
+
+        const INVOICE: &str = "lnbc20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqsfpp3qjmp7lwpagxun9pygexvgpjdc4jdj85fr9yq20q82gphp2nflc7jtzrcazrra7wwgzxqc8u7754cdlpfrmccae92qgzqvzq2ps8pqqqqqqpqqqqq9qqqvpeuqafqxu92d8lr6fvg0r5gv0heeeqgcrqlnm6jhphu9y00rrhy4grqszsvpcgpy9qqqqqqgqqqqq7qqzq9qrsgqdfjcdk6w3ak5pca9hwfwfh63zrrz06wwfya0ydlzpgzxkn5xagsqz7x9j4jwe7yj7vaf2k9lqsdk45kts2fd0fkr28am0u4w95tt2nsq76cqw0";
+
+        let invoice = INVOICE.parse::<lightning_invoice::Bolt11Invoice>().unwrap();
+        let uri = Uri::from_bolt11_fallback(&invoice).unwrap();
+        assert_eq!(
+            uri.to_string(),
+            alloc::format!("bitcoin:1RustyRX2oai4EYYDpQGWvEL62BBGqN9T?amount=0.02&lightning={}", INVOICE)
+        );
+        assert_eq!(uri.extras.invoice.as_deref(), Some(INVOICE));
+
+        let without_fallback = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+        let invoice = without_fallback.parse::<lightning_invoice::Bolt11Invoice>().unwrap();
+        assert_eq!(Uri::from_bolt11_fallback(&invoice).unwrap_err(), Bolt11FallbackError::MissingFallback);
+    }
+}
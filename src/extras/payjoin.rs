@@ -0,0 +1,933 @@
+//! Payjoin parameters as defined in [BIP78](https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki).
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::SerializeParams;
+use crate::Param;
+use super::UrlParts;
+
+/// Payjoin parameters of the URI.
+///
+/// Recognizes `pj` (the endpoint), `pjos` (output substitution), `v` (version) and `poll` (polling
+/// interval) parameters.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct PayjoinExtras {
+    /// The payjoin endpoint (`pj` parameter).
+    ///
+    /// This is the decoded value, it gets percent-encoded when displaying the URI.
+    pub endpoint: Option<String>,
+
+    /// Set if the receiver disallows output substitution (`pjos=0`).
+    pub disable_output_substitution: bool,
+
+    /// The payjoin version (`v` parameter) as it appeared in the URI.
+    ///
+    /// The value is preserved even if it's not a known version so that displaying the URI
+    /// doesn't drop it. Like `pjos` it's only displayed together with the endpoint. See
+    /// [`payjoin_version`](Self::payjoin_version).
+    pub version: Option<String>,
+
+    /// Suggested interval between polls of the endpoint in seconds (`poll` parameter).
+    ///
+    /// Asynchronous (v2) payjoin receivers may hint how often the sender should poll for the
+    /// response. Like `pjos` it's only displayed together with the endpoint. See
+    /// [`poll_interval`](Self::poll_interval).
+    pub poll: Option<u64>,
+}
+
+impl PayjoinExtras {
+    /// Creates payjoin extras with given endpoint.
+    pub fn new(endpoint: String) -> Self {
+        PayjoinExtras {
+            endpoint: Some(endpoint),
+            disable_output_substitution: false,
+            version: None,
+            poll: None,
+        }
+    }
+
+    /// Returns the suggested polling interval.
+    pub fn poll_interval(&self) -> Option<core::time::Duration> {
+        self.poll.map(core::time::Duration::from_secs)
+    }
+
+    /// Returns the payjoin version or `None` if it's missing or not a number.
+    ///
+    /// Unknown versions don't fail parsing, use [`check_version`](Self::check_version) to
+    /// reject them.
+    pub fn payjoin_version(&self) -> Option<u8> {
+        let version = self.version.as_deref()?;
+        if version.bytes().all(|byte| byte.is_ascii_digit()) {
+            version.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// Checks that the version is one of `supported`.
+    ///
+    /// This is the strict counterpart of the lenient parsing which preserves any version. A
+    /// missing version is accepted since BIP78 doesn't require it. Versions which are not a
+    /// number or not in `supported` fail with an error for which
+    /// [`PayjoinError::is_unsupported_version`] returns `true`.
+    pub fn check_version(&self, supported: &[u8]) -> Result<(), PayjoinError> {
+        let version = match &self.version {
+            Some(version) => version,
+            None => return Ok(()),
+        };
+        match self.payjoin_version() {
+            Some(known) if supported.contains(&known) => Ok(()),
+            _ => Err(PayjoinError {
+                parameter: "v",
+                value: version.clone(),
+                kind: PayjoinErrorKind::UnsupportedVersion,
+            }),
+        }
+    }
+
+    /// Compares the endpoint with `expected` without short-circuiting on the first difference.
+    ///
+    /// Payjoin v2 endpoints carry key material so comparing them should not leak the position
+    /// of the first difference through timing. The length may still leak. Returns `false` if
+    /// there's no endpoint.
+    #[cfg(feature = "subtle")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "subtle")))]
+    pub fn endpoint_ct_eq(&self, expected: &str) -> bool {
+        use subtle::ConstantTimeEq;
+
+        match &self.endpoint {
+            Some(endpoint) => endpoint.as_bytes().ct_eq(expected.as_bytes()).into(),
+            None => false,
+        }
+    }
+
+    /// Checks the endpoint for common problems.
+    ///
+    /// Parsing is lenient and accepts any endpoint, this reports which rule the endpoint
+    /// violates, if any, so that callers can decide what to do. Returns `Ok` if there's no
+    /// endpoint. See [`validate_endpoint`] for the rules.
+    pub fn validate_endpoint(&self) -> Result<(), PjEndpointError> {
+        self.endpoint.as_deref().map_or(Ok(()), validate_endpoint)
+    }
+
+    /// Checks the endpoint like [`validate_endpoint`](Self::validate_endpoint) with additional
+    /// rules of `policy`.
+    ///
+    /// Returns `Ok` if there's no endpoint.
+    pub fn validate_endpoint_with_policy(&self, policy: &EndpointPolicy) -> Result<(), PjEndpointError> {
+        self.endpoint
+            .as_deref()
+            .map_or(Ok(()), |endpoint| validate_endpoint_with_policy(endpoint, policy))
+    }
+
+    /// Returns the endpoint parsed as [`url::Url`] or `None` if there's no endpoint.
+    ///
+    /// The returned value can be directly handed to an HTTP client.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn pj_url(&self) -> Option<Result<url::Url, url::ParseError>> {
+        self.endpoint.as_deref().map(url::Url::parse)
+    }
+
+    /// Returns the validated endpoint classified as clearnet or onion.
+    ///
+    /// The endpoint is checked by [`validate_endpoint`] first. Callers can then branch on
+    /// whether they need to connect via Tor. Returns `None` if there's no endpoint.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn pj_endpoint(&self) -> Option<Result<PjEndpoint, PjEndpointError>> {
+        self.pj_endpoint_with_policy(&EndpointPolicy::new())
+    }
+
+    /// Returns the endpoint validated according to `policy` classified as clearnet or onion.
+    ///
+    /// Same as [`pj_endpoint`](Self::pj_endpoint) but the endpoint is checked by
+    /// [`validate_endpoint_with_policy`] instead.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn pj_endpoint_with_policy(&self, policy: &EndpointPolicy) -> Option<Result<PjEndpoint, PjEndpointError>> {
+        self.endpoint.as_deref().map(|endpoint| PjEndpoint::parse(endpoint, policy))
+    }
+}
+
+/// Validated payjoin endpoint.
+///
+/// Returned by [`PayjoinExtras::pj_endpoint`].
+#[cfg(feature = "url")]
+#[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PjEndpoint {
+    /// The endpoint is reachable over clearnet.
+    Clearnet(url::Url),
+    /// The endpoint is a `.onion` service which requires connecting via Tor.
+    Onion(String),
+}
+
+#[cfg(feature = "url")]
+impl PjEndpoint {
+    /// Validates and classifies `endpoint`.
+    fn parse(endpoint: &str, policy: &EndpointPolicy) -> Result<Self, PjEndpointError> {
+        validate_endpoint_with_policy(endpoint, policy)?;
+        if UrlParts::parse_endpoint(endpoint)?.is_onion() {
+            Ok(PjEndpoint::Onion(endpoint.into()))
+        } else {
+            url::Url::parse(endpoint).map(PjEndpoint::Clearnet).map_err(PjEndpointError::InvalidUrl)
+        }
+    }
+}
+
+impl<NetVal: bitcoin::address::NetworkValidation> crate::Uri<'_, NetVal, PayjoinExtras> {
+    /// Returns `true` if the URI offers payjoin.
+    ///
+    /// This checks that the endpoint is present and passes
+    /// [`validate_pj_https_or_onion`](Self::validate_pj_https_or_onion), so plain `http` is only
+    /// accepted for `.onion` services. It doesn't contact the endpoint.
+    pub fn is_payjoin(&self) -> bool {
+        self.extras.endpoint.is_some() && self.validate_pj_https_or_onion().is_ok()
+    }
+
+    /// Checks that the endpoint uses `https` unless it's a `.onion` service.
+    ///
+    /// BIP78 requires the endpoint to be either `https` or `.onion`, which may use plain `http`
+    /// since Tor already encrypts the connection. The endpoint is checked by
+    /// [`validate_endpoint`] first, so e.g. `HTTP://.ONION/` fails with
+    /// [`PjEndpointError::EmptyHost`] and `http://example.com` with
+    /// [`PjEndpointError::InsecureScheme`]. Returns `Ok` if there's no endpoint.
+    pub fn validate_pj_https_or_onion(&self) -> Result<(), PjEndpointError> {
+        let endpoint = match &self.extras.endpoint {
+            Some(endpoint) => endpoint,
+            None => return Ok(()),
+        };
+        validate_endpoint(endpoint)?;
+        let parts = UrlParts::parse_endpoint(endpoint)?;
+        if parts.is_onion() || parts.scheme.eq_ignore_ascii_case("https") {
+            Ok(())
+        } else {
+            Err(PjEndpointError::InsecureScheme)
+        }
+    }
+
+    /// Returns `true` if the endpoint is a clearnet `https` URL.
+    ///
+    /// Like [`is_payjoin`](Self::is_payjoin), this requires the endpoint to pass
+    /// [`validate_pj_https_or_onion`](Self::validate_pj_https_or_onion) and then checks that it
+    /// isn't a `.onion` service.
+    ///
+    /// This is a quick check for UI decisions whether the payjoin needs Tor. `false` covers
+    /// missing endpoint, `.onion` services (even over `https`), plain `http` and invalid
+    /// endpoints alike, use [`pj_endpoint`](PayjoinExtras::pj_endpoint) or
+    /// [`validate_pj_https_or_onion`](Self::validate_pj_https_or_onion) to tell them apart.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn require_https_pj(&self) -> bool {
+        self.validate_pj_https_or_onion().is_ok() && matches!(self.extras.pj_endpoint(), Some(Ok(PjEndpoint::Clearnet(_))))
+    }
+
+    /// Alias of [`require_https_pj`](Self::require_https_pj).
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    pub fn is_https_pj(&self) -> bool {
+        self.require_https_pj()
+    }
+}
+
+const ONION_TLD: &str = ".onion";
+
+impl<'a> UrlParts<'a> {
+    fn parse_endpoint(endpoint: &'a str) -> Result<Self, PjEndpointError> {
+        if let Some(position) = endpoint.find(|c: char| c.is_control()) {
+            return Err(PjEndpointError::ControlCharacter { position });
+        }
+        UrlParts::split(endpoint).ok_or(PjEndpointError::DisallowedScheme)
+    }
+
+    fn is_onion(&self) -> bool {
+        let tld = self.host.len().checked_sub(ONION_TLD.len()).and_then(|start| self.host.get(start..));
+        matches!(tld, Some(tld) if tld.eq_ignore_ascii_case(ONION_TLD))
+    }
+
+    /// Returns `true` if the host is an IPv6 or IPv4 literal.
+    ///
+    /// Like the WHATWG URL parser, any host ending with a numeric label is considered IPv4, so
+    /// that forms such as `0x7f.1` aren't missed.
+    fn is_ip_literal(&self) -> bool {
+        if self.host.starts_with('[') {
+            return true;
+        }
+        let host = self.host.strip_suffix('.').unwrap_or(self.host);
+        let last_label = host.rsplit('.').next().unwrap_or(host);
+        let hex = last_label.strip_prefix("0x").or_else(|| last_label.strip_prefix("0X"));
+        match hex {
+            Some(hex) => hex.bytes().all(|byte| byte.is_ascii_hexdigit()),
+            None => !last_label.is_empty() && last_label.bytes().all(|byte| byte.is_ascii_digit()),
+        }
+    }
+}
+
+/// Additional rules for payjoin endpoints.
+///
+/// All rules are off by default which makes validation equivalent to [`validate_endpoint`].
+/// Used by [`validate_endpoint_with_policy`].
+#[derive(Debug, Default, Clone)]
+pub struct EndpointPolicy {
+    forbid_ip_literal: bool,
+}
+
+impl EndpointPolicy {
+    /// Creates the default (permissive) policy.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Rejects hosts that are IP literals, e.g. `https://192.0.2.1/pj` or `https://[::1]/pj`.
+    ///
+    /// Connecting to a raw IP address skips DNS and onion routing which some privacy-conscious
+    /// deployments want to enforce. Rejected endpoints fail with
+    /// [`PjEndpointError::IpLiteralHost`].
+    pub fn forbid_ip_literal(mut self, value: bool) -> Self {
+        self.forbid_ip_literal = value;
+        self
+    }
+}
+
+/// Checks the payjoin endpoint for common problems.
+///
+/// The rules are checked in this order:
+///
+/// * The endpoint must not contain control characters.
+/// * The scheme must be `http` or `https` (case-insensitive).
+/// * The port, if present, must be a number up to 65535.
+/// * The host must not be empty. For `.onion` hosts the name in front of `.onion` must not be
+///   empty either.
+/// * Hosts other than `.onion` and IP literals must contain a top-level domain.
+///
+/// Note that this doesn't enforce that clearnet endpoints use `https`.
+pub fn validate_endpoint(endpoint: &str) -> Result<(), PjEndpointError> {
+    validate_endpoint_with_policy(endpoint, &EndpointPolicy::new())
+}
+
+/// Checks the payjoin endpoint like [`validate_endpoint`] with additional rules of `policy`.
+///
+/// The rules of the policy are checked after the host is found to be non-empty.
+pub fn validate_endpoint_with_policy(endpoint: &str, policy: &EndpointPolicy) -> Result<(), PjEndpointError> {
+    let parts = UrlParts::parse_endpoint(endpoint)?;
+    if !parts.scheme.eq_ignore_ascii_case("http") && !parts.scheme.eq_ignore_ascii_case("https") {
+        return Err(PjEndpointError::DisallowedScheme);
+    }
+    if !parts.has_valid_port() {
+        return Err(PjEndpointError::InvalidPort);
+    }
+    if parts.is_onion() {
+        if parts.host.len() == ONION_TLD.len() {
+            return Err(PjEndpointError::EmptyHost);
+        }
+        return Ok(());
+    }
+    if parts.host.is_empty() {
+        return Err(PjEndpointError::EmptyHost);
+    }
+    if policy.forbid_ip_literal && parts.is_ip_literal() {
+        return Err(PjEndpointError::IpLiteralHost);
+    }
+    if parts.host.starts_with('[') {
+        return Ok(());
+    }
+    match parts.host.rfind('.') {
+        Some(pos) if pos > 0 && pos + 1 < parts.host.len() => Ok(()),
+        _ => Err(PjEndpointError::MissingTld),
+    }
+}
+
+/// The rule violated by a payjoin endpoint.
+///
+/// Returned by [`validate_endpoint`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PjEndpointError {
+    /// The endpoint contains a control character at given byte position.
+    ControlCharacter {
+        /// Byte position of the character.
+        position: usize,
+    },
+    /// The scheme is not `http` nor `https` or it is missing.
+    DisallowedScheme,
+    /// The port is not a number up to 65535.
+    InvalidPort,
+    /// The host is empty.
+    EmptyHost,
+    /// The host is not `.onion` and doesn't contain a top-level domain.
+    MissingTld,
+    /// The endpoint is not `.onion` and its scheme is not `https`.
+    ///
+    /// Only returned by [`Uri::validate_pj_https_or_onion`](crate::Uri::validate_pj_https_or_onion).
+    InsecureScheme,
+    /// The host is an IP literal.
+    ///
+    /// Only returned if forbidden by [`EndpointPolicy::forbid_ip_literal`].
+    IpLiteralHost,
+    /// The endpoint passes the other rules but it is not a valid URL.
+    #[cfg(feature = "url")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "url")))]
+    InvalidUrl(url::ParseError),
+}
+
+impl fmt::Display for PjEndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PjEndpointError::ControlCharacter { position } => write!(f, "the payjoin endpoint contains control character at {}", position),
+            PjEndpointError::DisallowedScheme => write!(f, "the payjoin endpoint scheme is not http(s)"),
+            PjEndpointError::InvalidPort => write!(f, "the payjoin endpoint port is invalid"),
+            PjEndpointError::EmptyHost => write!(f, "the payjoin endpoint host is empty"),
+            PjEndpointError::MissingTld => write!(f, "the payjoin endpoint host is missing top-level domain"),
+            PjEndpointError::InsecureScheme => write!(f, "the payjoin endpoint is neither https nor onion"),
+            PjEndpointError::IpLiteralHost => write!(f, "the payjoin endpoint host is an IP address"),
+            #[cfg(feature = "url")]
+            PjEndpointError::InvalidUrl(_) => write!(f, "the payjoin endpoint is not a valid URL"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for PjEndpointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "url")]
+            PjEndpointError::InvalidUrl(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl crate::IntoOwnedExtras for PayjoinExtras {
+    type Owned = PayjoinExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl crate::MergeExtras for PayjoinExtras {
+    /// Merges the endpoints, `pjos` is taken from the URI providing the endpoint.
+    fn merge(self, other: &Self) -> Result<Self, crate::MergeConflict> {
+        let pjos_conflict =
+            self.endpoint.is_some() && other.endpoint.is_some() && self.disable_output_substitution != other.disable_output_substitution;
+        let disable_output_substitution = if self.endpoint.is_none() && other.endpoint.is_some() {
+            other.disable_output_substitution
+        } else {
+            self.disable_output_substitution
+        };
+        let endpoint = crate::merge_option("pj", self.endpoint, &other.endpoint, PartialEq::eq)?;
+        if pjos_conflict {
+            return Err(crate::MergeConflict::new("pjos"));
+        }
+        Ok(PayjoinExtras {
+            endpoint,
+            disable_output_substitution,
+            version: crate::merge_option("v", self.version, &other.version, PartialEq::eq)?,
+            poll: crate::merge_option("poll", self.poll, &other.poll, PartialEq::eq)?,
+        })
+    }
+}
+
+impl DeserializeParams<'_> for PayjoinExtras {
+    type DeserializationState = PayjoinState;
+}
+
+impl DeserializationError for PayjoinExtras {
+    type Error = PayjoinError;
+}
+
+/// Deserialization state of [`PayjoinExtras`].
+#[derive(Debug, Default, Clone)]
+pub struct PayjoinState {
+    endpoint: Option<String>,
+    disable_output_substitution: bool,
+    version: Option<String>,
+    poll: Option<u64>,
+}
+
+impl DeserializationState<'_> for PayjoinState {
+    type Value = PayjoinExtras;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        matches!(key, "pj" | "pjos" | "v" | "poll")
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, PayjoinError> {
+        match key {
+            "pj" => {
+                let endpoint = super::decode_utf8(value).map_err(PayjoinError::utf8("pj"))?;
+                // a second endpoint could be appended by an attacker so it's not silently ignored
+                if let Some(previous) = self.endpoint.take().filter(|previous| *previous != endpoint) {
+                    return Err(PayjoinError {
+                        parameter: "pj",
+                        value: endpoint,
+                        kind: PayjoinErrorKind::DuplicateEndpoint(previous),
+                    });
+                }
+                self.endpoint = Some(endpoint);
+                Ok(ParamKind::Known)
+            },
+            "pjos" => {
+                let value = super::decode_utf8(value).map_err(PayjoinError::utf8("pjos"))?;
+                self.disable_output_substitution = match super::parse_bool_param(value.as_bytes()) {
+                    Ok(allowed) => !allowed,
+                    Err(_) => {
+                        return Err(PayjoinError {
+                            parameter: "pjos",
+                            value,
+                            kind: PayjoinErrorKind::InvalidOutputSubstitution,
+                        })
+                    },
+                };
+                Ok(ParamKind::Known)
+            },
+            "v" => {
+                self.version = Some(super::decode_utf8(value).map_err(PayjoinError::utf8("v"))?);
+                Ok(ParamKind::Known)
+            },
+            "poll" => {
+                let value = super::decode_utf8(value).map_err(PayjoinError::utf8("poll"))?;
+                let poll = Some(&*value)
+                    .filter(|value| !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()))
+                    .and_then(|value| value.parse().ok());
+                match poll {
+                    Some(poll) => self.poll = Some(poll),
+                    None => {
+                        return Err(PayjoinError {
+                            parameter: "poll",
+                            value,
+                            kind: PayjoinErrorKind::InvalidPollInterval,
+                        })
+                    },
+                }
+                Ok(ParamKind::Known)
+            },
+            _ => Ok(ParamKind::Unknown),
+        }
+    }
+
+    fn finalize(self) -> Result<PayjoinExtras, PayjoinError> {
+        Ok(PayjoinExtras {
+            endpoint: self.endpoint,
+            disable_output_substitution: self.disable_output_substitution,
+            version: self.version,
+            poll: self.poll,
+        })
+    }
+}
+
+type ParamIter<'a> = core::option::IntoIter<(&'static str, Cow<'a, str>)>;
+
+impl<'a> SerializeParams for &'a PayjoinExtras {
+    type Key = &'static str;
+    type Value = Cow<'a, str>;
+    type Iterator = core::iter::Chain<core::iter::Chain<core::iter::Chain<ParamIter<'a>, ParamIter<'a>>, ParamIter<'a>>, ParamIter<'a>>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        let endpoint = self.endpoint.as_deref().map(|endpoint| ("pj", Cow::Borrowed(endpoint)));
+        let version = self
+            .version
+            .as_deref()
+            .filter(|_| endpoint.is_some())
+            .map(|version| ("v", Cow::Borrowed(version)));
+        let pjos = Some(("pjos", Cow::Borrowed("0"))).filter(|_| endpoint.is_some() && self.disable_output_substitution);
+        let poll = self
+            .poll
+            .filter(|_| endpoint.is_some())
+            .map(|poll| ("poll", Cow::Owned(poll.to_string())));
+        endpoint.into_iter().chain(version).chain(pjos).chain(poll)
+    }
+}
+
+/// Error returned when payjoin parameters are invalid.
+#[derive(Debug, Clone)]
+pub struct PayjoinError {
+    parameter: &'static str,
+    value: String,
+    kind: PayjoinErrorKind,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+enum PayjoinErrorKind {
+    Utf8(core::str::Utf8Error),
+    InvalidOutputSubstitution,
+    InvalidPollInterval,
+    DuplicateEndpoint(String),
+    UnsupportedVersion,
+}
+
+impl PayjoinError {
+    fn utf8(parameter: &'static str) -> impl FnOnce((core::str::Utf8Error, String)) -> Self {
+        move |(error, value)| PayjoinError {
+            parameter,
+            value,
+            kind: PayjoinErrorKind::Utf8(error),
+        }
+    }
+
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        self.parameter
+    }
+
+    /// Returns the decoded value of the invalid parameter.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns `true` if the URI contains multiple `pj` parameters with different values.
+    ///
+    /// [`value`](Self::value) returns the second endpoint in this case. The same endpoint
+    /// repeated is accepted.
+    pub fn is_duplicate_endpoint(&self) -> bool {
+        matches!(self.kind, PayjoinErrorKind::DuplicateEndpoint(_))
+    }
+
+    /// Returns `true` if the version was rejected by [`PayjoinExtras::check_version`].
+    pub fn is_unsupported_version(&self) -> bool {
+        matches!(self.kind, PayjoinErrorKind::UnsupportedVersion)
+    }
+}
+
+impl fmt::Display for PayjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            PayjoinErrorKind::Utf8(_) => write!(f, "the payjoin parameter '{}' is not valid UTF-8", self.parameter),
+            PayjoinErrorKind::InvalidOutputSubstitution => write!(f, "invalid value '{}' of pjos parameter, expected 0 or 1", self.value),
+            PayjoinErrorKind::InvalidPollInterval => {
+                write!(f, "invalid value '{}' of poll parameter, expected whole number of seconds", self.value)
+            },
+            PayjoinErrorKind::DuplicateEndpoint(previous) => {
+                write!(f, "conflicting payjoin endpoints '{}' and '{}'", previous, self.value)
+            },
+            PayjoinErrorKind::UnsupportedVersion => write!(f, "unsupported payjoin version '{}'", self.value),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for PayjoinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            PayjoinErrorKind::Utf8(error) => Some(error),
+            PayjoinErrorKind::InvalidOutputSubstitution => None,
+            PayjoinErrorKind::InvalidPollInterval => None,
+            PayjoinErrorKind::DuplicateEndpoint(_) => None,
+            PayjoinErrorKind::UnsupportedVersion => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PayjoinExtras;
+    use crate::Uri;
+    use alloc::string::ToString;
+
+    #[test]
+    fn pj_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj?v%3D1&pjos=0";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.endpoint.as_deref(), Some("https://example.com/pj?v=1"));
+        assert!(uri.extras.disable_output_substitution);
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn strip_amount_for_reuse() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Alice&message=Rent&pj=https://example.com&pjos=0";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.strip_amount_for_reuse().to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pjos=0"
+        );
+    }
+
+    #[test]
+    fn pjos_defaults_to_allowed() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pjos=1";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert!(!uri.extras.disable_output_substitution);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com");
+    }
+
+    #[test]
+    fn merge_pj() {
+        let base = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Shop"
+            .parse::<Uri<'_, _, PayjoinExtras>>()
+            .unwrap();
+        let server = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pjos=0"
+            .parse::<Uri<'_, _, PayjoinExtras>>()
+            .unwrap();
+        let merged = base.clone().merge_params(&server).unwrap().assume_checked();
+        assert_eq!(
+            merged.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Shop&pj=https://example.com&pjos=0"
+        );
+
+        let other = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.org"
+            .parse::<Uri<'_, _, PayjoinExtras>>()
+            .unwrap();
+        assert_eq!(server.clone().merge_params(&other).unwrap_err().key(), "pj");
+        let allowed = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com"
+            .parse::<Uri<'_, _, PayjoinExtras>>()
+            .unwrap();
+        assert_eq!(server.merge_params(&allowed).unwrap_err().key(), "pjos");
+    }
+
+    #[test]
+    fn is_payjoin() {
+        let parse = |input: &str| input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert!(parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com").is_payjoin());
+        assert!(!parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=ftp://example.com").is_payjoin());
+        assert!(!parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=http://example.com").is_payjoin());
+        assert!(parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=http://example.onion").is_payjoin());
+        assert!(!parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1").is_payjoin());
+    }
+
+    #[test]
+    fn unknown_version_preserved() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&v=99&pjos=0";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.payjoin_version(), Some(99));
+        assert_eq!(uri.to_string(), input);
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&v=2b";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.payjoin_version(), None);
+        assert_eq!(uri.extras.version.as_deref(), Some("2b"));
+        assert_eq!(uri.to_string(), input);
+        let error = uri.extras.check_version(&[1, 2]).unwrap_err();
+        assert!(error.is_unsupported_version());
+        assert_eq!(error.parameter(), "v");
+        assert_eq!(error.value(), "2b");
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&v=99";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert!(uri.extras.check_version(&[1, 2]).unwrap_err().is_unsupported_version());
+        assert!(uri.extras.check_version(&[99]).is_ok());
+        assert!(PayjoinExtras::default().check_version(&[]).is_ok());
+
+        // like pjos, the version is meaningless without the endpoint
+        let mut uri = uri.assume_checked();
+        uri.extras.endpoint = None;
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd");
+    }
+
+    #[test]
+    fn duplicate_endpoint() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&amount=1&pj=https://evil.example";
+        match input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => {
+                assert!(error.is_duplicate_endpoint());
+                assert_eq!(error.value(), "https://evil.example");
+                assert_eq!(
+                    error.to_string(),
+                    "conflicting payjoin endpoints 'https://example.com' and 'https://evil.example'"
+                );
+            },
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pj=https%3A%2F%2Fexample.com";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert_eq!(uri.extras.endpoint.as_deref(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn invalid_pjos() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&pjos=yes";
+        match input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap_err() {
+            crate::de::Error::Extras(error) => {
+                assert_eq!(error.parameter(), "pjos");
+                assert_eq!(error.value(), "yes");
+            },
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn poll_interval() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&v=2&poll=30";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.poll_interval(), Some(core::time::Duration::from_secs(30)));
+        assert_eq!(uri.to_string(), input);
+
+        // like v and pjos, the interval is meaningless without the endpoint
+        let mut without_endpoint = uri.clone();
+        without_endpoint.extras.endpoint = None;
+        assert_eq!(without_endpoint.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd");
+
+        for poll in ["", "1.5", "-1", "30s", "18446744073709551616"] {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com&poll={}", poll);
+            match input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap_err() {
+                crate::de::Error::Extras(error) => {
+                    assert_eq!(error.parameter(), "poll");
+                    assert_eq!(error.value(), poll);
+                },
+                crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+            }
+        }
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn pj_url() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com/pj";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        let url = uri.extras.pj_url().unwrap().unwrap();
+        assert_eq!(url.host_str(), Some("example.com"));
+        assert_eq!(url.path(), "/pj");
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn pj_endpoint() {
+        use super::{PjEndpoint, PjEndpointError};
+
+        let endpoint = |pj: &str| PayjoinExtras::new(pj.into()).pj_endpoint().unwrap();
+        match endpoint("https://example.com/pj").unwrap() {
+            PjEndpoint::Clearnet(url) => assert_eq!(url.host_str(), Some("example.com")),
+            other => panic!("unexpected endpoint: {:?}", other),
+        }
+        let onion = "http://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion/pj";
+        assert_eq!(endpoint(onion), Ok(PjEndpoint::Onion(onion.into())));
+        assert_eq!(endpoint("ftp://example.com"), Err(PjEndpointError::DisallowedScheme));
+        assert_eq!(PayjoinExtras::default().pj_endpoint(), None);
+    }
+
+    #[test]
+    fn endpoint_validation() {
+        use super::{validate_endpoint, PjEndpointError};
+
+        assert_eq!(validate_endpoint("https://example.com/pj"), Ok(()));
+        assert_eq!(validate_endpoint("HTTPS://user@example.com:8080"), Ok(()));
+        assert_eq!(
+            validate_endpoint("http://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion/pj"),
+            Ok(())
+        );
+        assert_eq!(validate_endpoint("https://[::1]:8080/pj"), Ok(()));
+        assert_eq!(validate_endpoint("HTTP://.ONION/////q3"), Err(PjEndpointError::EmptyHost));
+        assert_eq!(validate_endpoint("https:///pj"), Err(PjEndpointError::EmptyHost));
+        assert_eq!(validate_endpoint("https://localhost/pj"), Err(PjEndpointError::MissingTld));
+        assert_eq!(validate_endpoint("https://ö/pj"), Err(PjEndpointError::MissingTld));
+        assert_eq!(validate_endpoint("https://example./pj"), Err(PjEndpointError::MissingTld));
+        assert_eq!(validate_endpoint("https://example.com:99999/pj"), Err(PjEndpointError::InvalidPort));
+        assert_eq!(validate_endpoint("https://[::1]:x/pj"), Err(PjEndpointError::InvalidPort));
+        assert_eq!(validate_endpoint("ftp://example.com"), Err(PjEndpointError::DisallowedScheme));
+        assert_eq!(validate_endpoint("example.com"), Err(PjEndpointError::DisallowedScheme));
+        assert_eq!(
+            validate_endpoint("https://exa\u{7}mple.com"),
+            Err(PjEndpointError::ControlCharacter { position: 11 })
+        );
+    }
+
+    #[test]
+    fn ip_literal_policy() {
+        use super::{validate_endpoint, validate_endpoint_with_policy, EndpointPolicy, PjEndpointError};
+
+        let strict = EndpointPolicy::new().forbid_ip_literal(true);
+        for endpoint in [
+            "https://192.0.2.1/pj",
+            "https://user@192.0.2.1:8080",
+            "https://[::1]:8080/pj",
+            "https://0x7f.1/pj",
+            "https://10.1/pj",
+        ] {
+            assert_eq!(validate_endpoint(endpoint), Ok(()), "{}", endpoint);
+            assert_eq!(
+                validate_endpoint_with_policy(endpoint, &strict),
+                Err(PjEndpointError::IpLiteralHost),
+                "{}",
+                endpoint
+            );
+        }
+        for endpoint in [
+            "https://example.com/pj",
+            "https://1.example.com/pj",
+            "http://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion/pj",
+        ] {
+            assert_eq!(validate_endpoint_with_policy(endpoint, &strict), Ok(()), "{}", endpoint);
+        }
+        let extras = PayjoinExtras::new("https://192.0.2.1/pj".into());
+        assert_eq!(extras.validate_endpoint_with_policy(&EndpointPolicy::new()), Ok(()));
+        assert_eq!(extras.validate_endpoint_with_policy(&strict), Err(PjEndpointError::IpLiteralHost));
+        assert_eq!(PayjoinExtras::default().validate_endpoint_with_policy(&strict), Ok(()));
+        #[cfg(feature = "url")]
+        assert_eq!(extras.pj_endpoint_with_policy(&strict), Some(Err(PjEndpointError::IpLiteralHost)));
+    }
+
+    #[test]
+    fn https_or_onion() {
+        use super::PjEndpointError;
+
+        let validate = |pj: &str| {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj={}", pj);
+            input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().validate_pj_https_or_onion()
+        };
+        assert_eq!(validate("https://example.com/pj"), Ok(()));
+        assert_eq!(validate("HTTPS://example.com/pj"), Ok(()));
+        assert_eq!(
+            validate("http://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion/pj"),
+            Ok(())
+        );
+        assert_eq!(validate("http://example.com/pj"), Err(PjEndpointError::InsecureScheme));
+        assert_eq!(validate("HTTP://.ONION/////q3"), Err(PjEndpointError::EmptyHost));
+        assert_eq!(validate("ftp://example.com"), Err(PjEndpointError::DisallowedScheme));
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert_eq!(uri.validate_pj_https_or_onion(), Ok(()));
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn https_pj() {
+        let is_https = |pj: &str| {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj={}", pj);
+            input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap().require_https_pj()
+        };
+        assert!(is_https("https://example.com/pj"));
+        assert!(is_https("HTTPS://example.com:8443/pj"));
+        assert!(!is_https("http://example.com/pj"));
+        assert!(!is_https("https://2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion/pj"));
+        assert!(!is_https("https:///pj"));
+        assert!(!is_https("example.com/pj"));
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert!(!uri.require_https_pj());
+        assert!(!uri.is_https_pj());
+    }
+
+    #[test]
+    fn lenient_parsing_exposes_endpoint_error() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=HTTP://.ONION/////q3";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert_eq!(uri.extras.validate_endpoint(), Err(super::PjEndpointError::EmptyHost));
+    }
+
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn endpoint_ct_eq() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com/pj";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert!(uri.extras.endpoint_ct_eq("https://example.com/pj"));
+        assert!(!uri.extras.endpoint_ct_eq("https://example.com/pk"));
+        assert!(!PayjoinExtras::default().endpoint_ct_eq(""));
+    }
+
+    #[test]
+    fn no_pj() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        let uri = input.parse::<Uri<'_, _, PayjoinExtras>>().unwrap();
+        assert!(uri.extras.endpoint.is_none());
+        assert_eq!(uri.extras.validate_endpoint(), Ok(()));
+    }
+}
@@ -0,0 +1,435 @@
+//! Collection of parameters not recognized by other extras.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+use crate::ser::{validate_key, InvalidKeyError, SerializeParams};
+use crate::Param;
+
+/// All (optional) parameters of the URI other than `amount`, `label` and `message`.
+///
+/// This is an ordered multimap - parameters are kept in the order of appearance and the
+/// repeated keys are all retained, so displaying the URI reproduces all of them. Unknown `req-`
+/// parameters are still rejected as mandated by BIP21.
+///
+/// Deserialization is zero-copy so this has to be parsed using `TryFrom<&str>` rather than
+/// `FromStr`. Use [`Uri::into_owned`](crate::Uri::into_owned) to get rid of the lifetime.
+///
+/// Two collections are equal if they contain the same keys with the same decoded values in the
+/// same order, the percent-encoding of the values doesn't matter.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct UnknownParams<'a> {
+    params: Vec<(Cow<'a, str>, Param<'a>)>,
+}
+
+impl<'a> UnknownParams<'a> {
+    /// Creates an empty collection.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a parameter.
+    pub fn push<K: Into<Cow<'a, str>>, V: Into<Param<'a>>>(&mut self, key: K, value: V) {
+        self.params.push((key.into(), value.into()));
+    }
+
+    /// Appends a parameter after checking that its key is valid, for chaining.
+    ///
+    /// Unlike [`push`](Self::push), this rejects keys that would corrupt the URI when displayed.
+    /// See [`validate_key`] for the rules.
+    pub fn param<K: Into<Cow<'a, str>>, V: Into<Param<'a>>>(mut self, key: K, value: V) -> Result<Self, InvalidKeyError> {
+        let key = key.into();
+        validate_key(&key)?;
+        self.params.push((key, value.into()));
+        Ok(self)
+    }
+
+    /// Appends a required parameter, for chaining.
+    ///
+    /// The key is `name` prefixed with `req-` so consumers that don't understand the parameter
+    /// must reject the URI as mandated by BIP21. The whole key is checked like in
+    /// [`param`](Self::param) and errors refer to it including the prefix. Note that parsing
+    /// into `UnknownParams` rejects such URIs since it doesn't understand any parameter.
+    pub fn required_param<V: Into<Param<'a>>>(self, name: &str, value: V) -> Result<Self, InvalidKeyError> {
+        self.param(alloc::format!("req-{}", name), value)
+    }
+
+    /// Returns the value of the first parameter with given key.
+    pub fn get(&self, key: &str) -> Option<&Param<'a>> {
+        self.params.iter().find(|(param_key, _)| param_key == key).map(|(_, value)| value)
+    }
+
+    /// Returns the values of all parameters with given key in order of appearance.
+    pub fn get_all<'s>(&'s self, key: &'s str) -> impl Iterator<Item = &'s Param<'a>> + 's {
+        self.params.iter().filter(move |(param_key, _)| param_key == key).map(|(_, value)| value)
+    }
+
+    /// Returns the iterator over all keys and values in order of appearance.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Param<'a>)> {
+        self.params.iter().map(|(key, value)| (&**key, value))
+    }
+
+    /// Retains only the parameters for which `f` returns `true`, keeping their order.
+    pub fn retain<F: FnMut(&str, &Param<'a>) -> bool>(&mut self, mut f: F) {
+        self.params.retain(|(key, value)| f(key, value));
+    }
+
+    /// Deserializes the parameters as extras `U`.
+    ///
+    /// This allows parsing the URI loosely first and interpreting the parameters later, e.g.
+    /// once it's known which extensions the application needs. The parameters are passed to `U`
+    /// in order, the same way parsing does, so unrecognized `req-` parameters are rejected and
+    /// the remaining unrecognized ones are ignored.
+    pub fn parse_as<U: DeserializeParams<'a>>(&self) -> Result<U, crate::de::Error<U::Error>> {
+        let mut state = U::DeserializationState::default();
+        for (key, value) in &self.params {
+            let normalized_key = state.normalize_key(key);
+            let kind = state.deserialize_temp(normalized_key, value.clone()).map_err(crate::de::Error::Extras)?;
+            if kind == ParamKind::Unknown && key.starts_with("req-") {
+                return Err(crate::de::Error::Uri(crate::de::UriError::unknown_required_parameter(key)));
+            }
+        }
+        state.finalize().map_err(crate::de::Error::Extras)
+    }
+
+    /// Returns the number of parameters, including the repeated ones.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns `true` if there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Collects the parameters in order without validating the keys.
+///
+/// Use [`Uri::from_parts`](crate::Uri::from_parts) to validate them when assembling the URI or
+/// [`UnknownParams::param`] to validate each key when adding it.
+impl<'a, K: Into<Cow<'a, str>>, V: Into<Param<'a>>> core::iter::FromIterator<(K, V)> for UnknownParams<'a> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut params = UnknownParams::new();
+        params.extend(iter);
+        params
+    }
+}
+
+/// Appends the parameters in order without validating the keys, like [`UnknownParams::push`].
+impl<'a, K: Into<Cow<'a, str>>, V: Into<Param<'a>>> Extend<(K, V)> for UnknownParams<'a> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.params.extend(iter.into_iter().map(|(key, value)| (key.into(), value.into())));
+    }
+}
+
+impl<'a, NetVal: bitcoin::address::NetworkValidation> crate::Uri<'a, NetVal, UnknownParams<'a>> {
+    /// Returns the URI with a parameter appended to extras.
+    ///
+    /// The key is checked by [`validate_key`] and it must not be `amount`, `label` or `message`,
+    /// which have dedicated fields. Like all methods modifying the URI, this forgets the string
+    /// it was parsed from.
+    pub fn with_param<K: Into<Cow<'a, str>>, V: Into<Param<'a>>>(self, key: K, value: V) -> Result<Self, InvalidKeyError> {
+        let key = key.into();
+        if matches!(&*key, "amount" | "label" | "message") {
+            return Err(InvalidKeyError::reserved(&key));
+        }
+        let extras = self.extras.param(key, value)?;
+        Ok(crate::Uri {
+            extras,
+            original: None,
+            ..self
+        })
+    }
+}
+
+impl<'a, NetVal: bitcoin::address::NetworkValidation> crate::Uri<'a, NetVal, UnknownParams<'a>> {
+    /// Returns the URI with all extras removed, keeping only `amount`, `label` and `message`.
+    ///
+    /// This is useful for services forwarding URIs which must not propagate data they don't
+    /// understand. URIs with other extras than `UnknownParams` don't need this since they only
+    /// retain recognized parameters. Use [`UnknownParams::retain`] to keep some of them.
+    pub fn strip_unknown_params(self) -> Self {
+        crate::Uri {
+            extras: UnknownParams::new(),
+            original: None,
+            ..self
+        }
+    }
+}
+
+impl<'a, NetVal: bitcoin::address::NetworkValidation + Clone> crate::Uri<'a, NetVal, UnknownParams<'a>> {
+    /// Returns a copy of the URI with the unknown parameters deserialized as extras `U`.
+    ///
+    /// See [`UnknownParams::parse_as`] for details.
+    pub fn clone_extras_as<U: DeserializeParams<'a>>(&self) -> Result<crate::Uri<'a, NetVal, U>, crate::de::Error<U::Error>> {
+        let extras = self.extras.parse_as()?;
+        Ok(self.clone().map_extras(|_| extras))
+    }
+}
+
+impl crate::IntoOwnedExtras for UnknownParams<'_> {
+    type Owned = UnknownParams<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        let params = self
+            .params
+            .into_iter()
+            .map(|(key, value)| (Cow::Owned(key.into_owned()), value.decode_into_owned()))
+            .collect();
+        UnknownParams { params }
+    }
+}
+
+impl<'a> DeserializeParams<'a> for UnknownParams<'a> {
+    type DeserializationState = UnknownParamsState<'a>;
+}
+
+impl DeserializationError for UnknownParams<'_> {
+    type Error = core::convert::Infallible;
+}
+
+/// Deserialization state of [`UnknownParams`].
+#[derive(Debug, Default, Clone)]
+pub struct UnknownParamsState<'a> {
+    params: UnknownParams<'a>,
+}
+
+impl<'a> DeserializationState<'a> for UnknownParamsState<'a> {
+    type Value = UnknownParams<'a>;
+
+    fn is_param_known(&self, key: &str) -> bool {
+        !key.starts_with("req-")
+    }
+
+    fn deserialize_temp(&mut self, key: &str, value: Param<'_>) -> Result<ParamKind, core::convert::Infallible> {
+        if !self.is_param_known(key) {
+            return Ok(ParamKind::Unknown);
+        }
+        self.params.push(String::from(key), value.decode_into_owned());
+        Ok(ParamKind::Known)
+    }
+
+    fn deserialize_borrowed(&mut self, key: &'a str, value: Param<'a>) -> Result<ParamKind, core::convert::Infallible> {
+        if !self.is_param_known(key) {
+            return Ok(ParamKind::Unknown);
+        }
+        self.params.push(key, value);
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<UnknownParams<'a>, core::convert::Infallible> {
+        Ok(self.params)
+    }
+}
+
+type ParamsIter<'a, 'b> =
+    core::iter::Map<core::slice::Iter<'a, (Cow<'b, str>, Param<'b>)>, fn(&'a (Cow<'b, str>, Param<'b>)) -> (&'a str, &'a Param<'b>)>;
+
+/// Values are serialized as bytes so even those that are not valid UTF-8 roundtrip.
+impl<'a, 'b> SerializeParams for &'a UnknownParams<'b> {
+    type Key = &'a str;
+    type Value = &'a Param<'b>;
+    type Iterator = ParamsIter<'a, 'b>;
+
+    fn serialize_params(self) -> Self::Iterator {
+        self.params.iter().map(|(key, value)| (&**key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnknownParams;
+    use crate::{IntoOwnedExtras, Uri};
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn equality_ignores_encoding() {
+        let encoded = Uri::<'_, _, UnknownParams<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=a%2Db").unwrap();
+        let plain = Uri::<'_, _, UnknownParams<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=a-b").unwrap();
+        assert_eq!(encoded.extras, plain.extras);
+        assert_eq!(encoded.extras, UnknownParams::new().param("foo", "a-b").unwrap());
+        let other = Uri::<'_, _, UnknownParams<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=a%2Dc").unwrap();
+        assert_ne!(other.extras, plain.extras);
+        assert_eq!(encoded.assume_checked(), plain.assume_checked());
+    }
+
+    #[test]
+    fn clone_extras_as() {
+        use crate::extras::PayjoinExtras;
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj&pjos=0&foo=bar";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap();
+        let payjoin = uri.clone_extras_as::<PayjoinExtras>().unwrap().assume_checked();
+        assert_eq!(payjoin.extras.endpoint.as_deref(), Some("https://example.com/pj"));
+        assert!(payjoin.extras.disable_output_substitution);
+        assert_eq!(payjoin.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(
+            payjoin.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&pj=https://example.com/pj&pjos=0"
+        );
+
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pjos=2").unwrap();
+        match uri.clone_extras_as::<PayjoinExtras>().unwrap_err() {
+            crate::de::Error::Extras(error) => assert_eq!(error.parameter(), "pjos"),
+            crate::de::Error::Uri(error) => panic!("unexpected error: {}", error),
+        }
+
+        let params = UnknownParams::new().required_param("foo", "bar").unwrap();
+        match params.parse_as::<PayjoinExtras>().unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.to_string(), "the URI contains unknown required parameter 'req-foo'"),
+            crate::de::Error::Extras(error) => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn repeated_keys_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&foo=1&bar=x%26y&foo=2";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.extras.len(), 3);
+        let foos = uri
+            .extras
+            .get_all("foo")
+            .map(|value| alloc::string::String::try_from(value.clone()).unwrap())
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(foos, ["1", "2"]);
+        assert_eq!(uri.to_string(), input);
+        assert_eq!(uri.into_owned().to_string(), input);
+    }
+
+    #[test]
+    fn binary_value_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?blob=%FF%00%41x";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.extras.get("blob").unwrap().decoded_bytes(), &[0xFF, 0x00, 0x41, b'x'][..]);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?blob=%FF%00Ax");
+        assert_eq!(uri.into_owned().to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?blob=%FF%00Ax");
+    }
+
+    #[test]
+    fn checked_param() {
+        let params = UnknownParams::new().param("foo", "a&b=c").unwrap().param("x-y.z~", "1").unwrap();
+        let uri = Uri::with_extras(crate::tests::test_address(), params);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=a%26b%3Dc&x-y.z~=1");
+
+        for (key, position) in [
+            ("a=b", Some(1)),
+            ("a&b", Some(1)),
+            ("a b", Some(1)),
+            ("a\u{7}", Some(1)),
+            ("#", Some(0)),
+            ("%4", Some(0)),
+            ("", None),
+        ] {
+            let error = UnknownParams::new().param(key, "value").unwrap_err();
+            assert_eq!(error.key(), key);
+            assert_eq!(error.position(), position, "{}", key);
+        }
+        assert_eq!(
+            UnknownParams::new().param("a=b", "").unwrap_err().to_string(),
+            "the key 'a=b' contains invalid character '=' at 1"
+        );
+    }
+
+    #[test]
+    fn required_param() {
+        let params = UnknownParams::new().param("baz", "1").unwrap().required_param("foo", "bar").unwrap();
+        assert_eq!(
+            params.get("req-foo").map(|value| value.decoded_bytes().into_owned()),
+            Some(b"bar".to_vec())
+        );
+        let uri = Uri::with_extras(crate::tests::test_address(), params);
+        let string = uri.to_string();
+        assert_eq!(string, "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?baz=1&req-foo=bar");
+        assert!(Uri::<'_, _, UnknownParams<'_>>::try_from(&*string).is_err());
+
+        let error = UnknownParams::new().required_param("a b", "").unwrap_err();
+        assert_eq!(error.key(), "req-a b");
+        assert_eq!(error.position(), Some(5));
+    }
+
+    #[test]
+    fn with_param() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&foo=1";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap();
+        let uri = uri.with_param("order-id", "42 & more").unwrap().with_param("foo", "2").unwrap();
+        assert_eq!(uri.original(), None);
+        assert_eq!(
+            uri.clone().assume_checked().to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&foo=1&order-id=42%20%26%20more&foo=2"
+        );
+        assert_eq!(uri.clone().with_param("a b", "").unwrap_err().position(), Some(1));
+        let error = uri.with_param("label", "x").unwrap_err();
+        assert_eq!(error.to_string(), "the key 'label' is reserved for a standard parameter");
+    }
+
+    #[test]
+    fn strip_unknown_params() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&foo=1&label=x&bar=2";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let mut retained = uri.clone();
+        retained.extras.retain(|key, _| key == "bar");
+        assert_eq!(retained.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=x&bar=2");
+        let stripped = uri.strip_unknown_params();
+        assert!(stripped.extras.is_empty());
+        assert_eq!(stripped.original(), None);
+        assert_eq!(stripped.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=x");
+    }
+
+    #[test]
+    fn reserved_characters_in_keys_encoded() {
+        let mut params = UnknownParams::new();
+        params.push("a&b", "1");
+        params.push("with space", "2");
+        params.push("tab\t#", "3");
+        params.push("pct%41", "4");
+        let uri = Uri::with_extras(crate::tests::test_address(), params);
+        let expected = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?a%26b=1&with%20space=2&tab%09%23=3&pct%41=4";
+        assert_eq!(uri.to_string(), expected);
+        assert_eq!(
+            uri.to_canonical_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?a%26b=1&pct%41=4&tab%09%23=3&with%20space=2"
+        );
+        let reparsed = Uri::<'_, _, UnknownParams<'_>>::try_from(expected).unwrap();
+        assert_eq!(reparsed.extras.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "contains equal sign")]
+    fn equal_sign_in_key_panics() {
+        let mut params = UnknownParams::new();
+        params.push("a=b", "1");
+        let _ = Uri::with_extras(crate::tests::test_address(), params).to_string();
+    }
+
+    #[test]
+    fn collect() {
+        let mut params = alloc::vec![("foo", "1"), ("bar", "x&y"), ("foo", "2")]
+            .into_iter()
+            .collect::<UnknownParams<'_>>();
+        params.extend(Some(("baz", "")));
+        assert_eq!(params.len(), 4);
+        let uri = Uri::from_parts(crate::tests::test_address(), None, None, None, params).unwrap();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1&bar=x%26y&foo=2&baz=");
+
+        let params = Some(("a=b", "1")).into_iter().collect::<UnknownParams<'_>>();
+        assert!(Uri::from_parts(crate::tests::test_address(), None, None, None, params).is_err());
+    }
+
+    #[test]
+    fn unknown_required_rejected() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1";
+        assert!(Uri::<'_, _, UnknownParams<'_>>::try_from(input).is_err());
+    }
+
+    #[test]
+    fn into_owned() {
+        let params = {
+            let input = alloc::string::String::from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=%41");
+            let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(&*input).unwrap();
+            uri.extras.into_owned()
+        };
+        assert_eq!(params.iter().next().map(|(key, _)| key), Some("foo"));
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! * Rust-idiomatic: uses strong types, standard traits and other things
 //! * Compliant: implements all requirements of BIP21, including protections to not forget about
-//!              `req-`. (But see features.)
+//!   `req-`. (But see features.)
 //! * Flexible: enables parsing/serializing additional arguments not defined by BIP21
 //! * Performant: uses zero-copy deserialization and lazy evaluation wherever possible.
 //!
@@ -21,6 +21,15 @@
 //!
 //! * `std` enables integration with `std` - mainly `std::error::Error`.
 //! * `non-compliant-bytes` - enables use of non-compliant API that can parse non-UTF-8 URI values.
+//! * `url` - enables accessors returning endpoints of [`extras`] as `url::Url`.
+//! * `subtle` - enables comparisons of secret-bearing values that don't short-circuit.
+//! * `rust_decimal` - enables reading the amount as `rust_decimal::Decimal`.
+//! * `arbitrary` - implements `arbitrary::Arbitrary` for [`Uri`] and [`Param`] for structured fuzzing, requires `std`.
+//! * `base64` - enables encoding the whole URI as base64url for embedding in other transports.
+//! * `lightning` - enables constructing unified URIs from on-chain fallbacks of BOLT11 invoices.
+//! * `compact` - enables the compact binary form of URIs for caching.
+//! * `encode-set` - enables percent-encoding additional characters when displaying URIs.
+//! * `qr` - enables estimating the sizes of QR codes needed to encode URIs.
 //!
 //! ## Stabilization roadmap
 //!
@@ -41,8 +50,18 @@ extern crate std;
 
 extern crate alloc;
 
+#[cfg(feature = "compact")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compact")))]
+pub mod compact;
 pub mod de;
+pub mod diff;
+pub mod extras;
+#[cfg(feature = "qr")]
+#[cfg_attr(docsrs, doc(cfg(feature = "qr")))]
+pub mod qr;
+pub mod raw;
 pub mod ser;
+pub mod spec;
 
 use alloc::borrow::ToOwned;
 use alloc::borrow::Cow;
@@ -53,10 +72,12 @@ use percent_encoding_rfc3986::{PercentDecode, PercentDecodeError};
 #[cfg(feature = "non-compliant-bytes")]
 use either::Either;
 use core::convert::{TryFrom, TryInto};
+use core::fmt;
 use bitcoin::address::NetworkValidation;
 
-pub use de::{DeserializeParams, DeserializationState, DeserializationError};
+pub use de::{DeserializeParams, DeserializationState, DeserializationError, ParseOptions};
 pub use ser::SerializeParams;
+pub use raw::RawUri;
 
 /// Parsed BIP21 URI.
 ///
@@ -68,11 +89,35 @@ pub use ser::SerializeParams;
 /// `Uri` implements `FromStr` so you can simply use `s.parse::<Uri<'static>>()`. However that is
 /// not zero-copy. If you wish to use zero-copy parsing call `try_into()` instead.
 ///
+/// ## Ownership
+///
+/// A `Uri` parsed using zero-copy parsing borrows the input and so does its clone. To obtain a
+/// `Uri<'static, _, _>` which doesn't borrow anything use [`into_owned`](Self::into_owned) or
+/// [`clone_into_owned_extras`](Self::clone_into_owned_extras). These decode `label` and
+/// `message` and convert `extras` using [`IntoOwnedExtras`] so they work even if the extras
+/// borrow from the input too.
+///
+/// `Uri` is `Clone` whenever its extras are, which is the case for [`NoExtras`] and all types in
+/// [`extras`]. Cloning never fails so there's no fallible alternative - extras holding resources
+/// that can't be duplicated should be wrapped in e.g. `Arc` instead.
+///
+/// ## Comparing
+///
+/// Network-checked URIs implement `Eq` and `Ord` comparing the decoded fields, so URIs differing
+/// only in formatting (casing of the scheme, percent-encoding, parameter order...) are equal,
+/// exactly when their [`to_canonical_string`](Self::to_canonical_string) is. The ordering is by
+/// address, then amount, label, message and the sorted parameters of extras, **not** by the
+/// payment value - it's only meant to allow storing URIs in ordered collections and producing
+/// stable sorted lists.
+///
 /// ## Displaying
 ///
 /// `Display` is implemented for `Uri` so you can format it naturally. However it currently does
 /// **not** support alignment.
 ///
+/// The amount is always displayed in BTC as mandated by BIP21, in its shortest form - without
+/// trailing zeros and without the decimal point for whole bitcoins (e.g. `amount=1`).
+///
 /// The code does _not_ assume strict BIP-21, so it displays the schema lower case
 /// instead as upper case.
 /// This makes it compatible with some (buggy) wallets but does not create the most optimal QR codes.
@@ -100,6 +145,12 @@ where
 
     /// Extra fields that can occur in a BIP21 URI.
     pub extras: Extras,
+
+    /// The string this URI was parsed from, if parsed using zero-copy parsing.
+    original: Option<de::Original<'a>>,
+
+    /// Whether any parameter of the parsed input was percent-encoded.
+    had_encoded_params: bool,
 }
 
 impl<NetVal: NetworkValidation, T: Default> Uri<'_, NetVal, T> {
@@ -114,6 +165,8 @@ impl<NetVal: NetworkValidation, T: Default> Uri<'_, NetVal, T> {
             label: None,
             message: None,
             extras: Default::default(),
+            original: None,
+            had_encoded_params: false,
         }
     }
 }
@@ -130,8 +183,623 @@ impl<NetVal: NetworkValidation, T> Uri<'_, NetVal, T> {
             label: None,
             message: None,
             extras,
+            original: None,
+            had_encoded_params: false,
+        }
+    }
+}
+
+impl<NetVal: NetworkValidation, T> Uri<'_, NetVal, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Returns `true` if the URI contains parameter with given key.
+    ///
+    /// This checks both standard parameters (`amount`, `label`, `message`) and the keys serialized
+    /// by `extras`, so it answers the question whether the parameter would be present in the
+    /// displayed URI.
+    pub fn contains_param(&self, key: &str) -> bool {
+        match key {
+            "amount" => self.amount.is_some(),
+            "label" => self.label.is_some(),
+            "message" => self.message.is_some(),
+            _ => self.extras.serialize_params().any(|(extra_key, _)| ser::display_eq(extra_key, key)),
+        }
+    }
+
+    /// Returns the decoded value of the first parameter with given key.
+    ///
+    /// Just like [`contains_param`](Self::contains_param) this covers both standard parameters
+    /// and extras uniformly. The amount is returned in BTC as displayed. Complements the typed
+    /// accessors for code handling arbitrary keys.
+    pub fn param(&self, key: &str) -> Option<Cow<'_, [u8]>> {
+        use ser::SerializeValue;
+
+        match key {
+            "amount" => self
+                .amount
+                .map(|amount| Cow::Owned(amount.display_in(bitcoin::Denomination::Bitcoin).to_decoded_bytes().into_owned())),
+            "label" => self.label.as_ref().map(Param::decoded_bytes),
+            "message" => self.message.as_ref().map(Param::decoded_bytes),
+            _ => self
+                .extras
+                .serialize_params()
+                .find(|(extra_key, _)| ser::display_eq(extra_key, key))
+                .map(|(_, value)| Cow::Owned(value.to_decoded_bytes().into_owned())),
+        }
+    }
+
+    /// Returns the decoded value of the first parameter with given key as UTF-8 string.
+    ///
+    /// Returns `Some(Err(_))` if the parameter is present but its value is not valid UTF-8.
+    pub fn param_str(&self, key: &str) -> Option<Result<Cow<'_, str>, core::str::Utf8Error>> {
+        self.param(key).map(|value| match value {
+            Cow::Borrowed(bytes) => core::str::from_utf8(bytes).map(Cow::Borrowed),
+            Cow::Owned(bytes) => String::from_utf8(bytes).map(Cow::Owned).map_err(|error| error.utf8_error()),
+        })
+    }
+
+    /// Returns the summary of the payment requested by the URI.
+    ///
+    /// This is a flat owned view of the fields usually shown to the user. The label and the
+    /// message are decoded lossily, replacing invalid UTF-8 with `U+FFFD`. `payjoin` is `true` if
+    /// `extras` serialize a `pj` or `req-pj` parameter, the endpoint is not validated.
+    pub fn intent(&self) -> PaymentIntent<NetVal>
+    where
+        NetVal: Clone,
+    {
+        let lossy = |param: &Param<'_>| String::from_utf8_lossy(&param.decoded_bytes()).into_owned();
+        PaymentIntent {
+            address: self.address.clone(),
+            amount: self.amount,
+            label: self.label.as_ref().map(lossy),
+            message: self.message.as_ref().map(lossy),
+            payjoin: self.contains_param("pj") || self.contains_param("req-pj"),
+        }
+    }
+
+    /// Returns the required (`req-`) parameters of the URI.
+    ///
+    /// A parsed URI can only contain required parameters understood by `extras` since the
+    /// others are rejected during parsing, so this yields those serialized by `extras`. Use
+    /// [`RawUri::required_params`] to list all required parameters, including the unknown ones.
+    pub fn required_params(&self) -> impl Iterator<Item = (<&'_ T as SerializeParams>::Key, <&'_ T as SerializeParams>::Value)> {
+        self.extras.serialize_params().filter(|(key, _)| ser::display_starts_with(key, "req-"))
+    }
+}
+
+impl<'a, NetVal: NetworkValidation, T> Uri<'a, NetVal, T>
+where
+    for<'b> &'b T: SerializeParams,
+{
+    /// Assembles the URI from its components, the counterpart of [`into_parts`](Self::into_parts).
+    ///
+    /// This validates the components so that the URI can be displayed and parsed back:
+    ///
+    /// * The amount must not exceed [`bitcoin::Amount::MAX_MONEY`].
+    /// * The keys of extras must not be empty, must not contain `=`, `&` or `#` and must not be
+    ///   `amount`, `label` or `message`.
+    pub fn from_parts(
+        address: bitcoin::Address<NetVal>,
+        amount: Option<bitcoin::Amount>,
+        label: Option<Param<'a>>,
+        message: Option<Param<'a>>,
+        extras: T,
+    ) -> Result<Self, FromPartsError> {
+        use alloc::string::ToString;
+
+        if let Some(amount) = amount {
+            if amount > bitcoin::Amount::MAX_MONEY {
+                return Err(FromPartsError(FromPartsErrorInner::AmountOutOfRange(AmountOutOfRangeError { amount })));
+            }
+        }
+        for (key, _) in extras.serialize_params() {
+            let key = key.to_string();
+            if key.is_empty() || key.contains(['=', '&', '#']) || matches!(&*key, "amount" | "label" | "message") {
+                return Err(FromPartsError(FromPartsErrorInner::InvalidKey(key)));
+            }
+        }
+        Ok(Uri {
+            address,
+            amount,
+            label,
+            message,
+            extras,
+            original: None,
+            had_encoded_params: false,
+        })
+    }
+}
+
+/// Summary of the payment requested by a URI.
+///
+/// Returned by [`Uri::intent`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentIntent<NetVal: NetworkValidation = bitcoin::address::NetworkChecked> {
+    /// The address to pay to.
+    pub address: bitcoin::Address<NetVal>,
+    /// The requested amount.
+    pub amount: Option<bitcoin::Amount>,
+    /// The lossily decoded label.
+    pub label: Option<String>,
+    /// The lossily decoded message.
+    pub message: Option<String>,
+    /// Whether the URI offers payjoin.
+    pub payjoin: bool,
+}
+
+/// Error returned by [`Uri::from_parts`] when the components are invalid.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FromPartsError(FromPartsErrorInner);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum FromPartsErrorInner {
+    AmountOutOfRange(AmountOutOfRangeError),
+    InvalidKey(String),
+}
+
+impl FromPartsError {
+    /// Returns the invalid key of extras if the error was caused by one.
+    pub fn key(&self) -> Option<&str> {
+        match &self.0 {
+            FromPartsErrorInner::InvalidKey(key) => Some(key),
+            FromPartsErrorInner::AmountOutOfRange(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for FromPartsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            FromPartsErrorInner::AmountOutOfRange(error) => fmt::Display::fmt(error, f),
+            FromPartsErrorInner::InvalidKey(key) => write!(f, "the key '{}' of extras is invalid", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for FromPartsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.0 {
+            FromPartsErrorInner::AmountOutOfRange(error) => Some(error),
+            FromPartsErrorInner::InvalidKey(_) => None,
+        }
+    }
+}
+
+impl<NetVal: NetworkValidation, T: IntoOwnedExtras> Uri<'_, NetVal, T> {
+    /// Converts the URI into one that doesn't borrow anything.
+    ///
+    /// This decodes percent-encoded `label` and `message` and converts `extras` to their owned
+    /// version.
+    pub fn into_owned(self) -> Uri<'static, NetVal, T::Owned> {
+        let uri = self.into_static();
+        Uri {
+            address: uri.address,
+            amount: uri.amount,
+            label: uri.label,
+            message: uri.message,
+            extras: uri.extras.into_owned(),
+            original: None,
+            had_encoded_params: uri.had_encoded_params,
+        }
+    }
+
+    /// Clones the URI into one that doesn't borrow anything.
+    ///
+    /// Unlike `clone()`, the returned value doesn't borrow the input even if the `extras` do.
+    pub fn clone_into_owned_extras(&self) -> Uri<'static, NetVal, T::Owned>
+    where
+        NetVal: Clone,
+        T: Clone,
+    {
+        self.clone().into_owned()
+    }
+}
+
+impl<'a, NetVal: NetworkValidation, T> Uri<'a, NetVal, T> {
+    /// Returns the exact string this URI was parsed from.
+    ///
+    /// This allows reproducing the input byte-for-byte regardless of how the crate normalizes
+    /// the displayed URI - percent-encoding, casing and parameter order are all preserved.
+    ///
+    /// The input is only retained by borrowing parses with
+    /// [`ParseOptions::retain_original`](crate::de::ParseOptions::retain_original) enabled, so
+    /// that other parses don't pay for it. Returns `None` otherwise, e.g. if the URI was parsed
+    /// using `TryFrom<&str>` or `FromStr` or if it was constructed manually.
+    ///
+    /// The input is kept by conversions that don't change the fields, such as
+    /// [`assume_checked`](Uri::assume_checked) or [`require_network`](Uri::require_network), and
+    /// it's forgotten by all methods returning a URI with different fields, such as
+    /// [`with_amount`](Uri::with_amount) or [`map_extras`](Uri::map_extras). Assigning the public
+    /// fields directly can't be tracked, so the returned string only describes the input.
+    pub fn original(&self) -> Option<&'a str> {
+        self.original.as_ref().map(|original| original.input)
+    }
+
+    /// Returns `true` if the value of any parameter in the parsed input was percent-encoded.
+    ///
+    /// This is a cheap diagnostic tracked during parsing, e.g. to decide whether a faithful
+    /// re-encoding is needed. It's `false` for URIs constructed manually and it's not affected by
+    /// modifications of the URI.
+    pub fn had_encoded_params(&self) -> bool {
+        self.had_encoded_params
+    }
+
+    /// Returns the address of the URI.
+    pub fn address(&self) -> &bitcoin::Address<NetVal> {
+        &self.address
+    }
+
+    /// Returns the type of the address.
+    ///
+    /// The type doesn't depend on the network so this works even if the network wasn't checked.
+    /// Returns `None` for unknown witness versions and non-standard programs.
+    pub fn address_type(&self) -> Option<bitcoin::AddressType> {
+        self.address.as_unchecked().assume_checked_ref().address_type()
+    }
+
+    /// Returns the requested amount or an error if the URI doesn't request any.
+    ///
+    /// This is useful when the amount is mandatory, e.g. in checkout flows.
+    pub fn amount_required(&self) -> Result<bitcoin::Amount, MissingAmount> {
+        self.amount.ok_or(MissingAmount)
+    }
+
+    /// Returns the requested amount or zero if the URI doesn't request any.
+    ///
+    /// This is intended for accounting code treating a missing amount as zero. Note that it
+    /// conflates a URI without amount - the payer chooses it - with a URI explicitly requesting
+    /// `amount=0`. Use the [`amount`](Self::amount) field if the distinction matters or
+    /// [`amount_required`](Self::amount_required) if the amount is mandatory.
+    pub fn amount_or_zero(&self) -> bitcoin::Amount {
+        self.amount.unwrap_or(bitcoin::Amount::ZERO)
+    }
+
+    /// Returns the requested amount in satoshis.
+    ///
+    /// This is exact and avoids any floating point conversion.
+    pub fn amount_sat(&self) -> Option<u64> {
+        self.amount.map(bitcoin::Amount::to_sat)
+    }
+
+    /// Returns the requested amount in satoshis checking that it doesn't exceed the supply.
+    ///
+    /// Parsed amounts never exceed [`bitcoin::Amount::MAX_MONEY`] (21 million BTC) but the
+    /// `amount` field is public so it may be set to anything. This allows defensive code to
+    /// handle such amounts explicitly rather than assuming the invariant. Returns `Ok(None)` if
+    /// there's no amount.
+    pub fn amount_sat_checked(&self) -> Result<Option<u64>, AmountOutOfRangeError> {
+        match self.amount {
+            Some(amount) if amount > bitcoin::Amount::MAX_MONEY => Err(AmountOutOfRangeError { amount }),
+            amount => Ok(amount.map(bitcoin::Amount::to_sat)),
+        }
+    }
+
+    /// Returns the requested amount in BTC as a fixed-point decimal.
+    ///
+    /// The returned value is exact and always has the scale of 8 decimal places.
+    #[cfg(feature = "rust_decimal")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rust_decimal")))]
+    pub fn amount_btc_decimal(&self) -> Option<rust_decimal::Decimal> {
+        self.amount
+            .map(|amount| rust_decimal::Decimal::from_i128_with_scale(amount.to_sat().into(), 8))
+    }
+
+    /// Returns the address dropping the rest of the URI.
+    pub fn into_address(self) -> bitcoin::Address<NetVal> {
+        self.address
+    }
+}
+
+impl<'a, NetVal: NetworkValidation, T> Uri<'a, NetVal, T> {
+    /// Returns the URI with the amount set to `amount`.
+    ///
+    /// Like all methods modifying the URI, this forgets the string it was parsed from.
+    ///
+    /// Fails if the amount exceeds [`bitcoin::Amount::MAX_MONEY`] since such a payment can never
+    /// be made.
+    pub fn with_amount(self, amount: bitcoin::Amount) -> Result<Self, AmountOutOfRangeError> {
+        if amount > bitcoin::Amount::MAX_MONEY {
+            return Err(AmountOutOfRangeError { amount });
+        }
+        Ok(Uri {
+            amount: Some(amount),
+            original: None,
+            ..self
+        })
+    }
+
+    /// Returns the URI without amount.
+    pub fn without_amount(self) -> Self {
+        Uri {
+            amount: None,
+            original: None,
+            ..self
+        }
+    }
+
+    /// Returns the URI without the amount, label and message, keeping all extras.
+    ///
+    /// This removes the parts specific to a single payment so that the URI, including payjoin
+    /// endpoint or other extras, can be shared for reuse.
+    pub fn strip_amount_for_reuse(self) -> Self {
+        Uri {
+            amount: None,
+            label: None,
+            message: None,
+            original: None,
+            ..self
+        }
+    }
+
+    /// Returns the URI with the label set to `label`.
+    pub fn with_label(self, label: Label<'a>) -> Self {
+        Uri {
+            label: Some(label.into()),
+            original: None,
+            ..self
+        }
+    }
+
+    /// Returns the URI with the message set to `message`.
+    pub fn with_message(self, message: Message<'a>) -> Self {
+        Uri {
+            message: Some(message.into()),
+            original: None,
+            ..self
+        }
+    }
+
+    /// Returns the URI with both the label and the message set.
+    ///
+    /// Equivalent to calling [`with_label`](Self::with_label) and
+    /// [`with_message`](Self::with_message) - both texts were already validated when constructing
+    /// [`Label`] and [`Message`].
+    pub fn with_label_and_message(self, label: Label<'a>, message: Message<'a>) -> Self {
+        Uri {
+            label: Some(label.into()),
+            message: Some(message.into()),
+            original: None,
+            ..self
+        }
+    }
+}
+
+impl<'a, NetVal: NetworkValidation, T> Uri<'a, NetVal, T> {
+    /// Decomposes the URI into address, amount, label, message and extras.
+    ///
+    /// The label and message are returned as [`Param`]s since parsed URIs may contain values
+    /// which are not valid [`Label`]s or [`Message`]s.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(self) -> (bitcoin::Address<NetVal>, Option<bitcoin::Amount>, Option<Param<'a>>, Option<Param<'a>>, T) {
+        (self.address, self.amount, self.label, self.message, self.extras)
+    }
+
+    /// Transforms the address keeping all other fields intact.
+    ///
+    /// This can also change the network validation state of the address. The string the URI
+    /// was parsed from is kept if `f` returns the same address.
+    pub fn map_address<NewNetVal: NetworkValidation, F>(self, f: F) -> Uri<'a, NewNetVal, T>
+    where
+        F: FnOnce(bitcoin::Address<NetVal>) -> bitcoin::Address<NewNetVal>,
+    {
+        let old_address = self.address.as_unchecked().clone();
+        let address = f(self.address);
+        let is_same_address = *address.as_unchecked() == old_address;
+        Uri {
+            address,
+            amount: self.amount,
+            label: self.label,
+            message: self.message,
+            extras: self.extras,
+            original: self.original.filter(|_| is_same_address),
+            had_encoded_params: self.had_encoded_params,
+        }
+    }
+
+    /// Replaces the address with `address` after checking it's valid for `network`.
+    ///
+    /// This keeps the amount, label, message and extras so that the metadata of an invoice can
+    /// be reused with a freshly derived address. The returned URI is network-checked, the string
+    /// it was parsed from is forgotten unless `address` is the same as the current one.
+    pub fn set_network_unchecked_address(
+        self,
+        address: bitcoin::Address<bitcoin::address::NetworkUnchecked>,
+        network: bitcoin::Network,
+    ) -> Result<Uri<'a, bitcoin::address::NetworkChecked, T>, ser::NetworkMismatchError> {
+        if !address.is_valid_for_network(network) {
+            return Err(ser::NetworkMismatchError::new(network));
+        }
+        Ok(self.map_address(|_| address.assume_checked()))
+    }
+
+    /// Transforms the extras keeping all other fields intact.
+    ///
+    /// The string the URI was parsed from is forgotten since the new extras can't be compared
+    /// with the old ones.
+    pub fn map_extras<U, F: FnOnce(T) -> U>(self, f: F) -> Uri<'a, NetVal, U> {
+        Uri {
+            address: self.address,
+            amount: self.amount,
+            label: self.label,
+            message: self.message,
+            extras: f(self.extras),
+            original: None,
+            had_encoded_params: self.had_encoded_params,
+        }
+    }
+}
+
+impl<'a, NetVal: NetworkValidation, T: MergeExtras> Uri<'a, NetVal, T> {
+    /// Combines parameters of this URI with parameters of `other`.
+    ///
+    /// The address of `self` is kept, `other` only contributes parameters. This is useful e.g. to
+    /// augment user-provided address URI with label and payjoin endpoint of a server.
+    ///
+    /// Parameters present in both URIs must have equal (decoded) values, otherwise
+    /// [`MergeConflict`] containing the key of the parameter is returned.
+    pub fn merge_params<OtherNetVal: NetworkValidation>(self, other: &Uri<'a, OtherNetVal, T>) -> Result<Self, MergeConflict> {
+        let amount = merge_option("amount", self.amount, &other.amount, PartialEq::eq)?;
+        let label = merge_option("label", self.label, &other.label, |ours, theirs| {
+            ours.decoded_bytes() == theirs.decoded_bytes()
+        })?;
+        let message = merge_option("message", self.message, &other.message, |ours, theirs| {
+            ours.decoded_bytes() == theirs.decoded_bytes()
+        })?;
+        Ok(Uri {
+            address: self.address,
+            amount,
+            label,
+            message,
+            extras: self.extras.merge(&other.extras)?,
+            original: None,
+            had_encoded_params: self.had_encoded_params,
+        })
+    }
+}
+
+/// Merges optional values of parameter `key` failing if both are present and not equal.
+pub(crate) fn merge_option<V: Clone, F: FnOnce(&V, &V) -> bool>(
+    key: &str,
+    ours: Option<V>,
+    theirs: &Option<V>,
+    eq: F,
+) -> Result<Option<V>, MergeConflict> {
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) if !eq(&ours, theirs) => Err(MergeConflict { key: key.into() }),
+        (Some(ours), _) => Ok(Some(ours)),
+        (None, theirs) => Ok(theirs.clone()),
+    }
+}
+
+/// Extras that can be combined with extras of another URI.
+///
+/// This is used by [`Uri::merge_params`].
+pub trait MergeExtras: Sized {
+    /// Combines `self` with `other` failing if both contain different values of the same parameter.
+    fn merge(self, other: &Self) -> Result<Self, MergeConflict>;
+}
+
+/// Error returned when merged URIs contain different values of the same parameter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MergeConflict {
+    key: String,
+}
+
+impl MergeConflict {
+    /// Creates the error for the conflicting parameter `key`.
+    pub fn new(key: impl Into<String>) -> Self {
+        MergeConflict { key: key.into() }
+    }
+
+    /// Returns the key of the conflicting parameter.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the parameter '{}' has conflicting values", self.key)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for MergeConflict {}
+
+impl<NetVal: NetworkValidation, T> AsRef<bitcoin::Address<NetVal>> for Uri<'_, NetVal, T> {
+    fn as_ref(&self) -> &bitcoin::Address<NetVal> {
+        &self.address
+    }
+}
+
+/// Error returned by [`Uri::amount_required`] when the URI has no amount.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MissingAmount;
+
+impl fmt::Display for MissingAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the URI is missing the required amount")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for MissingAmount {}
+
+/// Error returned when the amount exceeds the maximum possible supply of bitcoin.
+///
+/// Returned by [`Uri::with_amount`] and [`Uri::amount_sat_checked`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AmountOutOfRangeError {
+    amount: bitcoin::Amount,
+}
+
+impl AmountOutOfRangeError {
+    /// Returns the rejected amount.
+    pub fn amount(&self) -> bitcoin::Amount {
+        self.amount
+    }
+}
+
+impl fmt::Display for AmountOutOfRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the amount {} exceeds the maximum of {}", self.amount, bitcoin::Amount::MAX_MONEY)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for AmountOutOfRangeError {}
+
+impl<'a, T> Uri<'a, bitcoin::address::NetworkChecked, T> {
+    /// Returns the output script paying to the address of this URI.
+    pub fn script_pubkey(&self) -> bitcoin::ScriptBuf {
+        self.address.script_pubkey()
+    }
+
+    /// Forgets that the network of the address was checked.
+    ///
+    /// This is the inverse of [`assume_checked`](Uri::assume_checked) and can be used to
+    /// validate the URI against another network again.
+    pub fn into_unchecked(self) -> Uri<'a, bitcoin::address::NetworkUnchecked, T> {
+        Uri {
+            address: self.address.into_unchecked(),
+            amount: self.amount,
+            label: self.label,
+            message: self.message,
+            extras: self.extras,
+            original: self.original,
+            had_encoded_params: self.had_encoded_params,
         }
     }
+
+    /// Returns a clone of the URI with the network of the address unchecked.
+    ///
+    /// This is the same as `uri.clone().into_unchecked()` and it's useful when forwarding the URI
+    /// to a component that validates it against its own network, avoiding serializing and
+    /// parsing it again.
+    pub fn clone_with_network_unchecked(&self) -> Uri<'a, bitcoin::address::NetworkUnchecked, T>
+    where
+        T: Clone,
+    {
+        self.clone().into_unchecked()
+    }
+}
+
+/// Extras that can be converted into a value not borrowing anything.
+///
+/// This is used by [`Uri::into_owned`]. Extras that don't borrow should simply return `self`.
+pub trait IntoOwnedExtras {
+    /// The owned version of extras.
+    type Owned: 'static;
+
+    /// Converts extras into their owned version, copying borrowed data.
+    fn into_owned(self) -> Self::Owned;
 }
 
 /// Abstracted stringly parameter in the URI.
@@ -173,6 +841,47 @@ impl<'a> Param<'a> {
         })
     }
 
+    /// Creates a parameter holding `value` which gets percent-encoded when displaying the URI.
+    ///
+    /// This is the inverse of the decoded accessors: any bytes can be stored, including `&`,
+    /// `=` and non-UTF-8 sequences, and parsing the displayed URI yields them unchanged.
+    #[cfg(feature = "non-compliant-bytes")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "non-compliant-bytes")))]
+    pub fn encode(value: &[u8]) -> Param<'static> {
+        Param(ParamInner::UnencodedBytes(Cow::Owned(value.to_vec())))
+    }
+
+    /// Compares decoded value with `expected` without short-circuiting on the first difference.
+    ///
+    /// This is intended for parameters carrying secrets, the comparison time doesn't depend on
+    /// the position of the first differing byte. Note that the length of the value may still
+    /// leak.
+    #[cfg(feature = "subtle")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "subtle")))]
+    pub fn ct_eq(&self, expected: &[u8]) -> bool {
+        use subtle::ConstantTimeEq;
+
+        self.decoded_bytes().ct_eq(expected).into()
+    }
+
+    /// Returns decoded bytes, borrowing if possible.
+    pub(crate) fn decoded_bytes(&self) -> Cow<'_, [u8]> {
+        match &self.0 {
+            ParamInner::EncodedBorrowed(decoder) => decoder.clone().into(),
+            ParamInner::UnencodedBytes(bytes) => Cow::Borrowed(bytes),
+            ParamInner::UnencodedString(string) => Cow::Borrowed(string.as_bytes()),
+        }
+    }
+
+    /// Borrows the value without copying it.
+    pub(crate) fn reborrow(&self) -> Param<'_> {
+        Param(match &self.0 {
+            ParamInner::EncodedBorrowed(decoder) => ParamInner::EncodedBorrowed(decoder.clone()),
+            ParamInner::UnencodedBytes(bytes) => ParamInner::UnencodedBytes(Cow::Borrowed(bytes)),
+            ParamInner::UnencodedString(string) => ParamInner::UnencodedString(Cow::Borrowed(string)),
+        })
+    }
+
     /// Decodes the param if encoded making the lifetime static.
     fn decode_into_owned<'b>(self) -> Param<'b> {
         let owned = match self.0 {
@@ -186,6 +895,24 @@ impl<'a> Param<'a> {
     }
 }
 
+/// Compares the decoded values, so differently percent-encoded values are equal.
+///
+/// Use `Param::ct_eq` (requires `subtle`) for parameters carrying secrets.
+impl PartialEq for Param<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.decoded_bytes() == other.decoded_bytes()
+    }
+}
+
+impl Eq for Param<'_> {}
+
+/// Hashes the decoded value, consistently with `PartialEq`.
+impl core::hash::Hash for Param<'_> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.decoded_bytes().hash(state)
+    }
+}
+
 /// Cheap conversion
 impl<'a> From<&'a str> for Param<'a> {
     fn from(value: &'a str) -> Self {
@@ -272,24 +999,119 @@ impl<'a> TryFrom<Param<'a>> for Cow<'a, str> {
     }
 }
 
-#[derive(Debug, Clone)]
-enum ParamInner<'a> {
-    EncodedBorrowed(PercentDecode<'a>),
-    UnencodedBytes(Cow<'a, [u8]>),
-    UnencodedString(Cow<'a, str>),
-}
+macro_rules! text_param {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        ///
+        /// The text is guaranteed to not contain control characters so it displays faithfully and
+        /// the URI containing it can always be serialized.
+        #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        pub struct $name<'a>(Cow<'a, str>);
+
+        impl<'a> $name<'a> {
+            /// Validates the text and wraps it.
+            pub fn new<S: Into<Cow<'a, str>>>(text: S) -> Result<Self, ControlCharacterError> {
+                let text = text.into();
+                match text.char_indices().find(|(_, c)| c.is_control()) {
+                    Some((position, _)) => Err(ControlCharacterError { position }),
+                    None => Ok($name(text)),
+                }
+            }
+
+            /// Returns the (decoded) text.
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Returns the (decoded) text.
+            pub fn into_inner(self) -> Cow<'a, str> {
+                self.0
+            }
+        }
 
-/// Iterator over decoded bytes inside paramter.
-///
-/// The lifetime of this may be shorter than that of [`Param<'a>`].
-#[cfg(feature = "non-compliant-bytes")]
-#[cfg_attr(docsrs, doc(cfg(feature = "non-compliant-bytes")))]
-#[cfg_attr(feature = "non-compliant-bytes", allow(dead_code))]
-pub struct ParamBytes<'a>(ParamIterInner<'a, core::iter::Cloned<core::slice::Iter<'a, u8>>>);
+        /// Displays the decoded text.
+        impl fmt::Display for $name<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
 
-/// Iterator over decoded bytes inside paramter.
-///
-/// The lifetime of this is same as that of [`Param<'a>`].
+        /// Cheap conversion
+        impl<'a> From<$name<'a>> for Param<'a> {
+            fn from(value: $name<'a>) -> Self {
+                Param(ParamInner::UnencodedString(value.0))
+            }
+        }
+
+        impl<'a> TryFrom<&'a str> for $name<'a> {
+            type Error = ControlCharacterError;
+
+            fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+
+        impl TryFrom<String> for $name<'_> {
+            type Error = ControlCharacterError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                Self::new(value)
+            }
+        }
+    };
+}
+
+text_param! {
+    /// The label of the address - e.g. name of the receiver.
+    Label
+}
+
+text_param! {
+    /// Message that describes the transaction to the user.
+    Message
+}
+
+/// Error returned when the text of [`Label`] or [`Message`] contains a control character.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ControlCharacterError {
+    position: usize,
+}
+
+impl ControlCharacterError {
+    /// Returns the byte position of the offending character.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for ControlCharacterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the text contains control character at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for ControlCharacterError {}
+
+#[derive(Debug, Clone)]
+enum ParamInner<'a> {
+    EncodedBorrowed(PercentDecode<'a>),
+    UnencodedBytes(Cow<'a, [u8]>),
+    UnencodedString(Cow<'a, str>),
+}
+
+/// Iterator over decoded bytes inside paramter.
+///
+/// The lifetime of this may be shorter than that of [`Param<'a>`].
+#[cfg(feature = "non-compliant-bytes")]
+#[cfg_attr(docsrs, doc(cfg(feature = "non-compliant-bytes")))]
+#[cfg_attr(feature = "non-compliant-bytes", allow(dead_code))]
+pub struct ParamBytes<'a>(ParamIterInner<'a, core::iter::Cloned<core::slice::Iter<'a, u8>>>);
+
+/// Iterator over decoded bytes inside paramter.
+///
+/// The lifetime of this is same as that of [`Param<'a>`].
 #[cfg(feature = "non-compliant-bytes")]
 #[cfg_attr(docsrs, doc(cfg(feature = "non-compliant-bytes")))]
 #[cfg_attr(feature = "non-compliant-bytes", allow(dead_code))]
@@ -309,6 +1131,20 @@ pub struct NoExtras;
 #[derive(Debug, Default, Copy, Clone)]
 pub struct EmptyState;
 
+impl IntoOwnedExtras for NoExtras {
+    type Owned = NoExtras;
+
+    fn into_owned(self) -> Self::Owned {
+        self
+    }
+}
+
+impl MergeExtras for NoExtras {
+    fn merge(self, _other: &Self) -> Result<Self, MergeConflict> {
+        Ok(self)
+    }
+}
+
 impl DeserializeParams<'_> for NoExtras {
     type DeserializationState = EmptyState;
 }
@@ -343,20 +1179,118 @@ impl SerializeParams for &NoExtras {
     }
 }
 
+/// Generates URIs with random standard addresses on random networks.
+///
+/// The generated URIs are valid so they roundtrip unless extras fail to serialize.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a, T: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for Uri<'a, bitcoin::address::NetworkChecked, T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{Network, WitnessProgram, WitnessVersion};
+
+        let network = *u.choose(&[Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest])?;
+        let address = match u.int_in_range(0u8..=4)? {
+            0 => bitcoin::Address::p2pkh(bitcoin::PubkeyHash::from_byte_array(u.arbitrary()?), network),
+            1 => bitcoin::Address::p2sh_from_hash(bitcoin::ScriptHash::from_byte_array(u.arbitrary()?), network),
+            2 => {
+                let program = WitnessProgram::new(WitnessVersion::V0, &u.arbitrary::<[u8; 20]>()?).expect("valid length");
+                bitcoin::Address::from_witness_program(program, network)
+            },
+            3 => {
+                let program = WitnessProgram::new(WitnessVersion::V0, &u.arbitrary::<[u8; 32]>()?).expect("valid length");
+                bitcoin::Address::from_witness_program(program, network)
+            },
+            _ => {
+                let program = WitnessProgram::new(WitnessVersion::V1, &u.arbitrary::<[u8; 32]>()?).expect("valid length");
+                bitcoin::Address::from_witness_program(program, network)
+            },
+        };
+        let amount = if u.arbitrary()? {
+            Some(bitcoin::Amount::from_sat(u.int_in_range(0..=bitcoin::Amount::MAX_MONEY.to_sat())?))
+        } else {
+            None
+        };
+        Ok(Uri {
+            address,
+            amount,
+            label: u.arbitrary()?,
+            message: u.arbitrary()?,
+            extras: u.arbitrary()?,
+            original: None,
+            had_encoded_params: false,
+        })
+    }
+}
+
+/// Generates UTF-8 values borrowing the input.
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl<'a> arbitrary::Arbitrary<'a> for Param<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary::<&'a str>().map(Into::into)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+impl arbitrary::Arbitrary<'_> for NoExtras {
+    fn arbitrary(_u: &mut arbitrary::Unstructured<'_>) -> arbitrary::Result<Self> {
+        Ok(NoExtras)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::Uri;
     use alloc::string::ToString;
     use alloc::borrow::Cow;
-    use core::convert::TryInto;
+    use alloc::string::String;
+    #[cfg(feature = "test-utils")]
+    use alloc::vec::Vec;
+    use core::convert::{TryFrom, TryInto};
 
     fn check_send_sync<T: Send + Sync>() {}
 
+    fn check_clone<T: Clone>() {}
+
     #[test]
     fn send_sync() {
         check_send_sync::<crate::de::UriError>();
     }
 
+    #[test]
+    fn errors_are_clone() {
+        check_clone::<crate::de::UriError>();
+        check_clone::<crate::de::Error<core::convert::Infallible>>();
+        check_clone::<<Uri<'_, bitcoin::address::NetworkUnchecked> as core::str::FromStr>::Err>();
+    }
+
+    #[test]
+    fn cloned_error_outlives_input() {
+        let error = {
+            let input = alloc::string::String::from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=bar");
+            let error = Uri::<'_, bitcoin::address::NetworkUnchecked>::try_from(input.as_str()).unwrap_err();
+            error.clone()
+        };
+        match error {
+            crate::de::Error::Uri(error) => assert_eq!(error.to_string(), "the URI contains unknown required parameter 'req-foo'"),
+            crate::de::Error::Extras(never) => match never {},
+        }
+    }
+
+    pub(crate) fn test_address() -> bitcoin::Address {
+        "1andreas3batLhQa2FawWjeyjCqyBzypd"
+            .parse::<bitcoin::Address<_>>()
+            .unwrap()
+            .assume_checked()
+    }
+
+    /// Returns the options retaining the input, see [`Uri::original`].
+    pub(crate) fn retaining() -> crate::de::ParseOptions {
+        crate::de::ParseOptions::new().retain_original(true)
+    }
+
     // Note: the official test vectors contained an invalid address so it was replaced with the address of Andreas Antonopoulos.
 
     #[test]
@@ -413,6 +1347,22 @@ mod tests {
         assert_eq!(uri.to_string(), input);
     }
 
+    #[test]
+    fn whole_btc_amount_is_minimal() {
+        let mut uri = Uri::<'_, _>::new(test_address());
+        uri.amount = Some(bitcoin::Amount::ONE_BTC);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+    }
+
+    #[test]
+    fn fractional_amount_is_minimal() {
+        let mut uri = Uri::<'_, _>::new(test_address());
+        uri.amount = Some(bitcoin::Amount::from_sat(50_000_000));
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.5");
+        uri.amount = Some(bitcoin::Amount::from_sat(1));
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.00000001");
+    }
+
     #[test]
     fn required_not_understood() {
         let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-somethingyoudontunderstand=50&req-somethingelseyoudontget=999";
@@ -445,6 +1395,26 @@ mod tests {
         assert_eq!(uri.to_string(), input);
     }
 
+    #[test]
+    fn label_with_bip21_separators_roundtrips() {
+        let mut uri = Uri::<'_, _>::new(test_address());
+        uri.label = Some("A&B=C".into());
+        uri.message = Some("&amount=1".into());
+        let serialized = uri.to_string();
+        assert_eq!(
+            serialized,
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=A%26B%3DC&message=%26amount%3D1"
+        );
+
+        let uri = serialized.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let label: Cow<'_, str> = uri.label.clone().unwrap().try_into().unwrap();
+        let message: Cow<'_, str> = uri.message.clone().unwrap().try_into().unwrap();
+        assert_eq!(label, "A&B=C");
+        assert_eq!(message, "&amount=1");
+        assert!(uri.amount.is_none());
+        assert_eq!(uri.to_string(), serialized);
+    }
+
     #[test]
     fn label_with_rfc3986_fragment_separator() {
         let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=foo%23bar";
@@ -482,6 +1452,1911 @@ mod tests {
         assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=foo");
     }
 
+    #[test]
+    fn contains_param() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&somethingyoudontunderstand=50";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().require_network(bitcoin::Network::Bitcoin).unwrap();
+        assert!(uri.contains_param("amount"));
+        assert!(uri.contains_param("label"));
+        assert!(!uri.contains_param("message"));
+        assert!(!uri.contains_param("somethingyoudontunderstand"));
+        assert!(!uri.contains_param(""));
+    }
+
+    #[test]
+    fn unnecessary_encoding_accepted_by_default() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%4Cuke-Jr";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let label: Cow<'_, str> = uri.label.unwrap().try_into().unwrap();
+        assert_eq!(label, "Luke-Jr");
+    }
+
+    #[test]
+    fn reject_unnecessary_encoding() {
+        let options = crate::ParseOptions::new().reject_unnecessary_encoding(true);
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke%2dJr";
+        match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.position(), Some(52)),
+            crate::de::Error::Extras(never) => match never {},
+        }
+        assert_eq!(&input[52..55], "%2d");
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?somethingyoudontunderstand=%7e";
+        assert!(Uri::<'_, _>::parse_with_options(input, &options).is_err());
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?%6Cabel=Luke";
+        match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.position(), Some(42)),
+            crate::de::Error::Extras(never) => match never {},
+        }
+        let (_, warnings) = Uri::<'_, _>::parse_with_warnings(input);
+        assert_eq!(warnings[0], crate::de::Warning::UnnecessaryEncoding { position: 42 });
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=foo%26bar%20%3D%20baz";
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert_eq!(uri.to_string(), input);
+    }
+
+    #[test]
+    fn label_and_message_newtypes() {
+        let mut uri = Uri::<'_, _>::new(test_address());
+        let label = crate::Label::new("Luke-Jr").unwrap();
+        assert_eq!(label.to_string(), "Luke-Jr");
+        uri.label = Some(label.into());
+        uri.message = Some(crate::Message::new("Donation for project xyz").unwrap().into());
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke-Jr&message=Donation%20for%20project%20xyz"
+        );
+    }
+
+    #[test]
+    fn label_rejects_control_characters() {
+        assert_eq!(crate::Label::new("foo\nbar").unwrap_err().position(), 3);
+        assert_eq!(crate::Label::new("bad\n").unwrap_err().position(), 3);
+        assert!(crate::Message::try_from("\u{7f}").is_err());
+        assert!(crate::Message::try_from("ö & ü").is_ok());
+    }
+
+    #[test]
+    fn into_owned_doesnt_borrow() {
+        let owned: Uri<'static, _> = {
+            let input = alloc::string::String::from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke%20Jr&message=Donation");
+            let uri = Uri::<'_, _>::try_from(input.as_str()).unwrap().assume_checked();
+            uri.clone_into_owned_extras()
+        };
+        assert_eq!(
+            owned.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke%20Jr&message=Donation"
+        );
+        let label: Cow<'_, str> = owned.into_owned().label.unwrap().try_into().unwrap();
+        assert_eq!(label, "Luke Jr");
+    }
+
+    #[test]
+    fn semicolon_separator() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50;label=Luke-Jr&message=Donation";
+        // `;` is a valid character of the amount value by default, making it invalid
+        assert!(input.parse::<Uri<'_, _>>().is_err());
+
+        let options = crate::ParseOptions::new().allow_semicolon_separator(true);
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        let label: Cow<'_, str> = uri.label.clone().unwrap().try_into().unwrap();
+        let message: Cow<'_, str> = uri.message.clone().unwrap().try_into().unwrap();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::from_int_btc(50)));
+        assert_eq!(label, "Luke-Jr");
+        assert_eq!(message, "Donation");
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation"
+        );
+    }
+
+    #[test]
+    fn to_bytes() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.to_bytes(), input.as_bytes());
+        let mut string = alloc::string::String::new();
+        uri.to_writer(&mut string).unwrap();
+        assert_eq!(string, input);
+
+        assert_eq!(uri.to_bytes_bounded(input.len()).unwrap(), input.as_bytes());
+        assert_eq!(uri.to_bytes_bounded(input.len() - 1).unwrap_err().max(), input.len() - 1);
+        assert!(uri.to_bytes_bounded(0).is_err());
+    }
+
+    #[test]
+    fn parse_lines() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1\n\n  bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1 \r\nbitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd\n";
+        let results = crate::de::parse_lines::<crate::NoExtras>(input).collect::<alloc::vec::Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().amount, Some(bitcoin::Amount::ONE_BTC));
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn script_pubkey() {
+        let cases = [
+            (
+                "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+                "0014e8df018c7e326cc253faac7e46cdc51e68542c42",
+            ),
+            (
+                "bitcoin:bc1p5d7rjq7g6rdk2yhzks9smlaqtedr4dekq08ge8ztwac72sfr9rusxg3297",
+                "5120a37c3903c8d0db6512e2b40b0dffa05e5a3ab73603ce8c9c4b7771e5412328f9",
+            ),
+            (
+                "bitcoin:3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy?amount=1",
+                "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87",
+            ),
+        ];
+        for (input, script) in cases {
+            let uri = input.parse::<Uri<'_, _>>().unwrap().require_network(bitcoin::Network::Bitcoin).unwrap();
+            assert_eq!(uri.script_pubkey().to_hex_string(), script);
+        }
+    }
+
+    #[test]
+    fn canonical_string() {
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?message=Donation%20for%20project%20%78yz&label=Luke-Jr&amount=50.00";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.to_canonical_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz"
+        );
+    }
+
+    #[test]
+    fn canonical_string_orders_repeated_keys_by_value() {
+        use crate::ser::SerializeParams;
+
+        struct Repeated(&'static [(&'static str, &'static str)]);
+
+        impl<'a> SerializeParams for &'a Repeated {
+            type Key = &'static str;
+            type Value = &'static str;
+            type Iterator = core::iter::Copied<core::slice::Iter<'a, (&'static str, &'static str)>>;
+
+            fn serialize_params(self) -> Self::Iterator {
+                self.0.iter().copied()
+            }
+        }
+
+        let extras = Repeated(&[("foo", "b"), ("bar", "x"), ("foo", "a&b"), ("foo", "a")]);
+        let uri = Uri::<'_, _, _>::with_extras(test_address(), extras);
+        assert_eq!(
+            uri.to_canonical_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?bar=x&foo=a&foo=a%26b&foo=b"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn to_io_writer() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let mut buf = std::io::Cursor::new(alloc::vec::Vec::new());
+        uri.to_io_writer(&mut buf).unwrap();
+        assert_eq!(buf.into_inner(), input.as_bytes());
+    }
+
+    #[test]
+    fn is_canonical() {
+        let canonical = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz";
+        let uri = Uri::<'_, _>::try_from(canonical).unwrap().assume_checked();
+        assert!(uri.is_canonical());
+
+        let non_canonical = [
+            "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50.0",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%4Cuke-Jr",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke-Jr&amount=50",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?somethingyoudontunderstand=50",
+        ];
+        for input in non_canonical {
+            let uri = Uri::<'_, _>::try_from(input).unwrap().assume_checked();
+            assert!(uri.is_canonical(), "{}", input);
+            let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+            assert!(!uri.is_canonical(), "{}", input);
+        }
+
+        let uri = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50.0"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert!(uri.is_canonical());
+    }
+
+    #[test]
+    fn is_canonical_after_modification() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke-Jr";
+        let mut uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+        uri.label = None;
+        assert_eq!(uri.original(), Some(input));
+        assert!(uri.is_canonical());
+        assert_eq!(uri.canonicalization_diff(), []);
+
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke-Jr";
+        let mut uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+        uri.label = Some(crate::Label::new("Luke-Jr").unwrap().into());
+        assert!(!uri.is_canonical());
+        uri.amount = Some(bitcoin::Amount::ONE_BTC);
+        assert!(uri.is_canonical());
+
+        // extras normalizing the value still describe the input
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?feerate=012";
+        let uri = Uri::<'_, _, crate::extras::FeeExtras>::parse_with_options(input, &retaining())
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?feerate=12");
+        assert!(!uri.is_canonical());
+        let mut modified = uri.clone();
+        modified.extras.fee_rate = bitcoin::FeeRate::from_sat_per_vb(13);
+        assert!(modified.is_canonical());
+    }
+
+    #[test]
+    fn fluent_setters() {
+        let uri = Uri::<'_, _>::new(test_address())
+            .with_amount(bitcoin::Amount::from_sat(50_000_000))
+            .unwrap()
+            .with_label(crate::Label::new("Luke-Jr").unwrap())
+            .with_message(crate::Message::new("Donation").unwrap());
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.5&label=Luke-Jr&message=Donation"
+        );
+        let uri = uri.without_amount();
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke-Jr&message=Donation"
+        );
+
+        let error = uri.with_amount(bitcoin::Amount::MAX_MONEY + bitcoin::Amount::from_sat(1)).unwrap_err();
+        assert_eq!(error.amount(), bitcoin::Amount::MAX_MONEY + bitcoin::Amount::from_sat(1));
+        assert!(Uri::<'_, _>::new(test_address()).with_amount(bitcoin::Amount::MAX_MONEY).is_ok());
+    }
+
+    #[cfg(feature = "subtle")]
+    #[test]
+    fn param_ct_eq() {
+        let uri = Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=s%65cret").unwrap();
+        let label = uri.label.unwrap();
+        assert!(label.ct_eq(b"secret"));
+        assert!(!label.ct_eq(b"secreT"));
+        assert!(!label.ct_eq(b"secret2"));
+    }
+
+    #[test]
+    fn map_address() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr";
+        let uri = Uri::<'_, bitcoin::address::NetworkUnchecked>::parse_with_options(input, &retaining()).unwrap();
+        let uri: Uri<'_, bitcoin::address::NetworkChecked> = uri.map_address(|address| address.require_network(bitcoin::Network::Bitcoin).unwrap());
+        assert_eq!(uri.to_string(), input);
+        assert_eq!(uri.original(), Some(input));
+
+        let other = "1BoatSLRHtKNngkdXEeobR76b53LETtpyT"
+            .parse::<bitcoin::Address<_>>()
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.clone().map_address(|_| other).original(), None);
+
+        let uri = uri.map_extras(|_| crate::extras::ExpiryExtras::default()).map_extras(|mut extras| {
+            extras.time = Some(42);
+            extras
+        });
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr&time=42"
+        );
+    }
+
+    #[test]
+    fn merge_params() {
+        let base = Uri::new(test_address());
+        let other = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Andreas"
+            .parse::<Uri<'_, _>>()
+            .unwrap();
+        let merged = base.merge_params(&other).unwrap();
+        assert_eq!(merged.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Andreas");
+
+        let same = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41ndreas".parse::<Uri<'_, _>>().unwrap();
+        let merged = merged.merge_params(&same).unwrap();
+        assert_eq!(merged.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Andreas");
+
+        let conflicting = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2".parse::<Uri<'_, _>>().unwrap();
+        let error = merged.merge_params(&conflicting).unwrap_err();
+        assert_eq!(error.key(), "amount");
+        assert_eq!(error.to_string(), "the parameter 'amount' has conflicting values");
+    }
+
+    #[test]
+    fn original() {
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?message=%61%20b&amount=1.0";
+        assert_eq!(Uri::<'_, _>::try_from(input).unwrap().original(), None);
+        let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap();
+        assert_eq!(uri.original(), Some(input));
+        let uri = uri.assume_checked();
+        assert_eq!(uri.original(), Some(input));
+        assert_eq!(uri.without_amount().original(), None);
+
+        assert_eq!(input.parse::<Uri<'_, _>>().unwrap().original(), None);
+        let owned = Uri::<'_, _>::try_from(input.to_string()).unwrap();
+        assert_eq!(owned.original(), None);
+        assert_eq!(Uri::<'_, _>::new(test_address()).original(), None);
+    }
+
+    #[test]
+    fn parse_with_warnings() {
+        use crate::de::Warning;
+
+        let input = "Bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41&foo=bar&req-foo=x";
+        let (result, warnings) = Uri::<'_, _>::parse_with_warnings(input);
+        assert!(result.is_err());
+        assert_eq!(
+            warnings,
+            [
+                Warning::NonLowercaseScheme,
+                Warning::UnnecessaryEncoding { position: 48 },
+                Warning::UnknownParameter { position: 52 }
+            ]
+        );
+        assert_eq!(warnings[2].position(), 52);
+
+        let long = alloc::format!(
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message={}",
+            "a".repeat(crate::de::LONG_VALUE_LENGTH + 1)
+        );
+        let (result, warnings) = Uri::<'_, _>::parse_with_warnings(&long);
+        assert!(result.is_ok());
+        assert_eq!(warnings, [Warning::LongValue { position: 50, length: 1025 }]);
+
+        let (result, warnings) = Uri::<'_, _>::parse_with_warnings("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+        assert!(result.is_ok());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn address_accessors() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1".parse::<Uri<'_, _>>().unwrap();
+        let unchecked: bitcoin::Address<bitcoin::address::NetworkUnchecked> = uri.address().clone();
+        assert_eq!(uri.clone().into_address(), unchecked);
+        let uri = uri.assume_checked();
+        assert_eq!(*uri.address(), test_address());
+        assert_eq!(uri.into_address(), test_address());
+    }
+
+    #[test]
+    fn canonical_ordering() {
+        use alloc::collections::BTreeSet;
+
+        let a = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41&amount=1.0"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let b = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=A"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let c = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert_eq!(a, b);
+        assert!(b < c);
+
+        let set = alloc::vec![c.clone(), a, b].into_iter().collect::<BTreeSet<_>>();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.iter().next_back(), Some(&c));
+
+        // the order of parameters of extras doesn't matter either
+        let parse = |input| Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let foo_bar = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1&bar=2");
+        let bar_foo = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?bar=2&foo=1");
+        assert_eq!(foo_bar.to_canonical_string(), bar_foo.to_canonical_string());
+        assert_eq!(foo_bar, bar_foo);
+        assert_eq!(foo_bar.cmp(&bar_foo), core::cmp::Ordering::Equal);
+        assert_ne!(foo_bar, parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?bar=1&foo=2"));
+
+        // keys that can't be displayed don't make comparing panic
+        let mut extras = crate::extras::UnknownParams::new();
+        extras.push("a=b", "c");
+        let d = Uri::with_extras(test_address(), extras.clone());
+        let e = Uri::with_extras(test_address(), extras.param("x".repeat(100), "y").unwrap());
+        assert_eq!(d, d.clone());
+        assert!(d < e);
+    }
+
+    #[test]
+    fn normalize_legacy_key() {
+        use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind};
+
+        #[derive(Default)]
+        struct Migrated {
+            value: Option<alloc::string::String>,
+            legacy: bool,
+        }
+
+        impl DeserializeParams<'_> for Migrated {
+            type DeserializationState = Migrated;
+        }
+
+        impl DeserializationError for Migrated {
+            type Error = core::convert::Infallible;
+        }
+
+        impl DeserializationState<'_> for Migrated {
+            type Value = Migrated;
+
+            fn is_param_known(&self, key: &str) -> bool {
+                key == "new"
+            }
+
+            fn normalize_key<'k>(&mut self, key: &'k str) -> &'k str {
+                if key == "old" {
+                    self.legacy = true;
+                    "new"
+                } else {
+                    key
+                }
+            }
+
+            fn deserialize_temp(&mut self, key: &str, value: crate::Param<'_>) -> Result<ParamKind, core::convert::Infallible> {
+                if key != "new" {
+                    return Ok(ParamKind::Unknown);
+                }
+                self.value = Some(value.try_into().unwrap());
+                Ok(ParamKind::Known)
+            }
+
+            fn finalize(self) -> Result<Self, core::convert::Infallible> {
+                Ok(self)
+            }
+        }
+
+        impl<'a> crate::ser::SerializeParams for &'a Migrated {
+            type Key = &'static str;
+            type Value = &'a str;
+            type Iterator = core::option::IntoIter<(&'static str, &'a str)>;
+
+            fn serialize_params(self) -> Self::Iterator {
+                let key = if self.legacy { "old" } else { "new" };
+                self.value.as_deref().map(|value| (key, value)).into_iter()
+            }
+        }
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?old=foo";
+        let uri = input.parse::<Uri<'_, _, Migrated>>().unwrap().assume_checked();
+        assert_eq!(uri.extras.value.as_deref(), Some("foo"));
+        assert_eq!(uri.to_string(), input);
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?new=foo"
+            .parse::<Uri<'_, _, Migrated>>()
+            .unwrap();
+        assert!(!uri.extras.legacy);
+        assert!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-old=foo"
+            .parse::<Uri<'_, _, Migrated>>()
+            .is_err());
+    }
+
+    #[test]
+    fn sanitize() {
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?message=%1B[31mPay%0Anow&label=%41lice&amount=1.50&foo=%07bar";
+        let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let sanitized = uri.sanitize().unwrap();
+        assert_eq!(
+            sanitized.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1.5&label=Alice&message=%5B31mPaynow&foo=bar"
+        );
+        assert_eq!(sanitized.extras.get("foo").unwrap().decoded_bytes(), &b"bar"[..]);
+        assert_eq!(
+            sanitized.to_canonical_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1.5&foo=bar&label=Alice&message=%5B31mPaynow"
+        );
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%FF"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let error = uri.sanitize().unwrap_err();
+        assert_eq!(error.parameter(), "label");
+        assert_eq!(error.to_string(), "the parameter 'label' is not valid UTF-8");
+    }
+
+    #[test]
+    fn amount_sat() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=20.3".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_sat(), Some(2_030_000_000));
+        assert_eq!(Uri::<'_, _>::new(test_address()).amount_sat(), None);
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn amount_btc_decimal() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.00000001"
+            .parse::<Uri<'_, _>>()
+            .unwrap();
+        assert_eq!(uri.amount_btc_decimal().unwrap().to_string(), "0.00000001");
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=20.3".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_btc_decimal(), Some(rust_decimal::Decimal::new(203, 1)));
+    }
+
+    #[test]
+    fn empty_query() {
+        for input in [
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?",
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?#foo",
+        ] {
+            let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+            assert!(uri.amount.is_none() && uri.label.is_none() && uri.message.is_none());
+            assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd");
+            assert_eq!(uri.original(), Some(input));
+        }
+    }
+
+    #[test]
+    fn to_string_with_order() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=50&label=Luke-Jr&message=Donation%20for%20project%20xyz";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.to_string_with_order(&["message", "foo", "amount"]),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message=Donation%20for%20project%20xyz&amount=50&label=Luke-Jr"
+        );
+        assert_eq!(uri.to_string_with_order(&[]), input);
+    }
+
+    #[test]
+    fn reserialize_minified() {
+        let input = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1.000&label=&message=%41b";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.reserialize_minified(),
+            "bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?amount=1&message=Ab"
+        );
+
+        let uri = Uri::<'_, _>::new(test_address()).with_label(crate::Label::new("").unwrap());
+        assert_eq!(uri.reserialize_minified(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd");
+    }
+
+    #[test]
+    fn as_ref_address() {
+        fn script<A: AsRef<bitcoin::Address>>(address: A) -> bitcoin::ScriptBuf {
+            address.as_ref().script_pubkey()
+        }
+
+        let uri = Uri::<'_, _>::new(test_address());
+        assert_eq!(script(&uri), test_address().script_pubkey());
+    }
+
+    #[test]
+    fn invalid_address_character() {
+        let cases = [
+            ("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd&amount=1", b'&', 41),
+            ("bitcoin:amount=1", b'=', 14),
+            ("bitcoin:1andreas3bat\0LhQa2FawWjeyjCqyBzypd", 0, 20),
+            ("bitcoin:\n?amount=1", b'\n', 8),
+        ];
+        for (input, byte, position) in cases {
+            match input.parse::<Uri<'_, _>>().unwrap_err() {
+                crate::de::Error::Uri(error) => {
+                    assert_eq!(error.position(), Some(position), "{}", input);
+                    assert_eq!(
+                        error.to_string(),
+                        alloc::format!("the address contains invalid character 0x{:02x} at {}", byte, position)
+                    );
+                },
+                crate::de::Error::Extras(never) => match never {},
+            }
+        }
+    }
+
+    #[test]
+    fn params_map() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message=%FF%00&label=Luke-Jr&amount=50";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let map = uri.params_map();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["amount"], "50");
+        assert_eq!(map["label"], "Luke-Jr");
+        assert_eq!(map["message"], "hex:ff00");
+    }
+
+    #[test]
+    fn amount_in_sats() {
+        let options = crate::ParseOptions::new().amount_in_sats(true);
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1000";
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap();
+        assert_eq!(uri.amount_sat(), Some(1000));
+        assert_eq!(Uri::<'_, _>::try_from(input).unwrap().amount_sat(), Some(100_000_000_000));
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1000.0";
+        match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.position(), Some(53)),
+            crate::de::Error::Extras(never) => match never {},
+        }
+    }
+
+    #[test]
+    fn required_params() {
+        use crate::extras::UnknownParams;
+
+        let mut extras = UnknownParams::new();
+        extras.push("req-foo", "1");
+        extras.push("bar", "2");
+        extras.push("req", "3");
+        let uri = Uri::<'_, _, _>::with_extras(test_address(), extras);
+        let required = uri
+            .required_params()
+            .map(|(key, value)| (key, alloc::string::String::try_from(value.clone()).unwrap()))
+            .collect::<alloc::vec::Vec<_>>();
+        assert_eq!(required, [("req-foo", "1".to_string())]);
+    }
+
+    #[test]
+    fn plus_as_space() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke+Jr%2B";
+        let uri = Uri::<'_, _>::try_from(input).unwrap().assume_checked();
+        assert_eq!(<Cow<'_, str>>::try_from(uri.label.clone().unwrap()).unwrap(), "Luke+Jr+");
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke+Jr+");
+
+        let options = crate::ParseOptions::new().plus_as_space(true);
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert_eq!(<Cow<'_, str>>::try_from(uri.label.clone().unwrap()).unwrap(), "Luke Jr+");
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Luke%20Jr+");
+    }
+
+    #[test]
+    fn lossy_parse() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=abc&label=ok&flag&message=%ZZ";
+        let (uri, fixups) = Uri::<'_, _>::try_from_uri_str_lossy(input).unwrap();
+        assert_eq!(uri.amount, None);
+        assert_eq!(<Cow<'_, str>>::try_from(uri.label.unwrap()).unwrap(), "ok");
+        assert!(uri.message.is_none());
+        let positions = fixups.iter().map(|fixup| fixup.position()).collect::<alloc::vec::Vec<_>>();
+        assert_eq!(positions, [42, 62, 67]);
+        assert!(fixups[1].to_string().starts_with("dropped parameter at 62: "));
+
+        match Uri::<'_, _>::try_from_uri_str_lossy("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=abc&req-x=1").unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.to_string(), "the URI contains unknown required parameter 'req-x'"),
+            crate::de::Error::Extras(never) => match never {},
+        }
+        assert!(Uri::<'_, _>::try_from_uri_str_lossy("bitcoin:invalid?amount=1").is_err());
+    }
+
+    #[test]
+    fn address_type() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.address_type(), Some(bitcoin::AddressType::P2pkh));
+        let uri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.address_type(), Some(bitcoin::AddressType::P2wpkh));
+        let uri = "bitcoin:bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+            .parse::<Uri<'_, _>>()
+            .unwrap();
+        assert_eq!(uri.address_type(), Some(bitcoin::AddressType::P2tr));
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary")]
+    fn arbitrary_roundtrip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = (0..=255u8).cycle().take(4096).collect::<alloc::vec::Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..16 {
+            let uri = Uri::<'_, _>::arbitrary(&mut u).unwrap();
+            let string = uri.to_string();
+            let parsed = Uri::<'_, _>::try_from(&*string).unwrap().assume_checked();
+            assert_eq!(parsed, uri);
+        }
+    }
+
+    #[test]
+    fn amount_grammar() {
+        // the value of the amount starts at 49
+        let cases = [
+            ("", "the amount is empty at 49"),
+            (".5", "the amount is missing digits before the decimal point at 49"),
+            (".", "the amount is missing digits before the decimal point at 49"),
+            ("5.", "the amount is missing digits after the decimal point at 50"),
+            ("+5", "the amount must not have a sign at 49"),
+            ("-5", "the amount must not have a sign at 49"),
+            ("5+", "the amount contains invalid character '+' at 50"),
+            ("5e2", "the amount must not use scientific notation at 50"),
+            ("1.0e0", "the amount must not use scientific notation at 52"),
+            ("1.5E-3", "the amount must not use scientific notation at 52"),
+            ("e5", "the amount contains invalid character 'e' at 49"),
+            ("0x10", "the amount must not be hexadecimal at 50"),
+            ("0X1f", "the amount must not be hexadecimal at 50"),
+            ("1,5", "the amount uses decimal comma instead of decimal point at 50"),
+            (",5", "the amount contains invalid character ',' at 49"),
+            ("1x", "the amount contains invalid character 'x' at 50"),
+            ("1.2.3", "the amount contains invalid character '.' at 52"),
+            (" 1", "the amount contains invalid character '%' at 49"),
+            ("123456789", "the amount has more than 8 digits before the decimal point at 57"),
+            ("1.123456789", "the amount has more than 8 decimal places at 59"),
+            ("1.100000001", "the amount has more than 8 decimal places at 59"),
+            ("1.1000000000010", "the amount has more than 8 decimal places at 62"),
+            ("99999999", "the amount exceeds 21 million BTC at 49"),
+            ("21000000.00000001", "the amount exceeds 21 million BTC at 49"),
+        ];
+        for (amount, expected) in cases {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount={}", amount.replace(' ', "%20"));
+            match Uri::<'_, _>::try_from(&*input).unwrap_err() {
+                crate::de::Error::Uri(error) => assert_eq!(error.to_string(), expected, "{}", amount),
+                crate::de::Error::Extras(never) => match never {},
+            }
+        }
+        let options = crate::de::ParseOptions::new().amount_in_sats(true);
+        match Uri::<'_, _>::parse_with_options("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2100000000000001", &options).unwrap_err() {
+            crate::de::Error::Uri(error) => assert!(error.is_amount_above_max_money()),
+            crate::de::Error::Extras(never) => match never {},
+        }
+
+        let accepted = [
+            ("0", 0),
+            ("00000001", 100_000_000),
+            ("0.00000001", 1),
+            ("21000000.00000000", 2_100_000_000_000_000),
+            ("1.5", 150_000_000),
+            ("1.100000000", 110_000_000),
+            ("0.000000010000", 1),
+        ];
+        for (amount, sats) in accepted {
+            let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount={}", amount);
+            assert_eq!(Uri::<'_, _>::try_from(&*input).unwrap().amount_sat(), Some(sats), "{}", amount);
+        }
+    }
+
+    #[test]
+    fn parse_with_callback() {
+        use crate::de::ParamKind;
+
+        let mut order_id = None;
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&order=4%32&foo=bar";
+        let uri = Uri::parse_with(input, |key, value| {
+            if key != "order" {
+                return Ok(ParamKind::Unknown);
+            }
+            order_id = Some(<Cow<'_, str>>::try_from(value).map_err(|_| "invalid UTF-8")?);
+            Ok::<_, &str>(ParamKind::Known)
+        })
+        .unwrap();
+        assert_eq!(uri.amount_sat(), Some(100_000_000));
+        assert_eq!(order_id.as_deref(), Some("42"));
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?order=%FF";
+        let result = Uri::parse_with(input, |_, value| {
+            <Cow<'_, str>>::try_from(value).map(|_| ParamKind::Known).map_err(|_| "invalid UTF-8")
+        });
+        assert!(matches!(result, Err(crate::de::Error::Extras("invalid UTF-8"))));
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-foo=1";
+        assert!(matches!(
+            Uri::parse_with(input, |_, _| Ok::<_, ()>(ParamKind::Unknown)),
+            Err(crate::de::Error::Uri(_))
+        ));
+    }
+
+    #[test]
+    fn checked_to_string() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.checked_to_string(bitcoin::Network::Bitcoin).unwrap(), input);
+        let error = uri.checked_to_string(bitcoin::Network::Testnet).unwrap_err();
+        assert_eq!(error.network(), bitcoin::Network::Testnet);
+        assert_eq!(error.to_string(), "the address is not valid for network testnet");
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn base64url_roundtrip() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message=Rent%20%2F%20May&amount=1.5&label=%E2%82%BF";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let encoded = uri.to_base64url();
+        assert!(encoded.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'));
+        let decoded = Uri::<'_, _>::from_base64url(&encoded).unwrap().assume_checked();
+        assert_eq!(decoded.to_string(), uri.to_canonical_string());
+        assert_eq!(decoded, uri);
+
+        assert!(matches!(
+            Uri::<'_, _>::from_base64url("Yml0Y29pbjo="),
+            Err(crate::de::Base64UrlError::Base64(_))
+        ));
+        assert!(matches!(
+            Uri::<'_, _>::from_base64url("Yml0Y29pbjo"),
+            Err(crate::de::Base64UrlError::Uri(_))
+        ));
+    }
+
+    #[test]
+    fn param_getters() {
+        use crate::extras::UnknownParams;
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1.50&label=Caf%C3%A9&foo=%FF&foo=2";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap();
+        assert_eq!(uri.param("amount").as_deref(), Some(&b"1.5"[..]));
+        assert_eq!(uri.param_str("label").unwrap().unwrap(), "Café");
+        assert_eq!(uri.param("foo").as_deref(), Some(&[0xFF][..]));
+        assert!(uri.param_str("foo").unwrap().is_err());
+        assert_eq!(uri.param("message"), None);
+        assert_eq!(uri.param("bar"), None);
+    }
+
+    #[test]
+    fn pathological_encoding() {
+        let escapes = "%41".repeat(20_000);
+        let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message={}", escapes);
+        let uri = Uri::<'_, _>::try_from(&*input).unwrap();
+        let message = uri.message.clone().unwrap();
+        assert_eq!(message.decoded_bytes().len(), 20_000);
+        assert_eq!(uri.into_owned().message.unwrap().decoded_bytes().len(), 20_000);
+
+        let input = alloc::format!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message={}", "%41".repeat(30_000));
+        match Uri::<'_, _>::try_from(&*input).unwrap_err() {
+            crate::de::Error::Uri(error) => assert_eq!(error.to_string(), "the URI is 90050 bytes long, the maximum is 65536"),
+            crate::de::Error::Extras(never) => match never {},
+        }
+        let options = crate::ParseOptions::new().max_length(100_000);
+        assert!(Uri::<'_, _>::parse_with_options(&input, &options).is_ok());
+        let options = crate::ParseOptions::new().max_length(49);
+        assert!(Uri::<'_, _>::parse_with_options("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1", &options).is_err());
+    }
+
+    #[test]
+    fn max_params() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a&message=b&foo=1&bar=2";
+        assert!(Uri::<'_, _>::try_from(input).is_ok());
+        let options = crate::ParseOptions::new().max_params(5);
+        assert!(Uri::<'_, _>::parse_with_options(input, &options).is_ok());
+        let options = crate::ParseOptions::new().max_params(3);
+        match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => {
+                assert!(error.is_too_many_params());
+                assert_eq!(error.to_string(), "the URI has 5 parameters, the maximum is 3");
+            },
+            crate::de::Error::Extras(never) => match never {},
+        }
+    }
+
+    #[test]
+    fn partial_comparisons() {
+        let parse = |input: &str| input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let a = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Alice&message=Rent");
+        let b = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Bob&amount=1.0&message=Rent");
+        let c = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+        let d = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2");
+        assert_ne!(a, b);
+        assert!(a.eq_ignore_label(&b));
+        assert!(!a.eq_ignore_label(&c));
+        assert!(a.eq_ignore_annotations(&c));
+        assert!(!c.eq_ignore_annotations(&d));
+        assert!(c.eq_ignoring(&d, &["amount"]));
+
+        let parse = |input: &'static str| {
+            use core::convert::TryFrom;
+            Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked()
+        };
+        let uris = [
+            parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1&bar=2"),
+            parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?bar=2&foo=%31"),
+            parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1&bar=3"),
+            parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=1"),
+        ];
+        for a in &uris {
+            for b in &uris {
+                assert_eq!(a.eq_ignoring(b, &[]), a == b, "{} {}", a, b);
+            }
+        }
+        assert!(uris[0].eq_ignoring(&uris[2], &["bar"]));
+        assert!(uris[0].eq_ignoring(&uris[3], &["bar"]));
+    }
+
+    #[test]
+    fn display_address_case() {
+        use crate::ser::{AddressCase, DisplayOptions};
+
+        let lower = DisplayOptions::new();
+        let upper = DisplayOptions::new().address_case(AddressCase::Upper);
+
+        let input = "bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?label=Foo";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.display_with(&lower).to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?label=Foo"
+        );
+        assert_eq!(
+            uri.display_with(&upper).to_string(),
+            "bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?label=Foo"
+        );
+        assert_eq!(alloc::format!("{:#}", uri.display_with(&lower)), uri.display_with(&lower).to_string());
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Foo";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.display_with(&lower).to_string(), input);
+        assert_eq!(uri.display_with(&upper).to_string(), input);
+    }
+
+    #[test]
+    fn display_custom_scheme() {
+        use crate::ser::{AddressCase, DisplayOptions};
+
+        let input = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1&label=Foo";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let options = DisplayOptions::new().scheme("acme-pay");
+        assert_eq!(
+            uri.to_string_with(&options),
+            "acme-pay:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1&label=Foo"
+        );
+        let options = options.address_case(AddressCase::Upper);
+        assert_eq!(
+            uri.to_string_with(&options),
+            "acme-pay:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?amount=1&label=Foo"
+        );
+        assert_eq!(uri.to_string_with(&DisplayOptions::new()), input);
+    }
+
+    #[test]
+    fn into_parts() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr";
+        let (address, amount, label, message, extras) = Uri::<'_, _>::try_from(input).unwrap().into_parts();
+        assert_eq!(address.assume_checked(), test_address());
+        assert_eq!(amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(<Cow<'_, str>>::try_from(label.unwrap()).unwrap(), "Luke-Jr");
+        assert!(message.is_none());
+        assert_eq!(extras, crate::NoExtras);
+    }
+
+    #[test]
+    fn from_parts() {
+        use crate::extras::UnknownParams;
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr&foo=bar";
+        let (address, amount, label, message, extras) = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().into_parts();
+        let uri = Uri::from_parts(address.clone(), amount, label, message, extras).unwrap();
+        assert_eq!(uri.assume_checked().to_string(), input);
+
+        let error = Uri::<'_, _>::from_parts(address.clone(), Some(bitcoin::Amount::MAX), None, None, crate::NoExtras).unwrap_err();
+        assert_eq!(error.key(), None);
+        for key in ["", "a=b", "a&b", "label"] {
+            let mut extras = UnknownParams::new();
+            extras.push(key, "1");
+            let error = Uri::from_parts(address.clone(), None, None, None, extras).unwrap_err();
+            assert_eq!(error.key(), Some(key));
+        }
+    }
+
+    #[test]
+    fn web_prefix() {
+        let input = "web+bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        assert!(Uri::<'_, _>::try_from(input).is_err());
+        let options = retaining().allow_web_prefix(true);
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert_eq!(uri.amount_sat(), Some(100_000_000));
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+        assert_eq!(uri.original(), None);
+        assert!(uri.canonicalization_diff().is_empty());
+        assert!(Uri::<'_, _>::parse_with_options("WEB+BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd", &options).is_ok());
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd";
+        assert_eq!(Uri::<'_, _>::parse_with_options(input, &options).unwrap().original(), Some(input));
+        assert!(Uri::<'_, _>::parse_with_options("web+", &options).is_err());
+    }
+
+    #[test]
+    fn display_amount_precision() {
+        use core::fmt::Write;
+        use crate::ser::DisplayOptions;
+
+        let options = DisplayOptions::new();
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.12345678&label=Foo";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        // the amount is never changed by rounding
+        let mut output = alloc::string::String::new();
+        assert!(write!(output, "{:.4}", uri.display_with(&options)).is_err());
+        assert!(write!(output, "{:.0}", uri.display_with(&options)).is_err());
+        assert_eq!(alloc::format!("{:.8}", uri.display_with(&options)), input);
+        assert_eq!(uri.display_with(&options).to_string(), input);
+        assert_eq!(alloc::format!("{:.4}", uri), input);
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1.5";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(alloc::format!("{:.4}", uri.display_with(&options)), input);
+        assert_eq!(alloc::format!("{:.1}", uri.display_with(&options)), input);
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.00000051"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert!(write!(output, "{:.6}", uri.display_with(&options)).is_err());
+    }
+
+    #[test]
+    fn redacted() {
+        let input = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.5&label=Luke&message=Donation&foo=bar";
+        let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.redacted(), "bitcoin:bc1qar...5mdq?amount=0.5&label=***&message=***&foo=***");
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.redacted(), "bitcoin:1andre...zypd");
+    }
+
+    #[test]
+    fn escapes_uppercase() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%3db%c3%a9";
+        let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap();
+        assert_eq!(uri.original(), Some(input));
+        let uri = uri.assume_checked();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%3Db%C3%A9");
+        assert!(!uri.is_canonical());
+
+        let options = retaining().normalize_escape_case(true);
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap();
+        assert_eq!(uri.original(), Some(input));
+        let uri = uri.assume_checked();
+        assert!(uri.is_canonical());
+        assert!(uri.canonicalization_diff().is_empty());
+
+        // only the case of escapes is ignored
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41%3db&amount=1.000";
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert!(!uri.is_canonical());
+        assert_eq!(
+            uri.canonicalization_diff(),
+            [
+                crate::ser::CanonChange::SchemeCaseNormalized,
+                crate::ser::CanonChange::RedundantEncodingRemoved,
+                crate::ser::CanonChange::AmountTrailingZerosStripped,
+                crate::ser::CanonChange::ParamReordered,
+            ]
+        );
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%3Db";
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap();
+        assert_eq!(uri.original(), Some(input));
+    }
+
+    #[test]
+    fn clone_with_network_unchecked() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Foo";
+        let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+        let unchecked = uri.clone_with_network_unchecked();
+        assert_eq!(unchecked.original(), Some(input));
+        assert!(unchecked.clone().require_network(bitcoin::Network::Testnet).is_err());
+        assert_eq!(unchecked.require_network(bitcoin::Network::Bitcoin).unwrap(), uri);
+        assert_eq!(uri.clone().into_unchecked().assume_checked(), uri);
+    }
+
+    #[test]
+    fn display_amount_form() {
+        use crate::ser::{AmountForm, DisplayOptions};
+
+        let canonical = DisplayOptions::new();
+        let faithful = DisplayOptions::new().amount_form(AmountForm::Faithful);
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.0100&label=Foo";
+        let uri = Uri::<'_, _>::parse_with_options(input, &retaining()).unwrap().assume_checked();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.01&label=Foo");
+        assert_eq!(uri.display_with(&canonical).to_string(), uri.to_string());
+        assert_eq!(uri.display_with(&canonical).amount(), Some("0.01"));
+        assert_eq!(uri.display_with(&faithful).to_string(), input);
+        assert_eq!(uri.display_with(&faithful).amount(), Some("0.0100"));
+        assert_eq!(
+            alloc::format!("{:.2}", uri.display_with(&faithful)),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.01&label=Foo"
+        );
+
+        let mut modified = uri.clone();
+        modified.amount = Some(bitcoin::Amount::from_sat(2_000_000));
+        assert_eq!(
+            modified.display_with(&faithful).to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.02&label=Foo"
+        );
+
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(
+            uri.display_with(&faithful).to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.01&label=Foo"
+        );
+
+        let options = crate::de::ParseOptions::new().amount_in_sats(true);
+        let uri = Uri::<'_, _>::parse_with_options("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1000", &options).unwrap();
+        assert_eq!(
+            uri.assume_checked().display_with(&faithful).to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.00001"
+        );
+    }
+
+    #[test]
+    fn intent() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr&message=%FF%41&pj=https://example.com";
+        let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let intent = uri.intent();
+        assert_eq!(intent.address, test_address());
+        assert_eq!(intent.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(intent.label.as_deref(), Some("Luke-Jr"));
+        assert_eq!(intent.message.as_deref(), Some("\u{FFFD}A"));
+        assert!(intent.payjoin);
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd".parse::<Uri<'_, _>>().unwrap();
+        let intent = uri.intent();
+        assert_eq!(intent.amount, None);
+        assert!(!intent.payjoin);
+    }
+
+    /// Errors are only displayed using `core::fmt` without `std` so this checks they carry the
+    /// details, which `std::error::Error::source` provides otherwise.
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn errors_display_without_std() {
+        match Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%ZZ").unwrap_err() {
+            crate::de::Error::Uri(error) => assert!(error.to_string().starts_with("can not percent-decode parameter label: ")),
+            crate::de::Error::Extras(never) => match never {},
+        }
+        let uri = Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1")
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+    }
+
+    #[test]
+    fn amount_required() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_required(), Ok(bitcoin::Amount::ONE_BTC));
+        let uri = uri.without_amount();
+        assert_eq!(uri.amount_required(), Err(crate::MissingAmount));
+        assert_eq!(crate::MissingAmount.to_string(), "the URI is missing the required amount");
+    }
+
+    #[test]
+    fn amount_or_zero() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_or_zero(), bitcoin::Amount::ONE_BTC);
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_or_zero(), bitcoin::Amount::ZERO);
+        let uri = uri.without_amount();
+        assert_eq!(uri.amount_or_zero(), bitcoin::Amount::ZERO);
+        assert!(uri.amount.is_none());
+    }
+
+    #[test]
+    fn display_omit_zero_amount() {
+        use crate::ser::DisplayOptions;
+
+        let omit = DisplayOptions::new().omit_zero_amount(true);
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0&label=Foo";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.to_string(), input);
+        assert_eq!(uri.display_with(&DisplayOptions::new()).to_string(), input);
+        assert_eq!(uri.display_with(&omit).to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Foo");
+        assert_eq!(uri.display_with(&omit).amount(), None);
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.1";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.display_with(&omit).to_string(), input);
+    }
+
+    /// Deliberately naive parser of valid URIs used as the reference in `differential`.
+    #[cfg(feature = "test-utils")]
+    #[allow(clippy::type_complexity)]
+    fn reference_parse(input: &str) -> (String, Option<u64>, Option<String>, Option<String>, Vec<(String, String)>) {
+        fn decode(value: &str) -> String {
+            let mut bytes = Vec::new();
+            let mut iter = value.bytes();
+            while let Some(byte) = iter.next() {
+                if byte == b'%' {
+                    let high = char::from(iter.next().unwrap()).to_digit(16).unwrap() as u8;
+                    let low = char::from(iter.next().unwrap()).to_digit(16).unwrap() as u8;
+                    bytes.push(high * 16 + low);
+                } else {
+                    bytes.push(byte);
+                }
+            }
+            String::from_utf8(bytes).unwrap()
+        }
+
+        let rest = &input["bitcoin:".len()..];
+        let rest = rest.split('#').next().unwrap();
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (mut amount, mut label, mut message, mut extras) = (None, None, None, Vec::new());
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap();
+            match key {
+                "amount" => {
+                    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+                    let whole = whole.parse::<u64>().unwrap();
+                    let fraction = alloc::format!("{:0<8}", fraction).parse::<u64>().unwrap();
+                    amount = Some(whole * 100_000_000 + fraction);
+                },
+                "label" => label = Some(decode(value)),
+                "message" => message = Some(decode(value)),
+                _ => extras.push((key.into(), decode(value))),
+            }
+        }
+        (address.into(), amount, label, message, extras)
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn differential() {
+        let addresses = ["1andreas3batLhQa2FawWjeyjCqyBzypd", "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"];
+        let params = [
+            "amount=1",
+            "amount=0.00000001",
+            "amount=20999999.9769",
+            "amount=0.10",
+            "label=Luke-Jr",
+            "label=",
+            "label=%C3%A9%20%26%3D",
+            "message=Donation%20for%20project%20xyz",
+            "message=a+b",
+            "foo=bar",
+            "foo=",
+            "pj=https://example.com/pj?x%3D1",
+            "amount=2",
+        ];
+        let mut corpus = Vec::new();
+        for address in addresses {
+            corpus.push(alloc::format!("bitcoin:{}", address));
+            corpus.push(alloc::format!("bitcoin:{}?", address));
+            for (i, first) in params.iter().enumerate() {
+                corpus.push(alloc::format!("bitcoin:{}?{}", address, first));
+                for second in params.iter().skip(i + 1).step_by(2) {
+                    corpus.push(alloc::format!("bitcoin:{}?{}&{}#fragment", address, first, second));
+                    for third in params.iter().take(3) {
+                        corpus.push(alloc::format!("bitcoin:{}?{}&{}&{}", address, third, first, second));
+                    }
+                }
+            }
+        }
+        for input in &corpus {
+            let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input.as_str())
+                .unwrap_or_else(|error| panic!("failed to parse {}: {}", input, error))
+                .assume_checked();
+            let text = |param: &Option<crate::Param<'_>>| param.clone().map(|param| String::try_from(param).unwrap());
+            let extras = uri
+                .extras
+                .iter()
+                .map(|(key, value)| (String::from(key), String::try_from(value.clone()).unwrap()))
+                .collect::<Vec<_>>();
+            let actual = (uri.address.to_string(), uri.amount_sat(), text(&uri.label), text(&uri.message), extras);
+            assert_eq!(actual, reference_parse(input), "{}", input);
+
+            let displayed = uri.to_string();
+            let reparsed = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(displayed.as_str())
+                .unwrap()
+                .assume_checked();
+            assert_eq!(reparsed.to_string(), displayed, "{}", input);
+        }
+    }
+
+    #[test]
+    fn set_network_unchecked_address() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Order%2042";
+        let uri = input.parse::<Uri<'_, _>>().unwrap();
+        let fresh = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".parse::<bitcoin::Address<_>>().unwrap();
+        let uri = uri.set_network_unchecked_address(fresh.clone(), bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1&label=Order%2042"
+        );
+
+        let error = uri.set_network_unchecked_address(fresh, bitcoin::Network::Testnet).unwrap_err();
+        assert_eq!(error.network(), bitcoin::Network::Testnet);
+    }
+
+    #[test]
+    fn with_label_and_message() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=old"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let uri = uri.with_label_and_message(crate::Label::new("Order 42").unwrap(), crate::Message::new("Two coffees").unwrap());
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Order%2042&message=Two%20coffees"
+        );
+    }
+
+    #[test]
+    fn amountless_label_and_message() {
+        let address = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+            .parse::<bitcoin::Address<_>>()
+            .unwrap()
+            .assume_checked();
+        let uri = Uri::<'_, _>::new(address).with_label_and_message(
+            crate::Label::new("Donations & tips").unwrap(),
+            crate::Message::new("Pay what you want: 100% goes to=the project").unwrap(),
+        );
+        let string = uri.to_string();
+        assert_eq!(
+            string,
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?label=Donations%20%26%20tips&message=Pay%20what%20you%20want:%20100%25%20goes%20to%3Dthe%20project"
+        );
+        let parsed = Uri::<'_, _>::try_from(&*string).unwrap().assume_checked();
+        assert_eq!(parsed.amount, None);
+        assert_eq!(parsed, uri);
+
+        let only_message = Uri::<'_, _>::new(uri.address.clone()).with_message(crate::Message::new("thanks").unwrap());
+        assert_eq!(
+            only_message.to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?message=thanks"
+        );
+        let only_label = Uri::<'_, _>::new(uri.address.clone()).with_label(crate::Label::new("shop").unwrap());
+        assert_eq!(only_label.to_string(), "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?label=shop");
+    }
+
+    #[test]
+    fn assume_scheme() {
+        use crate::de::ParseOptions;
+
+        let options = ParseOptions::new().assume_scheme(true).retain_original(true);
+        let input = "1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::from_btc(1.0).unwrap()));
+        assert_eq!(uri.original(), None);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+
+        let uri = Uri::<'_, _>::parse_with_options("1andreas3batLhQa2FawWjeyjCqyBzypd", &options).unwrap();
+        assert_eq!(uri.amount, None);
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        assert_eq!(Uri::<'_, _>::parse_with_options(input, &options).unwrap().original(), Some(input));
+        assert!(Uri::<'_, _>::parse_with_options("litecoin:1andreas3batLhQa2FawWjeyjCqyBzypd", &options).is_err());
+        assert!(Uri::<'_, _>::parse_with_options("1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1", &ParseOptions::new()).is_err());
+    }
+
+    #[test]
+    fn lenient_options() {
+        use crate::de::ParseOptions;
+
+        let input = "web+bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1;label=a+b%2c";
+        assert!(Uri::<'_, _>::parse_with_options(input, &ParseOptions::new()).is_err());
+        let uri = Uri::<'_, _>::parse_with_options(input, &ParseOptions::lenient())
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b,");
+
+        let uri = Uri::<'_, _>::parse_with_options("1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1", &ParseOptions::lenient()).unwrap();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+    }
+
+    #[test]
+    fn had_encoded_params() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%20b".parse::<Uri<'_, _>>().unwrap();
+        assert!(uri.had_encoded_params());
+        assert!(uri.assume_checked().without_amount().had_encoded_params());
+        let uri = Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=ab").unwrap();
+        assert!(!uri.had_encoded_params());
+        assert!(!Uri::<'_, _>::new(crate::tests::test_address()).had_encoded_params());
+    }
+
+    #[test]
+    fn clone() {
+        use bitcoin::address::{NetworkChecked, NetworkUnchecked};
+        use crate::extras::{AssetExtras, Bip72Extras, CallbackExtras, ExpiryExtras, FeeExtras, LightningExtras, PayjoinExtras, UnknownParams};
+
+        fn assert_clone<T: Clone>() {}
+        fn assert_clone_uri<T: Clone>() {
+            assert_clone::<Uri<'_, NetworkChecked, T>>();
+            assert_clone::<Uri<'_, NetworkUnchecked, T>>();
+        }
+        assert_clone_uri::<crate::NoExtras>();
+        assert_clone_uri::<UnknownParams<'_>>();
+        assert_clone_uri::<AssetExtras>();
+        assert_clone_uri::<CallbackExtras>();
+        assert_clone_uri::<Bip72Extras>();
+        assert_clone_uri::<ExpiryExtras>();
+        assert_clone_uri::<FeeExtras>();
+        assert_clone_uri::<LightningExtras>();
+        assert_clone_uri::<PayjoinExtras>();
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%20b&foo=bar";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::parse_with_options(input, &retaining()).unwrap();
+        let clone = uri.clone();
+        assert_eq!(clone.original(), Some(input));
+        assert_eq!(clone.assume_checked().to_string(), uri.assume_checked().to_string());
+    }
+
+    #[test]
+    fn unmangle_deep_link() {
+        use crate::de::unmangle_deep_link;
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&amp;label=a%2520b&amp;message=c";
+        let unmangled = unmangle_deep_link(input);
+        assert_eq!(unmangled, "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b&message=c");
+        let uri = unmangled.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::from_btc(1.0).unwrap()));
+        assert_eq!(Cow::try_from(uri.label.unwrap()).unwrap(), "a b");
+        assert_eq!(Cow::try_from(uri.message.unwrap()).unwrap(), "c");
+
+        // collapsed only once
+        assert_eq!(unmangle_deep_link("bitcoin:x?label=%252520"), "bitcoin:x?label=%2520");
+        assert_eq!(unmangle_deep_link("bitcoin:x?label=%25zz&amp"), "bitcoin:x?label=%25zz&amp");
+        assert!(matches!(unmangle_deep_link("bitcoin:x?a=%25&b=%20"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn strip_invisible() {
+        use crate::de::strip_invisible;
+
+        let input = "\u{FEFF}bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1";
+        assert!(input.parse::<Uri<'_, _>>().is_err());
+        let stripped = strip_invisible(input);
+        assert!(matches!(stripped, Cow::Borrowed("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1")));
+        let uri = stripped.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+
+        let input = "bit\u{200B}coin:1andreas3bat\u{200B}LhQa2FawWjeyjCqyBzypd?amount=1&la\u{2060}bel=a";
+        assert_eq!(strip_invisible(input), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a");
+        assert!(matches!(strip_invisible("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd"), Cow::Borrowed(_)));
+
+        // joiners are part of emoji sequences in unencoded values
+        let input = "bitcoin:1andreas3bat\u{200B}LhQa2FawWjeyjCqyBzypd?label=\u{1F468}\u{200D}\u{1F469}&amount=1#\u{200C}";
+        let stripped = strip_invisible(input);
+        assert_eq!(
+            stripped,
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=\u{1F468}\u{200D}\u{1F469}&amount=1#\u{200C}"
+        );
+        let uri = stripped.parse::<Uri<'_, _>>().unwrap();
+        let label: Cow<'_, str> = uri.label.unwrap().try_into().unwrap();
+        assert_eq!(label, "\u{1F468}\u{200D}\u{1F469}");
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=\u{1F468}\u{200D}\u{1F469}";
+        assert!(matches!(strip_invisible(input), Cow::Borrowed(_)));
+    }
+
+    #[cfg(feature = "non-compliant-bytes")]
+    #[test]
+    fn encode_param() {
+        use crate::extras::UnknownParams;
+
+        let value = crate::Param::encode(b"a&b=c d\xff");
+        let extras = UnknownParams::new().param("data", value).unwrap();
+        let uri = Uri::from_parts(test_address(), None, None, None, extras).unwrap();
+        let string = uri.to_string();
+        assert_eq!(string, "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?data=a%26b%3Dc%20d%FF");
+        let parsed = Uri::<'_, _, UnknownParams<'_>>::try_from(string.as_str()).unwrap();
+        assert_eq!(parsed.extras.get("data").unwrap().decoded_bytes(), &b"a&b=c d\xff"[..]);
+    }
+
+    #[test]
+    fn amount_display_btc_string() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.0100".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_display_btc_string().as_deref(), Some("0.01"));
+        let uri = uri.assume_checked();
+        assert!(uri
+            .to_string()
+            .ends_with(&alloc::format!("amount={}", uri.amount_display_btc_string().unwrap())));
+        assert_eq!(uri.without_amount().amount_display_btc_string(), None);
+    }
+
+    #[test]
+    fn amount_display_in() {
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_display_in(bitcoin::Denomination::Bitcoin), uri.amount_display_btc_string());
+        assert_eq!(uri.amount_display_in(bitcoin::Denomination::Satoshi).as_deref(), Some("200000000"));
+        assert_eq!(uri.amount_display_in(bitcoin::Denomination::MilliBitcoin).as_deref(), Some("2000"));
+        assert_eq!(uri.without_amount().amount_display_in(bitcoin::Denomination::Bitcoin), None);
+    }
+
+    #[test]
+    fn to_string_with() {
+        use crate::ser::DisplayOptions;
+
+        let uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0&label=a~b"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert_eq!(uri.to_string_with(&DisplayOptions::new()), uri.to_string());
+        assert_eq!(
+            uri.to_string_with(&DisplayOptions::new().omit_zero_amount(true)),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a~b"
+        );
+    }
+
+    #[cfg(feature = "encode-set")]
+    #[test]
+    fn extra_encode_set() {
+        use crate::ser::{AsciiSet, DisplayOptions, CONTROLS};
+
+        const STRICT: &AsciiSet = &CONTROLS.add(b'~').add(b'!').add(b'.').add(b'a');
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.5&label=a~b!c%20d&message=%C3%A1!&foo=x~";
+        let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let options = DisplayOptions::new().extra_encode_set(STRICT);
+        assert_eq!(
+            uri.to_string_with(&options),
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.5&label=a%7Eb%21c%20d&message=%C3%A1%21&foo=x%7E"
+        );
+        let reparsed = Uri::<'_, _>::try_from(uri.to_string_with(&options)).unwrap().assume_checked();
+        assert_eq!(Cow::try_from(reparsed.label.unwrap()).unwrap(), "a~b!c d");
+    }
+
+    #[test]
+    fn truncated_percent_escape() {
+        let check = |input: &str, expected_position: usize| {
+            let error = match Uri::<'_, _>::try_from(input).unwrap_err() {
+                crate::de::Error::Uri(error) => error,
+                crate::de::Error::Extras(never) => match never {},
+            };
+            assert!(error.is_truncated_percent_escape(), "{}", input);
+            assert_eq!(error.position(), Some(expected_position), "{}", input);
+        };
+        check("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=ab%", 50);
+        check("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=ab%A", 50);
+        check("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?message=%&label=x", 50);
+        check("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?foo=%4", 46);
+
+        let error = match Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%G0").unwrap_err() {
+            crate::de::Error::Uri(error) => error,
+            crate::de::Error::Extras(never) => match never {},
+        };
+        assert!(!error.is_truncated_percent_escape());
+        assert!(Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41").is_ok());
+    }
+
+    #[test]
+    fn amount_sat_checked() {
+        let mut uri = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=21000000".parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(uri.amount_sat_checked().unwrap(), Some(bitcoin::Amount::MAX_MONEY.to_sat()));
+        uri.amount = Some(bitcoin::Amount::MAX_MONEY + bitcoin::Amount::ONE_SAT);
+        assert_eq!(
+            uri.amount_sat_checked().unwrap_err().amount(),
+            bitcoin::Amount::MAX_MONEY + bitcoin::Amount::ONE_SAT
+        );
+        uri.amount = None;
+        assert_eq!(uri.amount_sat_checked().unwrap(), None);
+
+        assert!("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=99999999".parse::<Uri<'_, _>>().is_err());
+    }
+
+    #[test]
+    fn canonicalization_diff() {
+        use crate::ser::CanonChange;
+
+        let diff = |input: &str| {
+            Uri::<'_, _>::parse_with_options(input, &retaining())
+                .unwrap()
+                .assume_checked()
+                .canonicalization_diff()
+        };
+        assert_eq!(diff("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b"), []);
+        assert_eq!(
+            diff("BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41%3d&amount=1.50"),
+            [
+                CanonChange::SchemeCaseNormalized,
+                CanonChange::RedundantEncodingRemoved,
+                CanonChange::EscapeCaseNormalized,
+                CanonChange::AmountTrailingZerosStripped,
+                CanonChange::ParamReordered,
+            ]
+        );
+        assert_eq!(
+            diff("bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?amount=01&message=\"#x"),
+            [
+                CanonChange::AddressNormalized,
+                CanonChange::AmountReformatted,
+                CanonChange::EncodingAdded,
+                CanonChange::FragmentRemoved,
+            ]
+        );
+        assert_eq!(diff("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?"), [CanonChange::EmptyQueryRemoved]);
+        let uri = Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=b&amount=1")
+            .unwrap()
+            .assume_checked();
+        for change in uri.canonicalization_diff() {
+            assert!(!uri.is_canonical(), "{}", change);
+        }
+        assert_eq!(uri.clone().without_amount().canonicalization_diff(), []);
+    }
+
+    #[test]
+    fn hash_canonical() {
+        use bitcoin::hashes::{sha256, Hash};
+
+        let parse = |input: &'static str| Uri::<'_, _>::try_from(input).unwrap().assume_checked();
+        let uri = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b");
+        let expected = sha256::Hash::hash(b"bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b").to_byte_array();
+        assert_eq!(uri.hash_canonical(), expected);
+        assert_eq!(
+            parse("BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?label=a%20%62&amount=1.0").hash_canonical(),
+            parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=a%20b").hash_canonical()
+        );
+        assert_ne!(uri.hash_canonical(), uri.clone().without_amount().hash_canonical());
+    }
+
+    #[test]
+    fn trailing_separator() {
+        use crate::de::{ParseOptions, Warning};
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&";
+        let uri = Uri::<'_, _, crate::extras::UnknownParams<'_>>::parse_with_options(input, &retaining()).unwrap();
+        assert!(uri.extras.is_empty());
+        let uri = uri.assume_checked();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+        assert_eq!(uri.canonicalization_diff(), [crate::ser::CanonChange::TrailingSeparatorRemoved]);
+        let (result, warnings) = Uri::<'_, _>::parse_with_warnings(input);
+        assert!(result.is_ok());
+        assert_eq!(warnings, [Warning::TrailingSeparator { position: 50 }]);
+        assert!(Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?&").is_ok());
+
+        assert!(Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&&").is_err());
+        let options = ParseOptions::new().reject_trailing_separator(true);
+        let error = match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => error,
+            crate::de::Error::Extras(never) => match never {},
+        };
+        assert_eq!(error.position(), Some(50));
+        assert!(Uri::<'_, _>::parse_with_options("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1", &options).is_ok());
+    }
+
+    #[test]
+    fn parse_for_network() {
+        use crate::de::ParseForNetworkError;
+
+        let uri = Uri::parse_for_network("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1", bitcoin::Network::Bitcoin).unwrap();
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1");
+
+        let testnet = "bitcoin:tb1qghfhmd4zh7ncpmxl3qzhmq566jk8ckq4gafnmg?amount=1";
+        assert!(Uri::parse_for_network(testnet, bitcoin::Network::Testnet).is_ok());
+        match Uri::parse_for_network(testnet, bitcoin::Network::Bitcoin).unwrap_err() {
+            ParseForNetworkError::WrongNetwork(error) => assert_eq!(error.network(), bitcoin::Network::Bitcoin),
+            error => panic!("unexpected error: {}", error),
+        }
+        match Uri::parse_for_network("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=x", bitcoin::Network::Bitcoin).unwrap_err() {
+            ParseForNetworkError::Parse(_) => (),
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn into_io_error() {
+        fn parse(input: &str) -> std::io::Result<Uri<'_, bitcoin::address::NetworkUnchecked>> {
+            Ok(Uri::try_from(input)?)
+        }
+
+        assert!(parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd").is_ok());
+        let error = parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=x").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid BIP21 URI");
+        let inner = error.into_inner().unwrap();
+        assert!(inner.downcast_ref::<crate::de::Error<core::convert::Infallible>>().is_some());
+    }
+
+    #[test]
+    fn amount_sign() {
+        let uri_error = |input: &'static str, options: crate::de::ParseOptions| match Uri::<'_, _>::parse_with_options(input, &options).unwrap_err() {
+            crate::de::Error::Uri(error) => error,
+            crate::de::Error::Extras(never) => match never {},
+        };
+        for options in [crate::de::ParseOptions::new(), crate::de::ParseOptions::new().amount_in_sats(true)] {
+            for input in [
+                "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=+1",
+                "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=-1",
+            ] {
+                let error = uri_error(input, options.clone());
+                assert!(error.is_amount_sign_not_allowed(), "{}", input);
+                assert_eq!(error.position(), Some(49));
+            }
+        }
+        let error = uri_error("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1e1", crate::de::ParseOptions::new());
+        assert!(!error.is_amount_sign_not_allowed());
+
+        let uri = Uri::<'_, _>::try_from("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1").unwrap();
+        assert_eq!(uri.amount_sat(), Some(100_000_000));
+    }
+
+    #[test]
+    fn normalize() {
+        let cases = [
+            ("BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd", "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd"),
+            (
+                "bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?message=%48i&amount=1.50000000&label=a%2fb",
+                "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1.5&label=a/b&message=Hi",
+            ),
+            (
+                "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?z=1&pj=https://example.com/%3Fa%3Db&",
+                "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?pj=https://example.com/?a%3Db&z=1",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(crate::de::normalize(input).unwrap(), expected, "{}", input);
+            assert_eq!(crate::de::normalize(expected).unwrap(), expected);
+        }
+        assert!(crate::de::normalize("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=x").is_err());
+    }
+
+    #[test]
+    fn address_type_hint() {
+        use crate::de::Warning;
+
+        let input = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1&addrtype=bech32";
+        let (uri, warnings) = Uri::<'_, _, crate::extras::UnknownParams<'_>>::parse_with_warnings(input);
+        let uri = uri.unwrap().assume_checked();
+        assert!(warnings.is_empty());
+        assert_eq!(uri.to_string(), input);
+
+        let cases = [
+            ("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?addrtype=bc", true),
+            ("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?addrtype=P2PKH", false),
+            ("bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?addrtype=bech32m", true),
+            ("bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?addrtype=BC", false),
+            ("bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?addrtype=something", false),
+        ];
+        for (input, mismatch) in cases {
+            let (uri, warnings) = Uri::<'_, _>::parse_with_warnings(input);
+            uri.unwrap();
+            let position = input.find("addrtype=").unwrap() + "addrtype=".len();
+            assert_eq!(warnings.contains(&Warning::AddressTypeHintMismatch { position }), mismatch, "{}", input);
+        }
+    }
+
+    #[test]
+    fn write_into() {
+        let inputs = [
+            "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr",
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+        ];
+        let mut buf = String::from("prefix ");
+        let uri = Uri::<'_, _>::try_from(inputs[0]).unwrap().assume_checked();
+        uri.write_into(&mut buf);
+        assert_eq!(buf, alloc::format!("prefix {}", inputs[0]));
+
+        let capacity = buf.capacity();
+        for input in inputs {
+            buf.clear();
+            Uri::<'_, _>::try_from(input).unwrap().assume_checked().write_into(&mut buf);
+            assert_eq!(buf, input);
+        }
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn whitespace_in_scheme() {
+        let uri_error = |input: &'static str| match Uri::<'_, _>::try_from(input).unwrap_err() {
+            crate::de::Error::Uri(error) => error,
+            crate::de::Error::Extras(never) => match never {},
+        };
+        let cases = [
+            ("bitcoin :1andreas3batLhQa2FawWjeyjCqyBzypd", 7),
+            ("bit coin:1andreas3batLhQa2FawWjeyjCqyBzypd", 3),
+            (" BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd", 0),
+            ("bitcoin\t:1andreas3batLhQa2FawWjeyjCqyBzypd", 7),
+        ];
+        for (input, position) in cases {
+            let error = uri_error(input);
+            assert!(error.is_whitespace_in_scheme(), "{}", input);
+            assert_eq!(error.position(), Some(position), "{}", input);
+        }
+        assert_eq!(uri_error(cases[0].0).to_string(), "the scheme contains whitespace at 7");
+
+        for input in [
+            "bitcoins :1andreas3batLhQa2FawWjeyjCqyBzypd",
+            "litecoin:1andreas3batLhQa2FawWjeyjCqyBzypd",
+        ] {
+            let error = uri_error(input);
+            assert!(!error.is_whitespace_in_scheme(), "{}", input);
+            assert_eq!(error.to_string(), "the URI has invalid scheme");
+        }
+    }
+
+    #[test]
+    fn custom_address_parser() {
+        use crate::de::CustomAddressError;
+
+        // pretends that `future1...` addresses encode the test address
+        let parser = |address: &str| match address.strip_prefix("future1") {
+            Some(_) => Ok(test_address().into_unchecked()),
+            None => Err("not a future address"),
+        };
+        let uri = Uri::<'_, _>::parse_with_address_parser("bitcoin:future1qqqq?amount=1", parser).unwrap();
+        assert_eq!(uri.address.assume_checked_ref(), &test_address());
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(uri.original(), None);
+        assert!(uri.assume_checked().is_canonical());
+
+        let options = crate::de::ParseOptions::new().allow_semicolon_separator(true);
+        let input = "bitcoin:future1qqqq?amount=1;label=Shop";
+        assert!(Uri::<'_, _>::parse_with_address_parser(input, parser).is_err());
+        let uri = Uri::<'_, _>::parse_with_options_and_address_parser(input, &options, parser).unwrap();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert!(uri.label.is_some());
+
+        match Uri::<'_, _>::parse_with_address_parser("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd", parser).unwrap_err() {
+            error @ CustomAddressError::Address(_) => {
+                assert_eq!(error.to_string(), "the address was rejected by the address parser: not a future address")
+            },
+            CustomAddressError::Uri(error) => panic!("unexpected error: {}", error),
+        }
+        let error = Uri::<'_, _>::parse_with_address_parser("bitcoin:future1qqqq?amount=x", parser).unwrap_err();
+        match &error {
+            CustomAddressError::Uri(crate::de::Error::Uri(uri_error)) => {
+                assert_eq!(error.to_string(), alloc::format!("invalid BIP21 URI: {}", uri_error))
+            },
+            _ => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn to_string_ascii_only() {
+        use crate::ser::{SerializeParams, SerializeValue};
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=M%C3%BCller%20%E2%82%BF";
+        let uri = input.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let string = uri.to_string_ascii_only();
+        assert!(string.is_ascii());
+        assert_eq!(string, input);
+        let parsed = string.parse::<Uri<'_, _>>().unwrap();
+        assert_eq!(<Cow<'_, str>>::try_from(parsed.label.unwrap()).unwrap(), "Müller ₿");
+
+        // a careless custom extras writing the value unencoded
+        struct Raw;
+        struct Unencoded;
+        impl SerializeValue for Unencoded {
+            fn fmt_encoded(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("Zürich")
+            }
+
+            fn to_decoded_bytes(&self) -> Cow<'_, [u8]> {
+                Cow::Borrowed("Zürich".as_bytes())
+            }
+        }
+        impl SerializeParams for &Raw {
+            type Key = &'static str;
+            type Value = Unencoded;
+            type Iterator = core::iter::Once<(&'static str, Unencoded)>;
+
+            fn serialize_params(self) -> Self::Iterator {
+                core::iter::once(("city", Unencoded))
+            }
+        }
+        let uri = Uri::with_extras(test_address(), Raw);
+        assert_eq!(uri.to_string(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?city=Zürich");
+        assert_eq!(uri.to_string_ascii_only(), "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?city=Z%C3%BCrich");
+    }
+
+    #[test]
+    fn bare_scheme() {
+        for input in ["bitcoin:", "bitcoin:?", "bitcoin:?&", "BITCOIN:?amount=1"] {
+            match input.parse::<Uri<'_, _>>().unwrap_err() {
+                crate::de::Error::Uri(error) => assert_eq!(error.to_string(), "the URI is missing address", "{}", input),
+                crate::de::Error::Extras(never) => match never {},
+            }
+        }
+    }
+
+    #[test]
+    fn addressless() {
+        for input in [
+            "bitcoin:?pj=https://example.com/pj",
+            "bitcoin:?amount=1&lightning=lnbc1",
+            "bitcoin:?req-pj=https://example.com",
+        ] {
+            match input.parse::<Uri<'_, _>>().unwrap_err() {
+                crate::de::Error::Uri(error) => assert!(error.is_addressless(), "{}", input),
+                crate::de::Error::Extras(never) => match never {},
+            }
+        }
+        match "bitcoin:?amount=1&pjos=0".parse::<Uri<'_, _>>().unwrap_err() {
+            crate::de::Error::Uri(error) => {
+                assert!(!error.is_addressless());
+                assert_eq!(error.to_string(), "the URI is missing address");
+            },
+            crate::de::Error::Extras(never) => match never {},
+        }
+        match "bitcoin:?pj=https://example.com/pj".parse::<Uri<'_, _>>().unwrap_err() {
+            crate::de::Error::Uri(error) => {
+                assert_eq!(
+                    error.to_string(),
+                    "the URI has no address, addressless URIs with parameter 'pj' are not supported"
+                )
+            },
+            crate::de::Error::Extras(never) => match never {},
+        }
+    }
+
     #[test]
     fn bad_unicode_scheme() {
         let input = "bitcoinö:1andreas3batLhQa2FawWjeyjCqyBzypd";
@@ -0,0 +1,241 @@
+//! Estimation of QR code sizes needed to encode URIs.
+//!
+//! [`Uri::estimate_qr_version`] computes the smallest QR code version able to hold the URI so
+//! that UIs can decide the layout before rendering. Data is split into numeric, alphanumeric and
+//! byte segments optimally, the same way good QR encoders do, so the estimate matches the code
+//! they produce. Kanji mode and structured append are not considered.
+//!
+//! [`Uri::to_string_qr`] additionally picks the error correction level so that the code stays
+//! easy to scan.
+
+use alloc::string::String;
+use core::fmt;
+use bitcoin::address::NetworkChecked;
+use crate::ser::SerializeParams;
+use crate::Uri;
+
+/// Error correction level of the QR code.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EcLevel {
+    /// Recovers about 7% of the code.
+    Low,
+    /// Recovers about 15% of the code.
+    Medium,
+    /// Recovers about 25% of the code.
+    Quartile,
+    /// Recovers about 30% of the code.
+    High,
+}
+
+/// The size of QR code able to hold some data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct QrVersion {
+    version: u8,
+}
+
+impl QrVersion {
+    /// Returns the version number (1 to 40).
+    pub fn version(self) -> u8 {
+        self.version
+    }
+
+    /// Returns the number of modules along one side of the symbol, not counting the quiet zone.
+    pub fn modules(self) -> u8 {
+        17 + 4 * self.version
+    }
+}
+
+impl fmt::Display for QrVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "version {} ({}x{} modules)", self.version, self.modules(), self.modules())
+    }
+}
+
+/// The largest version [`Uri::to_string_qr`] prefers to keep the code easy to scan.
+///
+/// Version 10 has 57x57 modules which cheap phone cameras still read reliably from a screen.
+pub const RECOMMENDED_MAX_VERSION: u8 = 10;
+
+/// Numbers of data codewords of each version at given error correction level.
+#[rustfmt::skip]
+const DATA_CODEWORDS: [[u16; 40]; 4] = [
+    [19, 34, 55, 80, 108, 136, 156, 194, 232, 274, 324, 370, 428, 461, 523, 589, 647, 721, 795, 861,
+     932, 1006, 1094, 1174, 1276, 1370, 1468, 1531, 1631, 1735, 1843, 1955, 2071, 2191, 2306, 2434, 2566, 2702, 2812, 2956],
+    [16, 28, 44, 64, 86, 108, 124, 154, 182, 216, 254, 290, 334, 365, 415, 453, 507, 563, 627, 669,
+     714, 782, 860, 914, 1000, 1062, 1128, 1193, 1267, 1373, 1455, 1541, 1631, 1725, 1812, 1914, 1992, 2102, 2216, 2334],
+    [13, 22, 34, 48, 62, 76, 88, 110, 132, 154, 180, 206, 244, 261, 295, 325, 367, 397, 445, 485,
+     512, 568, 614, 664, 718, 754, 808, 871, 911, 985, 1033, 1115, 1171, 1231, 1286, 1354, 1426, 1502, 1582, 1666],
+    [9, 16, 26, 36, 46, 60, 66, 86, 100, 122, 140, 158, 180, 197, 223, 253, 283, 313, 341, 385,
+     406, 442, 464, 514, 538, 596, 628, 661, 701, 745, 793, 845, 901, 961, 986, 1054, 1096, 1142, 1222, 1276],
+];
+
+/// Versions sharing the same lengths of character count fields, with the lengths for numeric,
+/// alphanumeric and byte mode.
+const VERSION_GROUPS: [(u8, u8, [u32; 3]); 3] = [(1, 9, [10, 9, 8]), (10, 26, [12, 11, 16]), (27, 40, [14, 13, 16])];
+
+/// Costs of one character in numeric, alphanumeric and byte mode in sixths of a bit.
+const CHAR_COSTS: [u32; 3] = [20, 33, 48];
+
+fn is_alphanumeric(byte: u8) -> bool {
+    matches!(byte, b'0'..=b'9' | b'A'..=b'Z' | b' ' | b'$' | b'%' | b'*' | b'+' | b'-' | b'.' | b'/' | b':')
+}
+
+fn round_up_to_bit(sixths: u32) -> u32 {
+    sixths + (6 - sixths % 6) % 6
+}
+
+/// Returns the number of bits of the optimally segmented data.
+///
+/// Each state holds the cost of the data ending with an open segment of the given mode. Partial
+/// groups of numeric and alphanumeric characters cost less than full ones so the costs are kept
+/// in sixths of a bit and only rounded when a segment ends.
+fn segmented_bits(data: &[u8], count_bits: [u32; 3]) -> u32 {
+    let headers = [(4 + count_bits[0]) * 6, (4 + count_bits[1]) * 6, (4 + count_bits[2]) * 6];
+    let mut costs = headers;
+    for &byte in data {
+        let encodable = [byte.is_ascii_digit(), is_alphanumeric(byte), true];
+        for mode in 0..3 {
+            costs[mode] = if encodable[mode] {
+                costs[mode].saturating_add(CHAR_COSTS[mode])
+            } else {
+                u32::MAX
+            };
+        }
+        let previous = costs;
+        for mode in 0..3 {
+            for (other, cost) in previous.iter().enumerate() {
+                if other != mode && *cost != u32::MAX {
+                    costs[mode] = costs[mode].min(round_up_to_bit(*cost) + headers[mode]);
+                }
+            }
+        }
+    }
+    costs.iter().map(|cost| round_up_to_bit(*cost) / 6).min().expect("there are three modes")
+}
+
+/// Returns the smallest QR code version able to hold `data` at given error correction level.
+///
+/// Returns `None` if the data doesn't fit even into the largest (40) version.
+pub fn estimate_version(data: &str, ec_level: EcLevel) -> Option<QrVersion> {
+    let capacities = &DATA_CODEWORDS[ec_level as usize];
+    for (first, last, count_bits) in VERSION_GROUPS {
+        let bits = segmented_bits(data.as_bytes(), count_bits);
+        for version in first..=last {
+            if bits <= u32::from(capacities[usize::from(version - 1)]) * 8 {
+                return Some(QrVersion { version });
+            }
+        }
+    }
+    None
+}
+
+impl<T> Uri<'_, NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Returns the smallest QR code version able to hold the URI at given error correction level.
+    ///
+    /// The estimate is for the QR-code-optimized alternate form (`{:#}`). Other forms, e.g. the
+    /// output of [`reserialize_minified`](Self::reserialize_minified), can be estimated using
+    /// [`estimate_version`]. Returns `None` if the URI doesn't fit into any QR code.
+    pub fn estimate_qr_version(&self, ec_level: EcLevel) -> Option<QrVersion> {
+        estimate_version(&alloc::format!("{:#}", self), ec_level)
+    }
+
+    /// Returns the QR-code-optimized form of the URI with recommended error correction level.
+    ///
+    /// The string is the output of [`reserialize_minified`](Self::reserialize_minified). The level is the highest one that keeps the
+    /// code at most [`RECOMMENDED_MAX_VERSION`], so long URIs get lower error correction in
+    /// exchange for larger modules. [`EcLevel::Low`] is returned if no level satisfies it even
+    /// if the URI doesn't fit into any QR code - check it with [`estimate_version`] if needed.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn to_string_qr(&self) -> (String, EcLevel) {
+        let string = self.reserialize_minified();
+        let ec_level = [EcLevel::High, EcLevel::Quartile, EcLevel::Medium]
+            .iter()
+            .copied()
+            .find(|ec_level| matches!(estimate_version(&string, *ec_level), Some(version) if version.version() <= RECOMMENDED_MAX_VERSION))
+            .unwrap_or(EcLevel::Low);
+        (string, ec_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_version, EcLevel};
+    use crate::Uri;
+    use alloc::string::{String, ToString};
+
+    fn version(data: &str, ec_level: EcLevel) -> Option<u8> {
+        estimate_version(data, ec_level).map(|version| version.version())
+    }
+
+    #[test]
+    fn capacity_limits() {
+        // the capacities listed in the QR code specification
+        assert_eq!(version(&"1".repeat(41), EcLevel::Low), Some(1));
+        assert_eq!(version(&"1".repeat(42), EcLevel::Low), Some(2));
+        assert_eq!(version(&"A".repeat(25), EcLevel::Low), Some(1));
+        assert_eq!(version(&"A".repeat(26), EcLevel::Low), Some(2));
+        assert_eq!(version(&"a".repeat(17), EcLevel::Low), Some(1));
+        assert_eq!(version(&"a".repeat(7), EcLevel::High), Some(1));
+        assert_eq!(version(&"a".repeat(8), EcLevel::High), Some(2));
+        assert_eq!(version(&"A".repeat(4296), EcLevel::Low), Some(40));
+        assert_eq!(version(&"A".repeat(4297), EcLevel::Low), None);
+        assert_eq!(version(&"a".repeat(2953), EcLevel::Low), Some(40));
+        assert_eq!(version(&"a".repeat(1273), EcLevel::High), Some(40));
+        assert_eq!(version(&"a".repeat(1274), EcLevel::High), None);
+    }
+
+    #[test]
+    fn mixed_segments() {
+        let mut data = String::from("BITCOIN:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ");
+        // 47 alphanumeric characters need 4 + 9 + 23 * 11 + 6 bits, exactly the capacity of version 2
+        assert_eq!(version(&data[..47], EcLevel::Low), Some(2));
+        assert_eq!(version(&data[..48], EcLevel::Low), Some(3));
+        // encoding everything in byte mode would need 4 + 8 + 52 * 8 bits, more than the 352 bits of version 3
+        data.push_str("?a");
+        assert_eq!(version(&data, EcLevel::Medium), Some(3));
+    }
+
+    #[test]
+    fn recommended_ec_level() {
+        let uri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=1"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let (string, ec_level) = uri.to_string_qr();
+        assert_eq!(string, "bitcoin:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?amount=1");
+        assert_eq!(ec_level, EcLevel::High);
+
+        let long = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?message=".to_string() + &"x".repeat(200);
+        let uri = long.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        let (string, ec_level) = uri.to_string_qr();
+        assert_eq!(ec_level, EcLevel::Low);
+        assert_eq!(estimate_version(&string, EcLevel::Low).unwrap().version(), 10);
+
+        let too_long = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?message=".to_string() + &"x".repeat(300);
+        let uri = too_long.parse::<Uri<'_, _>>().unwrap().assume_checked();
+        assert_eq!(uri.to_string_qr().1, EcLevel::Low);
+    }
+
+    #[test]
+    fn uri_version() {
+        let uri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        let estimate = uri.estimate_qr_version(EcLevel::Medium).unwrap();
+        assert_eq!(estimate.version(), 3);
+        assert_eq!(estimate.modules(), 29);
+        assert_eq!(estimate.to_string(), "version 3 (29x29 modules)");
+
+        let uri = "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?label=Luke-Jr&message=Donation%20for%20project%20xyz"
+            .parse::<Uri<'_, _>>()
+            .unwrap()
+            .assume_checked();
+        assert!(uri.estimate_qr_version(EcLevel::Medium).unwrap() > estimate);
+        assert!(uri.estimate_qr_version(EcLevel::High).unwrap() > uri.estimate_qr_version(EcLevel::Low).unwrap());
+    }
+}
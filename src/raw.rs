@@ -0,0 +1,324 @@
+//! Uninterpreted view of bitcoin URIs.
+//!
+//! [`RawUri`] splits the URI into its parts without validating or decoding anything. This is the
+//! lowest-level view for tools that want to inspect or transform URIs without the opinions of the
+//! crate. Use [`RawUri::into_typed`] to get a validated [`Uri`].
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use crate::de::{DeserializeParams, Error};
+use crate::Uri;
+
+/// URI split into its parts without any interpretation.
+///
+/// No part is validated nor percent-decoded. Parsing only fails if there's no `:` delimiting
+/// the scheme. The splitting rules are:
+///
+/// * The scheme ends at the first `:`.
+/// * The address ends at the first `?` or `#` after the scheme.
+/// * The query starts after `?` and ends at the first `#` (RFC 3986), the rest is fragment.
+/// * The query is split on every `&`. An empty query contains no parameters but empty parameters
+///   between separators are preserved.
+/// * Each parameter is split on the first `=` into key and value. A parameter without `=` has
+///   no value which is distinct from empty value.
+///
+/// `Display` reproduces the input exactly.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RawUri<'a> {
+    input: &'a str,
+    scheme: &'a str,
+    address: &'a str,
+    query: Option<&'a str>,
+    params: Vec<(&'a str, Option<&'a str>)>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> RawUri<'a> {
+    /// Splits `input` into parts.
+    pub fn parse(input: &'a str) -> Result<Self, MissingScheme> {
+        let (scheme, address, query, fragment) = split(input)?;
+        let params = query.map(|query| QueryTokenizer::new(query).collect()).unwrap_or_default();
+
+        Ok(RawUri {
+            input,
+            scheme,
+            address,
+            query,
+            params,
+            fragment,
+        })
+    }
+
+    /// Returns the complete input.
+    pub fn as_str(&self) -> &'a str {
+        self.input
+    }
+
+    /// Returns the scheme without the trailing `:`.
+    pub fn scheme(&self) -> &'a str {
+        self.scheme
+    }
+
+    /// Returns the raw address string.
+    pub fn address(&self) -> &'a str {
+        self.address
+    }
+
+    /// Returns the raw query without the leading `?` if present.
+    pub fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// Returns the parameters in the order they appear in the input.
+    ///
+    /// Both keys and values are percent-encoded.
+    pub fn params(&self) -> &[(&'a str, Option<&'a str>)] {
+        &self.params
+    }
+
+    /// Returns the byte ranges of the parameters within the [input](Self::as_str).
+    ///
+    /// The spans are yielded in the same order as [`params`](Self::params) and cover the raw
+    /// (percent-encoded) text: the key span excludes `=` and the value span starts after it.
+    /// The span of a parameter without `=` has no value, an empty value has an empty range
+    /// right after `=`. Replacing the text of a span in the input changes only that key or
+    /// value, which allows editing URIs without reserializing them.
+    pub fn spans(&self) -> impl Iterator<Item = ParamSpan> + '_ {
+        self.params.iter().enumerate().map(move |(index, (key, value))| ParamSpan {
+            index,
+            key: self.range_of(key),
+            value: value.map(|value| self.range_of(value)),
+        })
+    }
+
+    /// Returns the range of `part` which must be a subslice of the input.
+    fn range_of(&self, part: &str) -> Range<usize> {
+        let start = part.as_ptr() as usize - self.input.as_ptr() as usize;
+        start..(start + part.len())
+    }
+
+    /// Returns the required (`req-`) parameters in the order they appear in the input.
+    ///
+    /// Unlike [`Uri`], this lists the required parameters whether they are understood or not,
+    /// so it can be used to show which features a URI requires.
+    pub fn required_params(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> + '_ {
+        self.params.iter().copied().filter(|(key, _)| key.starts_with("req-"))
+    }
+
+    /// Returns the fragment without the leading `#` if present.
+    ///
+    /// BIP21 doesn't define fragments, so [`Uri`] ignores it.
+    pub fn fragment(&self) -> Option<&'a str> {
+        self.fragment
+    }
+
+    /// Validates the URI upgrading it to [`Uri`].
+    pub fn into_typed<T: DeserializeParams<'a>>(self) -> Result<Uri<'a, bitcoin::address::NetworkUnchecked, T>, Error<T::Error>> {
+        Uri::parse_with_options(self.input, &Default::default())
+    }
+}
+
+impl fmt::Display for RawUri<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.input)
+    }
+}
+
+/// Scheme, address, query and fragment of a URI.
+type Parts<'a> = (&'a str, &'a str, Option<&'a str>, Option<&'a str>);
+
+/// Splits `input` into parts using the rules of [`RawUri`].
+fn split(input: &str) -> Result<Parts<'_>, MissingScheme> {
+    let pos = input.find(':').ok_or(MissingScheme)?;
+    let scheme = &input[..pos];
+    let rest = &input[(pos + 1)..];
+    let (rest, fragment) = match rest.find('#') {
+        Some(pos) => (&rest[..pos], Some(&rest[(pos + 1)..])),
+        None => (rest, None),
+    };
+    let (address, query) = match rest.find('?') {
+        Some(pos) => (&rest[..pos], Some(&rest[(pos + 1)..])),
+        None => (rest, None),
+    };
+    Ok((scheme, address, query, fragment))
+}
+
+impl Uri<'_> {
+    /// Returns the scheme, the address and the query of `input` without parsing them.
+    ///
+    /// This is a cheaper alternative to [`RawUri::parse`] for callers that only need to peek at
+    /// the parts, e.g. to route the URI to the right handler, since it doesn't allocate nor split
+    /// the parameters. The parts are split using the same rules and are neither validated nor
+    /// decoded - the scheme may be anything, not just `bitcoin`. The query excludes the leading
+    /// `?` and the fragment. Fails only if there's no `:` delimiting the scheme.
+    pub fn as_unchecked_str_parts(input: &str) -> Result<(&str, &str, Option<&str>), MissingScheme> {
+        let (scheme, address, query, _) = split(input)?;
+        Ok((scheme, address, query))
+    }
+}
+
+/// Byte ranges of a parameter within the input of [`RawUri`].
+///
+/// Returned by [`RawUri::spans`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ParamSpan {
+    /// The position of the parameter among all parameters, starting at zero.
+    pub index: usize,
+    /// The range of the key.
+    pub key: Range<usize>,
+    /// The range of the value or `None` if the parameter has no `=`.
+    pub value: Option<Range<usize>>,
+}
+
+/// Iterator over raw parameters of a query.
+///
+/// This is the primitive [`RawUri`] uses to split the query. It yields `(key, value)` pairs
+/// borrowed from the query without decoding or interpreting them. The splitting rules are:
+///
+/// * The query (without the leading `?` and the fragment) is split on every `&`.
+/// * An empty query yields no parameters. Otherwise every part is yielded, including empty ones
+///   (`a&&b` yields `("", None)` between `a` and `b`, a trailing `&` yields it at the end).
+/// * Each part is split on the first `=` into key and value, further `=` belong to the value.
+///   A part without `=` yields `None` as value which is distinct from empty value (`key=`).
+#[derive(Debug, Clone)]
+pub struct QueryTokenizer<'a> {
+    parts: Option<core::str::Split<'a, char>>,
+}
+
+impl<'a> QueryTokenizer<'a> {
+    /// Creates the tokenizer for `query` which must not include the leading `?` nor fragment.
+    pub fn new(query: &'a str) -> Self {
+        QueryTokenizer {
+            parts: Some(query).filter(|query| !query.is_empty()).map(|query| query.split('&')),
+        }
+    }
+}
+
+impl<'a> Iterator for QueryTokenizer<'a> {
+    type Item = (&'a str, Option<&'a str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let param = self.parts.as_mut()?.next()?;
+        Some(match param.find('=') {
+            Some(pos) => (&param[..pos], Some(&param[(pos + 1)..])),
+            None => (param, None),
+        })
+    }
+}
+
+impl core::iter::FusedIterator for QueryTokenizer<'_> {}
+
+/// Error returned when the input of [`RawUri::parse`] doesn't contain `:`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MissingScheme;
+
+impl fmt::Display for MissingScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the URI is missing scheme")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for MissingScheme {}
+
+#[cfg(test)]
+mod tests {
+    use super::RawUri;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn raw_parts() {
+        let input = "BITCOIN:invalid-address?amount=1e3&label=%ZZ&flag&empty=&=x#frag";
+        let raw = RawUri::parse(input).unwrap();
+        assert_eq!(raw.scheme(), "BITCOIN");
+        assert_eq!(raw.address(), "invalid-address");
+        assert_eq!(raw.query(), Some("amount=1e3&label=%ZZ&flag&empty=&=x"));
+        assert_eq!(
+            raw.params(),
+            &[
+                ("amount", Some("1e3")),
+                ("label", Some("%ZZ")),
+                ("flag", None),
+                ("empty", Some("")),
+                ("", Some("x"))
+            ]
+        );
+        assert_eq!(raw.fragment(), Some("frag"));
+        assert_eq!(raw.to_string(), input);
+    }
+
+    #[test]
+    fn param_spans() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&flag&empty=&label=a%20b#frag";
+        let raw = RawUri::parse(input).unwrap();
+        let spans = raw.spans().collect::<Vec<_>>();
+        assert_eq!(spans.len(), raw.params().len());
+        for (span, (key, value)) in spans.iter().zip(raw.params()) {
+            assert_eq!(&input[span.key.clone()], *key);
+            assert_eq!(span.value.clone().map(|value| &input[value]), *value);
+        }
+        assert_eq!(spans[1].index, 1);
+        assert_eq!(spans[1].value, None);
+        assert_eq!(spans[2].value, Some(62..62));
+
+        let value = spans[3].value.clone().unwrap();
+        let mut edited = String::from(input);
+        edited.replace_range(value, "c");
+        assert_eq!(edited, "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&flag&empty=&label=c#frag");
+    }
+
+    #[test]
+    fn unchecked_str_parts() {
+        let parts = crate::Uri::as_unchecked_str_parts("BITCOIN:not-an-address?amount=x&y#frag").unwrap();
+        assert_eq!(parts, ("BITCOIN", "not-an-address", Some("amount=x&y")));
+        let parts = crate::Uri::as_unchecked_str_parts("lightning:lnbc1").unwrap();
+        assert_eq!(parts, ("lightning", "lnbc1", None));
+        assert_eq!(crate::Uri::as_unchecked_str_parts("bitcoin:?").unwrap(), ("bitcoin", "", Some("")));
+        assert!(crate::Uri::as_unchecked_str_parts("1andreas3batLhQa2FawWjeyjCqyBzypd").is_err());
+    }
+
+    #[test]
+    fn empty_query() {
+        let raw = RawUri::parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?").unwrap();
+        assert_eq!(raw.query(), Some(""));
+        assert!(raw.params().is_empty());
+        assert!(RawUri::parse("bitcoin").is_err());
+    }
+
+    #[test]
+    fn query_tokenizer() {
+        use super::QueryTokenizer;
+
+        assert!(QueryTokenizer::new("a=1&b&&c=&d==x&").eq([
+            ("a", Some("1")),
+            ("b", None),
+            ("", None),
+            ("c", Some("")),
+            ("d", Some("=x")),
+            ("", None)
+        ]));
+        assert_eq!(QueryTokenizer::new("").next(), None);
+        assert!(QueryTokenizer::new("&").eq([("", None), ("", None)]));
+    }
+
+    #[test]
+    fn required_params() {
+        let raw = RawUri::parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?req-pj=x&amount=1&req-flag").unwrap();
+        assert!(raw.required_params().eq([("req-pj", Some("x")), ("req-flag", None)]));
+    }
+
+    #[test]
+    fn into_typed() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Luke-Jr";
+        let uri = RawUri::parse(input).unwrap().into_typed::<crate::NoExtras>().unwrap().assume_checked();
+        assert_eq!(uri.amount, Some(bitcoin::Amount::ONE_BTC));
+        assert_eq!(uri.to_string(), input);
+
+        let raw = RawUri::parse("bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1e3").unwrap();
+        assert!(raw.into_typed::<crate::NoExtras>().is_err());
+    }
+}
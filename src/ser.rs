@@ -5,9 +5,19 @@
 //! Check [`SerializeParams`] to get started.
 
 use alloc::borrow::Cow;
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use bitcoin::amount::Denomination;
 use core::fmt;
 use super::{Uri, Param, ParamInner};
+use crate::de::DeserializeParams;
+
+/// Re-exported to allow building sets for [`DisplayOptions::extra_encode_set`].
+#[cfg(feature = "encode-set")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encode-set")))]
+pub use percent_encoding_rfc3986::{AsciiSet, CONTROLS};
 
 /// Represents a value that can be serialized.
 ///
@@ -16,9 +26,15 @@ pub trait SerializeParams {
     /// Parameter name.
     ///
     /// **Warning**: displaying [`Uri`] will panic if the key contains `=` character!
+    ///
+    /// Other characters not allowed in keys, such as `&`, `#`, spaces or control characters, are
+    /// percent-encoded so that they can't corrupt the query. `%` is written as is.
     type Key: fmt::Display;
     /// Parameter value.
-    type Value: fmt::Display;
+    ///
+    /// This is either any `Display` type, serialized as UTF-8 text, or [`Param`] which can carry
+    /// arbitrary bytes.
+    type Value: SerializeValue;
 
     /// Iterator over key-value pairs
     type Iterator: Iterator<Item = (Self::Key, Self::Value)>;
@@ -27,25 +43,130 @@ pub trait SerializeParams {
     fn serialize_params(self) -> Self::Iterator;
 }
 
-/// Checks if the display implementation outputs `=` character.
-struct EqSignChecker<'a, W: fmt::Write>(W, &'a dyn fmt::Display);
+/// Value of a parameter that can be serialized.
+///
+/// This is implemented for all `Display` types and for [`Param`] (and references to it). Use
+/// `Param` for values that are not valid UTF-8 - e.g. binary blobs - so that they are
+/// percent-encoded byte-by-byte and roundtrip.
+pub trait SerializeValue {
+    /// Writes the percent-encoded value.
+    fn fmt_encoded(&self, f: &mut fmt::Formatter) -> fmt::Result;
+
+    /// Returns the value as decoded bytes.
+    fn to_decoded_bytes(&self) -> Cow<'_, [u8]>;
+}
+
+impl<T: fmt::Display> SerializeValue for T {
+    fn fmt_encoded(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", DisplayEncoder(self))
+    }
+
+    fn to_decoded_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.to_string().into_bytes())
+    }
+}
+
+impl SerializeValue for Param<'_> {
+    fn fmt_encoded(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", DisplayParam(self))
+    }
+
+    fn to_decoded_bytes(&self) -> Cow<'_, [u8]> {
+        self.decoded_bytes()
+    }
+}
+
+impl SerializeValue for &Param<'_> {
+    fn fmt_encoded(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt_encoded(f)
+    }
 
-impl<W: fmt::Write> fmt::Write for EqSignChecker<'_, W> {
+    fn to_decoded_bytes(&self) -> Cow<'_, [u8]> {
+        (**self).decoded_bytes()
+    }
+}
+
+/// Displays [`SerializeValue`] as encoded.
+struct EncodedValue<T: SerializeValue>(T);
+
+impl<T: SerializeValue> fmt::Display for EncodedValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_encoded(f)
+    }
+}
+
+/// Percent-encodes the key written by the display implementation.
+///
+/// Panics if the key contains `=` character since encoding it would silently change the key.
+struct KeyEncoder<'a, W: fmt::Write>(W, &'a dyn fmt::Display);
+
+impl<W: fmt::Write> fmt::Write for KeyEncoder<'_, W> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         if s.contains('=') {
             panic!("key '{}' contains equal sign", self.1);
         }
-        self.0.write_str(s)
+        write!(self.0, "{}", EncodedKey(s))
     }
+}
 
-    fn write_char(&mut self, c: char) -> fmt::Result {
-        if c == '=' {
-            panic!("key '{}' contains equal sign", self.1);
+/// Compares written output against expected string.
+///
+/// Used to compare `Display` output without allocating.
+struct EqChecker<'a> {
+    remaining: &'a str,
+    matches: bool,
+}
+
+impl fmt::Write for EqChecker<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.remaining.strip_prefix(s) {
+            Some(remaining) if self.matches => self.remaining = remaining,
+            _ => self.matches = false,
         }
-        self.0.write_char(c)
+        Ok(())
     }
 }
 
+/// Returns `true` if `value` displays exactly as `expected`.
+pub(crate) fn display_eq(value: impl fmt::Display, expected: &str) -> bool {
+    use fmt::Write;
+
+    let mut checker = EqChecker {
+        remaining: expected,
+        matches: true,
+    };
+    // EqChecker never fails and errors from `value` mean it's not equal anyway
+    write!(checker, "{}", value).is_ok() && checker.matches && checker.remaining.is_empty()
+}
+
+/// Checks whether written output starts with expected prefix.
+struct PrefixChecker<'a> {
+    remaining: &'a str,
+    matches: bool,
+}
+
+impl fmt::Write for PrefixChecker<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.len().min(self.remaining.len());
+        if self.matches && s.as_bytes()[..len] != self.remaining.as_bytes()[..len] {
+            self.matches = false;
+        }
+        self.remaining = self.remaining.get(len..).unwrap_or("");
+        Ok(())
+    }
+}
+
+/// Returns `true` if `value` displays as a string starting with `prefix`.
+pub(crate) fn display_starts_with(value: impl fmt::Display, prefix: &str) -> bool {
+    use fmt::Write;
+
+    let mut checker = PrefixChecker {
+        remaining: prefix,
+        matches: true,
+    };
+    write!(checker, "{}", value).is_ok() && checker.matches && checker.remaining.is_empty()
+}
+
 /// Set of characters that will be percent-encoded
 ///
 /// This contains anything not in `query` (i.e. ``gen-delim` from the quoted
@@ -79,6 +200,8 @@ impl<W: fmt::Write> fmt::Write for EqSignChecker<'_, W> {
 /// > sub-delims    = "!" / "$" / "&" / "'" / "(" / ")"
 /// >               / "*" / "+" / "," / ";" / "="
 /// > ```
+///
+/// The escapes are always emitted with uppercase hex digits (`%2F`) as RFC 3986 recommends.
 const ASCII_SET: percent_encoding_rfc3986::AsciiSet = percent_encoding_rfc3986::NON_ALPHANUMERIC
     // allow non-alphanumeric characters from `unreserved`
     .remove(b'-')
@@ -103,6 +226,25 @@ const ASCII_SET: percent_encoding_rfc3986::AsciiSet = percent_encoding_rfc3986::
     .remove(b'/')
     .remove(b'?');
 
+/// Percent-encodes a key.
+///
+/// Keys are expected to be valid (see [`validate_key`]) so this only prevents invalid keys from
+/// corrupting the query, e.g. `&` or `#` in keys. Unlike in values, `%` is kept as is so that
+/// keys which are already percent-encoded are not encoded twice.
+struct EncodedKey<'a>(&'a str);
+
+impl fmt::Display for EncodedKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, part) in self.0.split('%').enumerate() {
+            if i > 0 {
+                f.write_str("%")?;
+            }
+            write!(f, "{}", percent_encoding_rfc3986::utf8_percent_encode(part, &ASCII_SET))?;
+        }
+        Ok(())
+    }
+}
+
 /// Percent-encodes writes.
 struct WriterEncoder<W: fmt::Write>(W);
 
@@ -142,6 +284,50 @@ impl fmt::Display for DisplayParam<'_> {
     }
 }
 
+/// Displays an already encoded value applying the extra encoding of [`DisplayOptions`].
+#[cfg_attr(not(feature = "encode-set"), allow(dead_code))]
+struct ExtraEncoded<'o, T: fmt::Display>(T, &'o DisplayOptions);
+
+impl<T: fmt::Display> fmt::Display for ExtraEncoded<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "encode-set")]
+        {
+            if let Some(ExtraEncodeSet(set)) = self.1.extra_encode_set {
+                use fmt::Write;
+
+                return write!(ExtraEncoder(f, set), "{}", self.0);
+            }
+        }
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Percent-encodes bytes of already encoded output contained in the set.
+///
+/// Alphanumeric characters and `%` are kept so that existing escapes are not corrupted.
+#[cfg(feature = "encode-set")]
+struct ExtraEncoder<W: fmt::Write>(W, &'static AsciiSet);
+
+#[cfg(feature = "encode-set")]
+impl<W: fmt::Write> fmt::Write for ExtraEncoder<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, part) in s.split('%').enumerate() {
+            if i > 0 {
+                self.0.write_str("%")?;
+            }
+            for chunk in part.split_inclusive(|c: char| !c.is_ascii_alphanumeric()) {
+                let (kept, last) = match chunk.char_indices().last() {
+                    Some((pos, c)) if !c.is_ascii_alphanumeric() => chunk.split_at(pos),
+                    _ => (chunk, ""),
+                };
+                self.0.write_str(kept)?;
+                write!(self.0, "{}", percent_encoding_rfc3986::utf8_percent_encode(last, self.1))?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Writes key-value pair with all required symbols around them.
 ///
 /// `value` is **not** percent-encoded - this must be done from the caller.
@@ -149,18 +335,25 @@ fn write_param(writer: &mut impl fmt::Write, key: impl fmt::Display, value: impl
     use core::fmt::Write;
 
     if *no_params {
-        write!(EqSignChecker(&mut *writer, &key), "?{}", key)?;
+        writer.write_char('?')?;
         *no_params = false;
     } else {
-        write!(EqSignChecker(&mut *writer, &key), "&{}", key)?;
+        writer.write_char('&')?;
     }
+    write!(KeyEncoder(&mut *writer, &key), "{}", key)?;
     write!(writer, "={}", value)
 }
 
 /// Write key-value pair if `value` is `Some`.
-fn maybe_write_param(writer: &mut impl fmt::Write, key: impl fmt::Display, value: Option<&Param<'_>>, no_params: &mut bool) -> fmt::Result {
+fn maybe_write_param(
+    writer: &mut impl fmt::Write,
+    key: impl fmt::Display,
+    value: Option<&Param<'_>>,
+    options: &DisplayOptions,
+    no_params: &mut bool,
+) -> fmt::Result {
     match value {
-        Some(value) => write_param(writer, key, DisplayParam(value), no_params),
+        Some(value) => write_param(writer, key, ExtraEncoded(DisplayParam(value), options), no_params),
         None => Ok(()),
     }
 }
@@ -182,16 +375,1105 @@ impl<T> fmt::Display for Uri<'_, bitcoin::address::NetworkChecked, T> where for<
         } else {
             write!(f, "bitcoin:{}", self.address)?;
         }
-        let mut no_params = true;
-        let display_amount = self.amount.as_ref().map(|amount| amount.display_in(Denomination::Bitcoin));
+        let amount = self.amount.map(|amount| amount.display_in(Denomination::Bitcoin));
+        self.fmt_params(f, amount.as_ref().map(|amount| amount as &dyn fmt::Display), &DisplayOptions::new())
+    }
+}
 
-        maybe_display_param(f, "amount", display_amount, &mut no_params)?;
-        maybe_write_param(f, "label", self.label.as_ref(), &mut no_params)?;
-        maybe_write_param(f, "message", self.message.as_ref(), &mut no_params)?;
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Writes the query part of the URI (including `?`) as used by `Display`.
+    ///
+    /// The amount is written as given by the caller, the other parameters are encoded according
+    /// to `options`.
+    fn fmt_params(&self, f: &mut fmt::Formatter, amount: Option<&dyn fmt::Display>, options: &DisplayOptions) -> fmt::Result {
+        let mut no_params = true;
+        maybe_display_param(f, "amount", amount, &mut no_params)?;
+        maybe_write_param(f, "label", self.label.as_ref(), options, &mut no_params)?;
+        maybe_write_param(f, "message", self.message.as_ref(), options, &mut no_params)?;
 
         for (key, value) in self.extras.serialize_params() {
-            write_param(f, key, DisplayEncoder(value), &mut no_params)?;
+            write_param(f, key, ExtraEncoded(EncodedValue(value), options), &mut no_params)?;
         }
         Ok(())
     }
 }
+
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T> {
+    /// Returns the amount exactly as it appeared in the original input.
+    ///
+    /// Returns `None` if the input isn't retained or the amount differs from the one in the
+    /// input, e.g. because it was modified after parsing.
+    fn original_amount(&self) -> Option<&str> {
+        let amount = self.amount?;
+        let query = self.original.as_ref()?.input.split('#').next()?.split_once('?')?.1;
+        let (_, raw) = crate::raw::QueryTokenizer::new(query).filter(|(key, _)| *key == "amount").last()?;
+        let raw = raw?;
+        let is_plain = raw.bytes().all(|byte| byte.is_ascii_digit() || byte == b'.');
+        let is_same = bitcoin::Amount::from_str_in(raw, Denomination::Bitcoin) == Ok(amount);
+        Some(raw).filter(|_| is_plain && is_same)
+    }
+}
+
+impl<NetVal: bitcoin::address::NetworkValidation, T> Uri<'_, NetVal, T> {
+    /// Returns the amount in BTC exactly as `Display` writes it into the URI, e.g. `"0.01"`.
+    ///
+    /// UIs can show this to make sure the displayed value matches the URI. This is the same as
+    /// `amount_display_in(Denomination::Bitcoin)`. Returns `None` if there's no amount.
+    pub fn amount_display_btc_string(&self) -> Option<String> {
+        self.amount_display_in(Denomination::Bitcoin)
+    }
+
+    /// Returns the amount formatted in `denomination` without the unit, e.g. `"1000000"` sats.
+    ///
+    /// The string is computed on each call, UIs showing the amount repeatedly should keep the
+    /// returned value or use [`UriDisplay::amount`]. Only [`Denomination::Bitcoin`] matches the
+    /// amount in the URI, other denominations are for presentation. Returns `None` if there's no
+    /// amount.
+    pub fn amount_display_in(&self, denomination: Denomination) -> Option<String> {
+        self.amount.map(|amount| amount.display_in(denomination).to_string())
+    }
+}
+
+/// Returns `true` if `amount` has at most `precision` decimal places of bitcoin.
+fn fits_precision(amount: bitcoin::Amount, precision: usize) -> bool {
+    if precision >= 8 {
+        return true;
+    }
+    let unit = 10u64.pow(8 - precision as u32);
+    amount.to_sat() / unit * unit == amount.to_sat()
+}
+
+/// Form of the amount used when displaying URIs.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum AmountForm {
+    /// Normalized amount in BTC without trailing zeros (`amount=0.01`), the same as `Display`.
+    #[default]
+    Canonical,
+    /// The digits exactly as they appeared in the parsed input (`amount=0.0100`).
+    ///
+    /// This only works for URIs retaining their input (see [`Uri::original`]) whose amount wasn't
+    /// changed since parsing, otherwise the canonical form is used.
+    Faithful,
+}
+
+/// Case of bech32 addresses used when displaying URIs.
+///
+/// Base58 addresses are case-sensitive so they are always displayed unchanged.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum AddressCase {
+    /// Lower case, typical for displaying to humans.
+    #[default]
+    Lower,
+    /// Upper case, which allows more compact QR codes.
+    Upper,
+}
+
+/// Options altering the serialization of URIs.
+///
+/// Use [`Uri::display_with`] to display with non-default options. The defaults result in the
+/// same output as `Display` without the alternate flag.
+#[derive(Debug, Default, Clone)]
+pub struct DisplayOptions {
+    address_case: AddressCase,
+    amount_form: AmountForm,
+    omit_zero_amount: bool,
+    scheme: Option<&'static str>,
+    #[cfg(feature = "encode-set")]
+    extra_encode_set: Option<ExtraEncodeSet>,
+}
+
+/// Wraps the set to make it `Debug`.
+#[cfg(feature = "encode-set")]
+#[derive(Copy, Clone)]
+struct ExtraEncodeSet(&'static AsciiSet);
+
+#[cfg(feature = "encode-set")]
+impl fmt::Debug for ExtraEncodeSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AsciiSet")
+    }
+}
+
+impl DisplayOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the case of bech32 addresses, lower case by default.
+    ///
+    /// Unlike the alternate form (`{:#}`), this only affects the address.
+    pub fn address_case(mut self, value: AddressCase) -> Self {
+        self.address_case = value;
+        self
+    }
+
+    /// Sets the form of the amount, canonical by default.
+    ///
+    /// The precision flag of the formatter takes precedence over the faithful form.
+    pub fn amount_form(mut self, value: AmountForm) -> Self {
+        self.amount_form = value;
+        self
+    }
+
+    /// Omits the amount if it's zero, off by default.
+    ///
+    /// Some producers treat `amount=0` as no amount so this displays such URIs without it.
+    pub fn omit_zero_amount(mut self, value: bool) -> Self {
+        self.omit_zero_amount = value;
+        self
+    }
+
+    /// Uses `scheme` instead of `bitcoin`, the colon is added automatically.
+    ///
+    /// **Non-standard**: BIP21 mandates the `bitcoin` scheme so URIs with other schemes are only
+    /// understood within closed-loop systems using a branded scheme or by testing tools. The
+    /// scheme isn't validated, it should be a valid RFC 3986 scheme (a letter followed by
+    /// letters, digits, `+`, `-` or `.`). Note that this crate can't parse such URIs.
+    pub fn scheme(mut self, scheme: &'static str) -> Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    /// Percent-encodes characters from `set` in values in addition to the default ones.
+    ///
+    /// This allows stricter encoding for consumers that mishandle some characters allowed by
+    /// RFC 3986, e.g. `&CONTROLS.add(b'~').add(b'!')` encodes `~` and `!` as well. The set can
+    /// only add encoding: alphanumeric characters are never encoded and the amount is not
+    /// affected since it must not be encoded.
+    #[cfg(feature = "encode-set")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encode-set")))]
+    pub fn extra_encode_set(mut self, set: &'static AsciiSet) -> Self {
+        self.extra_encode_set = Some(ExtraEncodeSet(set));
+        self
+    }
+}
+
+/// Displays the URI using [`DisplayOptions`].
+///
+/// Returned by [`Uri::display_with`], the alternate flag is ignored.
+///
+/// The precision flag limits the number of decimal places of the amount, e.g.
+/// `format!("{:.4}", uri.display_with(&options))` requires the amount to have at most four
+/// decimals. The amount is never rounded since the URI would request a different amount, so
+/// formatting fails with [`fmt::Error`] if the amount has more decimals, which makes `format!`
+/// panic. Use [`Uri::amount_display_in`] to present a rounded amount. The canonical `Display`
+/// of [`Uri`] ignores the precision.
+///
+/// The amount string is computed once when this is created, so displaying it repeatedly doesn't
+/// format the amount again. [`UriDisplay::amount`] returns it.
+pub struct UriDisplay<'u, 'a, T> {
+    uri: &'u Uri<'a, bitcoin::address::NetworkChecked, T>,
+    options: &'u DisplayOptions,
+    // only the faithful form is borrowed
+    amount: Option<Cow<'u, str>>,
+}
+
+impl<T> UriDisplay<'_, '_, T> {
+    /// Returns the amount in BTC as written into the URI without the precision flag.
+    ///
+    /// This is the canonical or the faithful form depending on [`DisplayOptions::amount_form`]
+    /// and `None` if there's no amount or it's omitted by [`DisplayOptions::omit_zero_amount`].
+    pub fn amount(&self) -> Option<&str> {
+        self.amount.as_deref()
+    }
+}
+
+impl<T> fmt::Display for UriDisplay<'_, '_, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scheme = self.options.scheme.unwrap_or("bitcoin");
+        match self.options.address_case {
+            AddressCase::Lower => write!(f, "{}:{}", scheme, self.uri.address)?,
+            AddressCase::Upper => write!(f, "{}:{:#}", scheme, self.uri.address)?,
+        }
+        let canonical;
+        let amount = match (&self.amount, self.uri.amount, f.precision()) {
+            (Some(amount), Some(value), Some(precision)) => {
+                // rounding would make the URI request a different amount
+                if !fits_precision(value, precision) {
+                    return Err(fmt::Error);
+                }
+                // the precision takes precedence over the faithful form
+                match amount {
+                    Cow::Borrowed(_) => {
+                        canonical = value.display_in(Denomination::Bitcoin);
+                        Some(&canonical as &dyn fmt::Display)
+                    },
+                    Cow::Owned(amount) => Some(amount as &dyn fmt::Display),
+                }
+            },
+            (Some(amount), _, _) => Some(amount as &dyn fmt::Display),
+            (None, _, _) => None,
+        };
+        self.uri.fmt_params(f, amount, self.options)
+    }
+}
+
+impl<'a, T> Uri<'a, bitcoin::address::NetworkChecked, T>
+where
+    for<'b> &'b T: SerializeParams,
+{
+    /// Returns an object displaying the URI using `options`.
+    pub fn display_with<'u>(&'u self, options: &'u DisplayOptions) -> UriDisplay<'u, 'a, T> {
+        let amount = self
+            .amount
+            .filter(|amount| !(options.omit_zero_amount && *amount == bitcoin::Amount::ZERO))
+            .map(|amount| {
+                let faithful = match options.amount_form {
+                    AmountForm::Faithful => self.original_amount(),
+                    AmountForm::Canonical => None,
+                };
+                faithful.map_or_else(|| Cow::Owned(amount.display_in(Denomination::Bitcoin).to_string()), Cow::Borrowed)
+            });
+        UriDisplay { uri: self, options, amount }
+    }
+
+    /// Serializes the URI using `options`.
+    ///
+    /// This is a shorthand for `uri.display_with(&options).to_string()`.
+    pub fn to_string_with(&self, options: &DisplayOptions) -> String {
+        self.display_with(options).to_string()
+    }
+}
+
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Writes the serialized URI into `writer`.
+    ///
+    /// This is equivalent to `write!(writer, "{}", uri)`.
+    pub fn to_writer<W: fmt::Write>(&self, mut writer: W) -> fmt::Result {
+        write!(writer, "{}", self)
+    }
+
+    /// Appends the serialized URI to `buf`.
+    ///
+    /// Serializing many URIs into one buffer that is cleared between them avoids allocating a
+    /// new `String` for each of them, unlike `to_string`.
+    pub fn write_into(&self, buf: &mut String) {
+        self.to_writer(buf).expect("writing to String never fails");
+    }
+
+    /// Writes the serialized URI as UTF-8 into `writer`.
+    ///
+    /// This allows serializing directly into a file or a socket without an intermediate `String`.
+    /// Note that this performs many small writes so using a buffered writer is recommended.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn to_io_writer<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        write!(writer, "{}", self)
+    }
+
+    /// Serializes the URI into UTF-8 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // VecWriter never fails so just like `to_string()` this only panics if extras return error
+        self.to_writer(VecWriter(&mut bytes))
+            .expect("a Display implementation returned an error unexpectedly");
+        bytes
+    }
+
+    /// Serializes the URI into UTF-8 bytes unless it's longer than `max` bytes.
+    ///
+    /// This is useful when the output goes into a fixed-size buffer or a QR code with limited
+    /// capacity. Serialization stops as soon as the limit is exceeded so huge extras are not
+    /// serialized completely.
+    pub fn to_bytes_bounded(&self, max: usize) -> Result<Vec<u8>, TooLongError> {
+        let mut writer = BoundedWriter {
+            bytes: Vec::new(),
+            max,
+            exceeded: false,
+        };
+        match self.to_writer(&mut writer) {
+            Ok(()) => Ok(writer.bytes),
+            Err(_) if writer.exceeded => Err(TooLongError { max }),
+            Err(_) => panic!("a Display implementation returned an error unexpectedly"),
+        }
+    }
+
+    /// Serializes the URI after checking that the address is valid for `network`.
+    ///
+    /// This is a safety net against `assume_checked` being used incorrectly upstream - e.g. an
+    /// invoice-generation service can make sure it never hands out an address for another
+    /// network. The check is the same as the one performed by
+    /// [`Uri::require_network`](crate::Uri::require_network).
+    pub fn checked_to_string(&self, network: bitcoin::Network) -> Result<String, NetworkMismatchError> {
+        if self.address.as_unchecked().is_valid_for_network(network) {
+            Ok(self.to_string())
+        } else {
+            Err(NetworkMismatchError { network })
+        }
+    }
+
+    /// Returns a log-safe representation of the URI.
+    ///
+    /// The address is shortened to its first six and last four characters and the values of all
+    /// parameters except `amount` are replaced with `***`, keeping the keys in their order. The
+    /// result is meant for logs and telemetry only - it is **not** a valid URI and must not be
+    /// parsed or paid to.
+    pub fn redacted(&self) -> String {
+        use core::fmt::Write;
+
+        let address = self.address.to_string();
+        let mut redacted = if address.len() > 10 {
+            alloc::format!("bitcoin:{}...{}", &address[..6], &address[(address.len() - 4)..])
+        } else {
+            alloc::format!("bitcoin:{}", address)
+        };
+        let mut separator = '?';
+        let mut push = |key: &dyn fmt::Display, value: &dyn fmt::Display| {
+            // writing to a String never fails
+            let _ = write!(redacted, "{}{}={}", separator, key, value);
+            separator = '&';
+        };
+        if let Some(amount) = self.amount {
+            push(&"amount", &amount.display_in(Denomination::Bitcoin));
+        }
+        if self.label.is_some() {
+            push(&"label", &"***");
+        }
+        if self.message.is_some() {
+            push(&"message", &"***");
+        }
+        for (key, _) in self.extras.serialize_params() {
+            push(&key, &"***");
+        }
+        redacted
+    }
+}
+
+/// Error returned when the address is not valid for the network.
+///
+/// Returned by [`Uri::checked_to_string`] and [`Uri::set_network_unchecked_address`] and
+/// contained in [`ParseForNetworkError`](crate::de::ParseForNetworkError).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NetworkMismatchError {
+    network: bitcoin::Network,
+}
+
+impl NetworkMismatchError {
+    pub(crate) fn new(network: bitcoin::Network) -> Self {
+        NetworkMismatchError { network }
+    }
+
+    /// Returns the network the address was expected to be valid for.
+    pub fn network(&self) -> bitcoin::Network {
+        self.network
+    }
+}
+
+impl fmt::Display for NetworkMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the address is not valid for network {}", self.network)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for NetworkMismatchError {}
+
+/// Error returned when the serialized URI would exceed the requested length.
+///
+/// Returned by [`Uri::to_bytes_bounded`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TooLongError {
+    max: usize,
+}
+
+impl TooLongError {
+    /// Returns the maximum length in bytes that was exceeded.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl fmt::Display for TooLongError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the serialized URI is longer than {} bytes", self.max)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for TooLongError {}
+
+/// Checks that `key` can be used as a key of a parameter.
+///
+/// Keys are serialized verbatim so they must match the BIP21 grammar of `otherparam` keys: a
+/// non-empty sequence of RFC 3986 `unreserved` characters, `sub-delims` other than `&` and `=`,
+/// `:`, `@` or percent-encoded bytes. Other keys would panic or corrupt the output when
+/// displaying the URI.
+pub fn validate_key(key: &str) -> Result<(), InvalidKeyError> {
+    let error = |position| InvalidKeyError {
+        key: key.to_owned(),
+        position,
+    };
+    if key.is_empty() {
+        return Err(error(None));
+    }
+    let bytes = key.as_bytes();
+    let mut position = 0;
+    while position < bytes.len() {
+        match bytes[position] {
+            b'%' if bytes.len() > position + 2 && bytes[(position + 1)..(position + 3)].iter().all(u8::is_ascii_hexdigit) => position += 3,
+            byte if byte.is_ascii_alphanumeric() => position += 1,
+            b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b':' | b'@' => position += 1,
+            _ => return Err(error(Some(position))),
+        }
+    }
+    Ok(())
+}
+
+/// Error returned when a key of a parameter doesn't match the BIP21 grammar.
+///
+/// Returned by [`validate_key`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidKeyError {
+    key: String,
+    position: Option<usize>,
+}
+
+impl InvalidKeyError {
+    /// Returns the invalid key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the byte position of the first invalid character.
+    ///
+    /// Returns `None` if the key is empty or it's reserved for a standard parameter.
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// Creates the error for a key of a standard parameter used as a key of extras.
+    pub(crate) fn reserved(key: &str) -> Self {
+        InvalidKeyError {
+            key: key.to_owned(),
+            position: None,
+        }
+    }
+}
+
+impl fmt::Display for InvalidKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(position) => {
+                let c = self.key[position..].chars().next().expect("position is a char boundary");
+                write!(
+                    f,
+                    "the key '{}' contains invalid character '{}' at {}",
+                    self.key.escape_debug(),
+                    c.escape_debug(),
+                    position
+                )
+            },
+            None if self.key.is_empty() => write!(f, "the key is empty"),
+            None => write!(f, "the key '{}' is reserved for a standard parameter", self.key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for InvalidKeyError {}
+
+/// A difference between parsed input and its canonical form.
+///
+/// Returned by [`Uri::canonicalization_diff`].
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CanonChange {
+    /// The scheme is not `bitcoin` in lower case.
+    SchemeCaseNormalized,
+    /// The address is not in its default form, e.g. upper case bech32.
+    AddressNormalized,
+    /// Trailing zeros (or the decimal point) of the amount were removed.
+    AmountTrailingZerosStripped,
+    /// The amount was reformatted in some other way, e.g. leading zeros were removed.
+    AmountReformatted,
+    /// Percent-encoding of `unreserved` characters was removed.
+    RedundantEncodingRemoved,
+    /// Lowercase hex digits in percent-escapes were converted to uppercase.
+    EscapeCaseNormalized,
+    /// Characters that must be encoded were percent-encoded.
+    EncodingAdded,
+    /// Parameters were sorted.
+    ParamReordered,
+    /// The trailing `&` of the query was removed.
+    TrailingSeparatorRemoved,
+    /// The empty query (`?` without parameters) was removed.
+    EmptyQueryRemoved,
+    /// The fragment (from `#`) was removed.
+    FragmentRemoved,
+    /// A change not described by other variants, e.g. a legacy key being renamed by extras.
+    Other,
+}
+
+impl fmt::Display for CanonChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CanonChange::SchemeCaseNormalized => write!(f, "the scheme was converted to lower case"),
+            CanonChange::AddressNormalized => write!(f, "the address was normalized"),
+            CanonChange::AmountTrailingZerosStripped => write!(f, "trailing zeros of the amount were removed"),
+            CanonChange::AmountReformatted => write!(f, "the amount was reformatted"),
+            CanonChange::RedundantEncodingRemoved => write!(f, "needless percent-encoding was removed"),
+            CanonChange::EscapeCaseNormalized => write!(f, "percent-escapes were converted to upper case"),
+            CanonChange::EncodingAdded => write!(f, "missing percent-encoding was added"),
+            CanonChange::ParamReordered => write!(f, "the parameters were sorted"),
+            CanonChange::TrailingSeparatorRemoved => write!(f, "the trailing separator was removed"),
+            CanonChange::EmptyQueryRemoved => write!(f, "the empty query was removed"),
+            CanonChange::FragmentRemoved => write!(f, "the fragment was removed"),
+            CanonChange::Other => write!(f, "the URI was normalized"),
+        }
+    }
+}
+
+/// Appends written strings to a byte vector.
+struct VecWriter<'a>(&'a mut Vec<u8>);
+
+impl fmt::Write for VecWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Appends written strings to a byte vector failing once it would exceed `max` bytes.
+struct BoundedWriter {
+    bytes: Vec<u8>,
+    max: usize,
+    exceeded: bool,
+}
+
+impl fmt::Write for BoundedWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.bytes.len() + s.len() > self.max {
+            self.exceeded = true;
+            return Err(fmt::Error);
+        }
+        self.bytes.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<'u, T: DeserializeParams<'u>> Uri<'u, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Returns the retained input unless the fields were modified after parsing.
+    ///
+    /// The fields are public so [`Uri::original`] may describe a different URI. To detect it,
+    /// the fields are compared with their copy taken while parsing and the extras are compared
+    /// with the parameters they recognized. Modifications that don't change the displayed URI,
+    /// e.g. replacing a value with an equal one, are not considered modifications.
+    ///
+    /// Extras may serialize a parameter differently from the input, e.g. to normalize it. Only
+    /// then the input is parsed again and the result is compared with the URI using `Display`.
+    pub(crate) fn unmodified_original(&self) -> Option<&crate::de::Original<'u>> {
+        let original = self.original.as_ref()?;
+        let fields = &original.fields;
+        let param_eq = |param: &Option<Param<'_>>, parsed: &Option<Param<'_>>| match (param, parsed) {
+            (Some(param), Some(parsed)) => param.decoded_bytes() == parsed.decoded_bytes(),
+            (param, parsed) => param.is_none() && parsed.is_none(),
+        };
+        if self.address.as_unchecked() != &fields.address
+            || self.amount != fields.amount
+            || !param_eq(&self.label, &fields.label)
+            || !param_eq(&self.message, &fields.message)
+        {
+            return None;
+        }
+        let mut parsed_extras = fields
+            .extras
+            .iter()
+            .map(|(key, value)| (String::from(*key), value.decoded_bytes().into_owned()))
+            .collect::<Vec<_>>();
+        parsed_extras.sort_unstable();
+        if self.sorted_extras() == parsed_extras {
+            return Some(original);
+        }
+        let parsed = Uri::<'u, bitcoin::address::NetworkUnchecked, T>::parse_with_options(original.input, &original.options).ok()?;
+        Some(original).filter(|_| display_eq(self, &parsed.assume_checked().to_string()))
+    }
+
+    /// Returns `true` if the URI was parsed from a string in canonical form.
+    ///
+    /// This compares the input with [`to_canonical_string`](Self::to_canonical_string) so it
+    /// detects upper case scheme, needless percent-encoding, unsorted parameters and amounts
+    /// with trailing zeros, for instance. It can be used to detect URIs that would change when
+    /// re-serialized.
+    ///
+    /// The input is only retained if requested by
+    /// [`ParseOptions::retain_original`](crate::de::ParseOptions::retain_original). If the URI
+    /// doesn't retain input (e.g. it was constructed or parsed with the default options) or if
+    /// its fields were modified after parsing, this checks whether the output of `Display` is
+    /// canonical instead.
+    pub fn is_canonical(&self) -> bool {
+        let canonical = self.to_canonical_string();
+        match self.unmodified_original() {
+            Some(original) => original.normalized_input() == canonical,
+            None => display_eq(self, &canonical),
+        }
+    }
+
+    /// Describes how the input differs from the [canonical form](Self::to_canonical_string).
+    ///
+    /// This is a detailed version of [`is_canonical`](Self::is_canonical) for linters and
+    /// logging - it returns an empty vector exactly when `is_canonical` returns `true`. Each
+    /// kind of change is reported at most once, in order of appearance in the input. Like
+    /// `is_canonical`, this inspects the output of `Display` if the URI doesn't retain input or
+    /// if its fields were modified after parsing.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn canonicalization_diff(&self) -> Vec<CanonChange> {
+        let canonical = self.to_canonical_string();
+        let input = match self.unmodified_original() {
+            Some(original) => original.normalized_input(),
+            None => Cow::Owned(self.to_string()),
+        };
+        let input = &*input;
+        if input == canonical {
+            return Vec::new();
+        }
+        let mut changes = Vec::new();
+        let mut push = |change| {
+            if !changes.contains(&change) {
+                changes.push(change);
+            }
+        };
+        let raw = crate::raw::RawUri::parse(input).expect("the input of parsed URI has a scheme");
+        if raw.scheme() != "bitcoin" {
+            push(CanonChange::SchemeCaseNormalized);
+        }
+        if !display_eq(&self.address, raw.address()) {
+            push(CanonChange::AddressNormalized);
+        }
+        let mut previous: Option<(&str, Cow<'_, [u8]>)> = None;
+        let mut params = raw.params();
+        if matches!(raw.query(), Some(query) if query.ends_with('&')) {
+            params = &params[..(params.len() - 1)];
+            push(CanonChange::TrailingSeparatorRemoved);
+        }
+        for (key, value) in params.iter().copied() {
+            let value = value.unwrap_or("");
+            if key == "amount" {
+                let canonical_amount = self.amount.map(|amount| amount.display_in(Denomination::Bitcoin).to_string());
+                if canonical_amount.as_deref() != Some(value) {
+                    let stripped = value.trim_end_matches('0').trim_end_matches('.');
+                    if value.contains('.') && canonical_amount.as_deref() == Some(stripped) {
+                        push(CanonChange::AmountTrailingZerosStripped);
+                    } else {
+                        push(CanonChange::AmountReformatted);
+                    }
+                }
+            } else {
+                if crate::de::find_unnecessary_encoding(value).is_some() {
+                    push(CanonChange::RedundantEncodingRemoved);
+                }
+                if crate::de::has_lowercase_escape(value) {
+                    push(CanonChange::EscapeCaseNormalized);
+                }
+                let is_missing_encoding = value
+                    .split('%')
+                    .any(|part| !display_eq(percent_encoding_rfc3986::utf8_percent_encode(part, &ASCII_SET), part));
+                if is_missing_encoding {
+                    push(CanonChange::EncodingAdded);
+                }
+            }
+            let decoded = percent_encoding_rfc3986::percent_decode_str(value)
+                .map(<Cow<'_, [u8]>>::from)
+                .unwrap_or(Cow::Borrowed(value.as_bytes()));
+            if let Some((previous_key, previous_value)) = &previous {
+                if (*previous_key, &**previous_value) > (key, &*decoded) {
+                    push(CanonChange::ParamReordered);
+                }
+            }
+            previous = Some((key, decoded));
+        }
+        if raw.query() == Some("") {
+            push(CanonChange::EmptyQueryRemoved);
+        }
+        if raw.fragment().is_some() {
+            push(CanonChange::FragmentRemoved);
+        }
+        if changes.is_empty() {
+            changes.push(CanonChange::Other);
+        }
+        changes
+    }
+}
+
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Returns all parameters as keys and decoded values in the order used by `Display`.
+    pub(crate) fn decoded_params(&self) -> Vec<(String, Cow<'_, [u8]>)> {
+        let mut params = Vec::new();
+        if let Some(amount) = self.amount {
+            params.push((
+                "amount".to_owned(),
+                Cow::Owned(amount.display_in(Denomination::Bitcoin).to_string().into_bytes()),
+            ));
+        }
+        if let Some(label) = &self.label {
+            params.push(("label".to_owned(), label.decoded_bytes()));
+        }
+        if let Some(message) = &self.message {
+            params.push(("message".to_owned(), message.decoded_bytes()));
+        }
+        for (key, value) in self.extras.serialize_params() {
+            let key = key.to_string();
+            if key.contains('=') {
+                panic!("key '{}' contains equal sign", key);
+            }
+            params.push((key, Cow::Owned(value.to_decoded_bytes().into_owned())));
+        }
+        params
+    }
+
+    /// Serializes the URI in canonical form.
+    ///
+    /// The canonical form is fully determined by the decoded content of the URI so two URIs
+    /// differing only in formatting have the same canonical form:
+    ///
+    /// * The scheme is lower case and the address is displayed in its default form.
+    /// * The amount is in BTC without trailing zeros (the same as `Display`).
+    /// * Values are minimally percent-encoded (the same set as `Display`).
+    /// * All parameters, including `amount`, `label` and `message`, are sorted by key bytes.
+    ///   Parameters with equal keys are sorted by their decoded value bytes. Both comparisons
+    ///   are lexicographic, so the order is total and the output is deterministic even when
+    ///   keys repeat.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn to_canonical_string(&self) -> String {
+        self.canonical_string_from(self.decoded_params())
+    }
+
+    /// Returns SHA-256 of the [canonical form](Self::to_canonical_string) of the URI.
+    ///
+    /// URIs differing only in formatting have the same hash so it can be used as a
+    /// content-addressable identifier, e.g. to deduplicate or cache payment requests.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn hash_canonical(&self) -> [u8; 32] {
+        use bitcoin::hashes::{sha256, Hash};
+
+        sha256::Hash::hash(self.to_canonical_string().as_bytes()).to_byte_array()
+    }
+
+    /// Encodes the [canonical form](Self::to_canonical_string) of the URI as unpadded base64url.
+    ///
+    /// This is useful for embedding the URI in transports that disallow some characters, e.g.
+    /// into a path segment of another URL. Use [`Uri::from_base64url`] to decode it.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    #[cfg(feature = "base64")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "base64")))]
+    pub fn to_base64url(&self) -> String {
+        use base64::Engine;
+
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(self.to_canonical_string())
+    }
+
+    /// Sanitizes the URI making it safe to display and store.
+    ///
+    /// This decodes all parameters and strips control characters from them. The returned URI
+    /// owns the cleaned values and keeps the parameters of extras as
+    /// [`UnknownParams`](crate::extras::UnknownParams) in their order of serialization.
+    /// Displaying it encodes the values minimally, use
+    /// [`to_canonical_string`](Self::to_canonical_string) to also sort the parameters.
+    /// Intended for URIs coming from untrusted sources such as scanned QR codes.
+    ///
+    /// Fails if a value is not valid UTF-8 since such value can not be displayed.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    #[allow(clippy::type_complexity)]
+    pub fn sanitize(&self) -> Result<Uri<'static, bitcoin::address::NetworkChecked, crate::extras::UnknownParams<'static>>, SanitizeError> {
+        let mut uri = Uri::with_extras(self.address.clone(), crate::extras::UnknownParams::new());
+        uri.amount = self.amount;
+        for (key, value) in self.decoded_params() {
+            let value = match core::str::from_utf8(&value) {
+                Ok(value) => value.chars().filter(|c| !c.is_control()).collect::<String>(),
+                Err(error) => return Err(SanitizeError { parameter: key, error }),
+            };
+            match &*key {
+                "amount" => (),
+                "label" => uri.label = Some(value.into()),
+                "message" => uri.message = Some(value.into()),
+                _ => uri.extras.push(key, value),
+            }
+        }
+        Ok(uri)
+    }
+
+    /// Serializes the URI in canonical form using given decoded `params`.
+    fn canonical_string_from(&self, mut params: Vec<(String, Cow<'_, [u8]>)>) -> String {
+        params.sort_by(|(key_a, value_a), (key_b, value_b)| key_a.cmp(key_b).then_with(|| value_a.cmp(value_b)));
+        self.string_from(&params)
+    }
+
+    /// Serializes the URI with decoded `params` in given order.
+    fn string_from(&self, params: &[(String, Cow<'_, [u8]>)]) -> String {
+        use fmt::Write;
+
+        let mut result = String::new();
+        write!(result, "bitcoin:{}", self.address).expect("writing to String never fails");
+        write_decoded_params(&mut result, params);
+        result
+    }
+
+    /// Serializes the URI with parameters in the order given by `order`.
+    ///
+    /// Parameters (standard and extras) with keys listed in `order` come first, in the listed
+    /// order. Parameters with repeated keys keep their relative order. All parameters not
+    /// listed are appended afterwards in the order used by `Display`. Apart from the order, the
+    /// output is the same as that of `Display`.
+    ///
+    /// This is useful to match a specific layout expected by a picky third-party parser.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn to_string_with_order(&self, order: &[&str]) -> String {
+        let mut params = self.decoded_params();
+        // stable sort keeps the `Display` order of unlisted and repeated keys
+        params.sort_by_key(|(key, _)| order.iter().position(|listed| listed == key).unwrap_or(order.len()));
+        self.string_from(&params)
+    }
+
+    /// Returns a snapshot of all decoded parameters.
+    ///
+    /// This is intended for logging and telemetry. Values that are not valid UTF-8 are
+    /// represented as `hex:` followed by lower case hex of the decoded bytes. If a key repeats
+    /// only its first value is kept.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn params_map(&self) -> BTreeMap<String, String> {
+        use fmt::Write;
+
+        let mut map = BTreeMap::new();
+        for (key, value) in self.decoded_params() {
+            if map.contains_key(&key) {
+                continue;
+            }
+            let value = match String::from_utf8(value.into_owned()) {
+                Ok(value) => value,
+                Err(error) => {
+                    let mut hex = String::from("hex:");
+                    for byte in error.as_bytes() {
+                        write!(hex, "{:02x}", byte).expect("writing to String never fails");
+                    }
+                    hex
+                },
+            };
+            map.insert(key, value);
+        }
+        map
+    }
+
+    /// Serializes the URI in the most compact form for QR codes.
+    ///
+    /// This performs these optimizations at once:
+    ///
+    /// * The address is upper case if it's segwit, like in the alternate form (`{:#}`), so that
+    ///   it can be encoded using the alphanumeric mode of QR codes. The scheme stays lower case
+    ///   for compatibility with wallets matching it case-sensitively, see [`Uri`].
+    /// * Values are minimally percent-encoded and the amount has no trailing zeros (the same as
+    ///   `Display`).
+    /// * `label` and `message` with empty values are dropped.
+    ///
+    /// The transformations are lossless except for dropping empty `label` and `message` which
+    /// are treated the same as missing ones by wallets. Parameters of extras are never dropped
+    /// since their empty values may be meaningful.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn reserialize_minified(&self) -> String {
+        use fmt::Write;
+
+        let mut params = self.decoded_params();
+        // standard parameters come first, the amount is never empty
+        let standard = [self.amount.is_some(), self.label.is_some(), self.message.is_some()]
+            .iter()
+            .filter(|present| **present)
+            .count();
+        let mut i = 0;
+        params.retain(|(_, value)| {
+            i += 1;
+            i > standard || !value.is_empty()
+        });
+        let mut result = String::new();
+        write!(result, "bitcoin:{:#}", self.address).expect("writing to String never fails");
+        write_decoded_params(&mut result, &params);
+        result
+    }
+
+    /// Serializes the URI guaranteeing that the output is pure ASCII.
+    ///
+    /// This is intended for legacy transports and old QR code readers that can't handle other
+    /// characters. `Display` already percent-encodes non-ASCII characters of the standard
+    /// parameters and of the extras in this crate, so the output is the same as `Display` for
+    /// them. Values of custom extras writing unencoded characters in
+    /// [`SerializeValue::fmt_encoded`] get them percent-encoded, which doesn't change their
+    /// decoded values.
+    pub fn to_string_ascii_only(&self) -> String {
+        let string = self.to_string();
+        if string.is_ascii() {
+            return string;
+        }
+        // control characters are never written so only non-ASCII bytes get encoded
+        percent_encoding_rfc3986::utf8_percent_encode(&string, percent_encoding_rfc3986::CONTROLS).to_string()
+    }
+
+    /// Compares the URIs like `==` but ignoring the parameters with keys listed in `ignored`.
+    ///
+    /// This uses the same comparison as `==`, so `eq_ignoring(other, &[])` is the same as
+    /// `self == other`. The address can't be ignored.
+    pub fn eq_ignoring<'b>(&self, other: &Uri<'b, bitcoin::address::NetworkChecked, T>, ignored: &[&str]) -> bool {
+        self.cmp_fields(other, ignored) == core::cmp::Ordering::Equal
+    }
+
+    /// Compares the URIs ignoring their labels.
+    pub fn eq_ignore_label<'b>(&self, other: &Uri<'b, bitcoin::address::NetworkChecked, T>) -> bool {
+        self.eq_ignoring(other, &["label"])
+    }
+
+    /// Compares the URIs ignoring human annotations - labels and messages.
+    ///
+    /// This can be used to treat two URIs requesting the same payment (address, amount and
+    /// extras such as payjoin endpoint) as duplicates despite different descriptions.
+    pub fn eq_ignore_annotations<'b>(&self, other: &Uri<'b, bitcoin::address::NetworkChecked, T>) -> bool {
+        self.eq_ignoring(other, &["label", "message"])
+    }
+}
+
+/// Writes decoded `params` as the query of the URI.
+fn write_decoded_params(result: &mut String, params: &[(String, Cow<'_, [u8]>)]) {
+    use fmt::Write;
+
+    for (i, (key, value)) in params.iter().enumerate() {
+        let separator = if i == 0 { '?' } else { '&' };
+        write!(
+            result,
+            "{}{}={}",
+            separator,
+            EncodedKey(key),
+            percent_encoding_rfc3986::percent_encode(value, &ASCII_SET)
+        )
+        .expect("writing to String never fails");
+    }
+}
+
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Compares the fields except the parameters with keys listed in `ignored`, the parameters
+    /// of extras are compared sorted like in the [canonical form](Self::to_canonical_string).
+    fn cmp_fields(&self, other: &Uri<'_, bitcoin::address::NetworkChecked, T>, ignored: &[&str]) -> core::cmp::Ordering {
+        let decoded = |key: &str, param: &Option<Param<'_>>| -> Option<Vec<u8>> {
+            if ignored.contains(&key) {
+                return None;
+            }
+            param.as_ref().map(|param| param.decoded_bytes().into_owned())
+        };
+        let amount = |amount: Option<bitcoin::Amount>| amount.filter(|_| !ignored.contains(&"amount"));
+        let extras = |uri: &Uri<'_, bitcoin::address::NetworkChecked, T>| {
+            let mut extras = uri.sorted_extras();
+            extras.retain(|(key, _)| !ignored.contains(&key.as_str()));
+            extras
+        };
+
+        self.address
+            .cmp(&other.address)
+            .then_with(|| amount(self.amount).cmp(&amount(other.amount)))
+            .then_with(|| decoded("label", &self.label).cmp(&decoded("label", &other.label)))
+            .then_with(|| decoded("message", &self.message).cmp(&decoded("message", &other.message)))
+            .then_with(|| extras(self).cmp(&extras(other)))
+    }
+
+    /// Returns the parameters of extras as keys and decoded values sorted by key and value bytes.
+    pub(crate) fn sorted_extras(&self) -> Vec<(String, Vec<u8>)> {
+        let mut params = self
+            .extras
+            .serialize_params()
+            .map(|(key, value)| (key.to_string(), value.to_decoded_bytes().into_owned()))
+            .collect::<Vec<_>>();
+        params.sort_unstable();
+        params
+    }
+}
+
+/// Compares the address, amount, label, message and extras of the URIs.
+///
+/// Values are compared decoded, so URIs differing only in formatting, e.g. the case of the
+/// scheme, percent-encoding or the order of parameters, are equal. This agrees with
+/// [`Uri::to_canonical_string`] and [`Uri::hash_canonical`].
+impl<'b, T> PartialEq<Uri<'b, bitcoin::address::NetworkChecked, T>> for Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    fn eq(&self, other: &Uri<'b, bitcoin::address::NetworkChecked, T>) -> bool {
+        self.cmp_fields(other, &[]) == core::cmp::Ordering::Equal
+    }
+}
+
+impl<T> Eq for Uri<'_, bitcoin::address::NetworkChecked, T> where for<'a> &'a T: SerializeParams {}
+
+/// Orders the URIs by address, then amount, label, message and the parameters of extras.
+///
+/// The formatting of the input doesn't affect the order. Note that this is not an order by
+/// payment value since the address is compared first.
+impl<'b, T> PartialOrd<Uri<'b, bitcoin::address::NetworkChecked, T>> for Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    fn partial_cmp(&self, other: &Uri<'b, bitcoin::address::NetworkChecked, T>) -> Option<core::cmp::Ordering> {
+        Some(self.cmp_fields(other, &[]))
+    }
+}
+
+/// Orders the URIs by address, then amount, label, message and the parameters of extras.
+impl<T> Ord for Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.cmp_fields(other, &[])
+    }
+}
+
+/// Error returned by [`Uri::sanitize`] when a value is not valid UTF-8.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub struct SanitizeError {
+    parameter: String,
+    error: core::str::Utf8Error,
+}
+
+impl SanitizeError {
+    /// Returns the key of the invalid parameter.
+    pub fn parameter(&self) -> &str {
+        &self.parameter
+    }
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the parameter '{}' is not valid UTF-8", self.parameter)
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for SanitizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,419 @@
+//! Declarative validation of URI parameters.
+//!
+//! BIP21 has only minimal rules. Applications such as payment gateways often have stricter
+//! business rules - e.g. the amount must be present and within bounds, only some parameters are
+//! allowed... [`ParamSpec`] describes such rules and [`Uri::validate_against`] checks them,
+//! collecting all violations.
+//!
+//! [`Uri::validate`] doesn't need any rules, it lists soft issues worth showing to the user,
+//! e.g. in an editor of URIs.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bitcoin::amount::Denomination;
+use core::fmt;
+use crate::de::{DeserializationError, DeserializationState, DeserializeParams, ParamKind, ParseOptions};
+use crate::ser::SerializeParams;
+use crate::Uri;
+
+/// Description of allowed parameters of the URI.
+///
+/// The spec applies to both standard parameters (`amount`, `label`, `message`) and extras.
+#[derive(Debug, Default, Clone)]
+pub struct ParamSpec {
+    params: Vec<(String, bool, ValueSpec)>,
+    deny_unlisted: bool,
+}
+
+impl ParamSpec {
+    /// Creates an empty spec which allows anything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Requires the parameter `key` to be present with value satisfying `value`.
+    pub fn required(mut self, key: impl Into<String>, value: ValueSpec) -> Self {
+        self.params.push((key.into(), true, value));
+        self
+    }
+
+    /// Allows the parameter `key` to be present, its value must satisfy `value`.
+    pub fn optional(mut self, key: impl Into<String>, value: ValueSpec) -> Self {
+        self.params.push((key.into(), false, value));
+        self
+    }
+
+    /// Rejects parameters not listed in the spec.
+    pub fn deny_unlisted(mut self, value: bool) -> Self {
+        self.deny_unlisted = value;
+        self
+    }
+}
+
+/// Constraints of a value of a parameter.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum ValueSpec {
+    /// Amount in BTC (the same format as the `amount` parameter) within inclusive bounds.
+    Amount {
+        /// The minimum allowed amount.
+        min: Option<bitcoin::Amount>,
+        /// The maximum allowed amount.
+        max: Option<bitcoin::Amount>,
+    },
+    /// Valid UTF-8 string with at most `max_len` characters.
+    String {
+        /// The maximum number of characters.
+        max_len: Option<usize>,
+    },
+    /// Boolean - one of `0`, `1`, `false` and `true`.
+    Bool,
+}
+
+impl ValueSpec {
+    /// Returns the name of the type used in violations.
+    fn type_name(&self) -> &'static str {
+        match self {
+            ValueSpec::Amount { .. } => "amount",
+            ValueSpec::String { .. } => "string",
+            ValueSpec::Bool => "bool",
+        }
+    }
+
+    /// Checks the decoded `value` returning violation of `key`, if any.
+    fn check(&self, key: &str, value: &[u8]) -> Option<SpecViolation> {
+        let invalid_type = || SpecViolation::InvalidType {
+            key: key.into(),
+            expected: self.type_name(),
+        };
+        let value = match core::str::from_utf8(value) {
+            Ok(value) => value,
+            Err(_) => return Some(invalid_type()),
+        };
+        let in_bounds = match self {
+            ValueSpec::Amount { min, max } => {
+                let amount = match bitcoin::Amount::from_str_in(value, Denomination::Bitcoin) {
+                    Ok(amount) => amount,
+                    Err(_) => return Some(invalid_type()),
+                };
+                !matches!(min, Some(min) if amount < *min) && !matches!(max, Some(max) if amount > *max)
+            },
+            ValueSpec::String { max_len } => !matches!(max_len, Some(max_len) if value.chars().count() > *max_len),
+            ValueSpec::Bool => match value {
+                "0" | "1" | "false" | "true" => true,
+                _ => return Some(invalid_type()),
+            },
+        };
+        if in_bounds {
+            None
+        } else {
+            Some(SpecViolation::OutOfBounds { key: key.into() })
+        }
+    }
+}
+
+/// A rule of [`ParamSpec`] violated by the URI.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SpecViolation {
+    /// A required parameter is missing.
+    Missing {
+        /// The key of the missing parameter.
+        key: String,
+    },
+    /// A parameter not listed in the spec is present while unlisted parameters are denied.
+    Unlisted {
+        /// The key of the unlisted parameter.
+        key: String,
+    },
+    /// The value of the parameter is not of the expected type.
+    InvalidType {
+        /// The key of the invalid parameter.
+        key: String,
+        /// The name of the expected type.
+        expected: &'static str,
+    },
+    /// The value of the parameter is not within bounds.
+    OutOfBounds {
+        /// The key of the invalid parameter.
+        key: String,
+    },
+}
+
+impl fmt::Display for SpecViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpecViolation::Missing { key } => write!(f, "the required parameter '{}' is missing", key),
+            SpecViolation::Unlisted { key } => write!(f, "the parameter '{}' is not allowed", key),
+            SpecViolation::InvalidType { key, expected } => write!(f, "the parameter '{}' is not a valid {}", key, expected),
+            SpecViolation::OutOfBounds { key } => write!(f, "the value of parameter '{}' is out of bounds", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for SpecViolation {}
+
+/// Extras accepting (and discarding) all parameters, including required ones.
+#[derive(Debug, Default)]
+struct AnyParams;
+
+impl DeserializeParams<'_> for AnyParams {
+    type DeserializationState = AnyParams;
+}
+
+impl DeserializationError for AnyParams {
+    type Error = core::convert::Infallible;
+}
+
+impl DeserializationState<'_> for AnyParams {
+    type Value = AnyParams;
+
+    fn is_param_known(&self, _key: &str) -> bool {
+        true
+    }
+
+    fn deserialize_temp(&mut self, _key: &str, _value: crate::Param<'_>) -> Result<ParamKind, core::convert::Infallible> {
+        Ok(ParamKind::Known)
+    }
+
+    fn finalize(self) -> Result<AnyParams, core::convert::Infallible> {
+        Ok(self)
+    }
+}
+
+/// Amounts above this are reported by [`Uri::validate`] as [`ValidationIssue::LargeAmount`].
+pub const LARGE_AMOUNT: bitcoin::Amount = bitcoin::Amount::from_sat(1_000 * 100_000_000);
+
+/// A soft issue of a URI found by [`Uri::validate`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// Parsing the URI reports the warning.
+    Warning(crate::de::Warning),
+    /// The value of the parameter is empty.
+    EmptyValue {
+        /// The key of the parameter.
+        key: String,
+    },
+    /// The decoded value of the parameter is not valid UTF-8.
+    NonUtf8 {
+        /// The key of the parameter.
+        key: String,
+    },
+    /// The amount is above [`LARGE_AMOUNT`].
+    LargeAmount,
+    /// The URI is not in [canonical form](Uri::to_canonical_string).
+    NonCanonical,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::Warning(warning) => fmt::Display::fmt(warning, f),
+            ValidationIssue::EmptyValue { key } => write!(f, "the value of parameter '{}' is empty", key),
+            ValidationIssue::NonUtf8 { key } => write!(f, "the value of parameter '{}' is not valid UTF-8", key),
+            ValidationIssue::LargeAmount => write!(
+                f,
+                "the amount is larger than {}",
+                LARGE_AMOUNT.display_in(Denomination::Bitcoin).show_denomination()
+            ),
+            ValidationIssue::NonCanonical => write!(f, "the URI is not in canonical form"),
+        }
+    }
+}
+
+impl<'u, T: DeserializeParams<'u>> Uri<'u, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Checks the URI for soft issues, returning all of them.
+    ///
+    /// Unlike parse errors, these don't make the URI invalid but they may indicate a mistake of
+    /// its producer. The issues are returned in this order: the [warnings](crate::de::Warning)
+    /// of parsing the input, empty and non-UTF-8 values in the order of parameters, large
+    /// amount and non-canonical form. The warnings come from parsing the input again with the
+    /// options it was parsed with. If the URI doesn't retain its input or its fields were
+    /// modified after parsing (see [`is_canonical`](Self::is_canonical)) the output of `Display`
+    /// is inspected instead, so the returned issues may change after the fields are modified.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let displayed;
+        let default_options;
+        let (input, options) = match self.unmodified_original() {
+            Some(original) => (original.input, &original.options),
+            None => {
+                displayed = self.to_string();
+                default_options = ParseOptions::new();
+                (displayed.as_str(), &default_options)
+            },
+        };
+        // unknown parameters are the business of the extras, not issues of the URI
+        let mut warnings = Vec::new();
+        let _ = Uri::<'_, bitcoin::address::NetworkUnchecked, AnyParams>::deserialize_raw(input, options, Some(&mut warnings), None);
+        let mut issues = warnings.into_iter().map(ValidationIssue::Warning).collect::<Vec<_>>();
+        for (key, value) in self.decoded_params() {
+            if value.is_empty() {
+                issues.push(ValidationIssue::EmptyValue { key });
+            } else if core::str::from_utf8(&value).is_err() {
+                issues.push(ValidationIssue::NonUtf8 { key });
+            }
+        }
+        if matches!(self.amount, Some(amount) if amount > LARGE_AMOUNT) {
+            issues.push(ValidationIssue::LargeAmount);
+        }
+        if !self.is_canonical() {
+            issues.push(ValidationIssue::NonCanonical);
+        }
+        issues
+    }
+}
+
+impl<T> Uri<'_, bitcoin::address::NetworkChecked, T>
+where
+    for<'a> &'a T: SerializeParams,
+{
+    /// Checks the parameters of the URI against `spec`.
+    ///
+    /// All parameters are checked and all violations are returned in the order of parameters
+    /// followed by the missing required parameters.
+    ///
+    /// **Warning**: this will panic if a key of extras contains `=` character!
+    pub fn validate_against(&self, spec: &ParamSpec) -> Result<(), Vec<SpecViolation>> {
+        let params = self.decoded_params();
+        let mut violations = Vec::new();
+        for (key, value) in &params {
+            match spec.params.iter().find(|(spec_key, _, _)| spec_key == key) {
+                Some((_, _, value_spec)) => violations.extend(value_spec.check(key, value)),
+                None if spec.deny_unlisted => violations.push(SpecViolation::Unlisted { key: key.clone() }),
+                None => (),
+            }
+        }
+        for (key, required, _) in &spec.params {
+            if *required && !params.iter().any(|(param_key, _)| param_key == key) {
+                violations.push(SpecViolation::Missing { key: key.clone() });
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParamSpec, SpecViolation, ValidationIssue, ValueSpec};
+    use crate::extras::UnknownParams;
+    use crate::Uri;
+    use alloc::string::ToString;
+    use core::convert::TryFrom;
+
+    fn spec() -> ParamSpec {
+        ParamSpec::new()
+            .required(
+                "amount",
+                ValueSpec::Amount {
+                    min: Some(bitcoin::Amount::from_sat(1000)),
+                    max: Some(bitcoin::Amount::ONE_BTC),
+                },
+            )
+            .optional("label", ValueSpec::String { max_len: Some(5) })
+            .optional("paid", ValueSpec::Bool)
+            .deny_unlisted(true)
+    }
+
+    #[test]
+    fn valid() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=0.5&label=Shop&paid=0";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.validate_against(&spec()), Ok(()));
+    }
+
+    #[test]
+    fn collects_all_violations() {
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=Coffee%20shop&paid=yes&foo=bar";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::try_from(input).unwrap().assume_checked();
+        let violations = uri.validate_against(&spec()).unwrap_err();
+        assert_eq!(
+            violations,
+            [
+                SpecViolation::OutOfBounds { key: "label".into() },
+                SpecViolation::InvalidType {
+                    key: "paid".into(),
+                    expected: "bool"
+                },
+                SpecViolation::Unlisted { key: "foo".into() },
+                SpecViolation::Missing { key: "amount".into() },
+            ]
+        );
+        assert_eq!(violations[3].to_string(), "the required parameter 'amount' is missing");
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=2";
+        let uri = Uri::<'_, _>::try_from(input).unwrap().assume_checked();
+        assert_eq!(
+            uri.validate_against(&spec()),
+            Err(alloc::vec![SpecViolation::OutOfBounds { key: "amount".into() }])
+        );
+    }
+
+    #[test]
+    fn validate() {
+        use crate::de::Warning;
+
+        let input = "BITCOIN:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=5000&label=&message=%41&x=";
+        let uri = Uri::<'_, _, UnknownParams<'_>>::parse_with_options(input, &crate::tests::retaining())
+            .unwrap()
+            .assume_checked();
+        let issues = uri.validate();
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::Warning(Warning::NonLowercaseScheme),
+                ValidationIssue::Warning(Warning::UnnecessaryEncoding { position: 69 }),
+                ValidationIssue::EmptyValue { key: "label".into() },
+                ValidationIssue::EmptyValue { key: "x".into() },
+                ValidationIssue::LargeAmount,
+                ValidationIssue::NonCanonical,
+            ]
+        );
+        assert_eq!(issues[4].to_string(), "the amount is larger than 1000 BTC");
+
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1&label=Shop";
+        let uri = Uri::<'_, _>::try_from(input).unwrap().assume_checked();
+        assert_eq!(uri.validate(), []);
+
+        let uri = uri.with_message(crate::Message::new("").unwrap());
+        assert_eq!(uri.validate(), [ValidationIssue::EmptyValue { key: "message".into() }]);
+
+        // the modified fields are inspected rather than the stale input
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?label=%41";
+        let mut uri = Uri::<'_, _>::parse_with_options(input, &crate::tests::retaining())
+            .unwrap()
+            .assume_checked();
+        assert_eq!(
+            uri.validate(),
+            [
+                ValidationIssue::Warning(Warning::UnnecessaryEncoding { position: 48 }),
+                ValidationIssue::NonCanonical,
+            ]
+        );
+        uri.label = None;
+        assert_eq!(uri.validate(), []);
+
+        // the input is parsed again with the same options
+        let input = "bitcoin:1andreas3batLhQa2FawWjeyjCqyBzypd?amount=1;message=%41";
+        let options = crate::tests::retaining().allow_semicolon_separator(true);
+        let uri = Uri::<'_, _>::parse_with_options(input, &options).unwrap().assume_checked();
+        assert_eq!(
+            uri.validate(),
+            [
+                ValidationIssue::Warning(Warning::UnnecessaryEncoding { position: 59 }),
+                ValidationIssue::NonCanonical,
+            ]
+        );
+    }
+}